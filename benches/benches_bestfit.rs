@@ -0,0 +1,49 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use rust_coinselect::{
+    algorithms::bestfit::select_coin_bestfit,
+    types::{CoinSelectionOpt, ExcessStrategy, FeeRate, OutputGroup},
+};
+
+fn benchmark_select_coin_bestfit(c: &mut Criterion) {
+    let inputs: Vec<OutputGroup> = (1..=1000)
+        .map(|i| OutputGroup {
+            value: i * 1000,
+            weight: 272,
+            input_count: 1,
+            creation_sequence: None,
+            ancestor_fee: 0,
+            ancestor_weight: 0,
+            block_height: None,
+        })
+        .collect();
+
+    let options = CoinSelectionOpt {
+        target_value: 2_500_000,
+        target_feerate: FeeRate::from_sat_per_wu(2.0),
+        long_term_feerate: Some(FeeRate::from_sat_per_wu(1.0)),
+        min_absolute_fee: 0,
+        base_weight: 44,
+        change_weight: 124,
+        change_cost: 300,
+        avg_input_weight: 272,
+        avg_output_weight: 124,
+        min_change_value: 1000,
+        excess_strategy: ExcessStrategy::ToChange,
+        max_inputs: None,
+        bnb_tries: None,
+        include_uneconomical: false,
+        max_excess: None,
+        max_tx_weight: None,
+        knapsack_iterations: None,
+        knapsack_stagnation: None,
+    };
+
+    c.bench_function("bestfit", |b| {
+        b.iter(|| {
+            let _ = select_coin_bestfit(black_box(&inputs), black_box(&options));
+        })
+    });
+}
+
+criterion_group!(benches, benchmark_select_coin_bestfit);
+criterion_main!(benches);
@@ -0,0 +1,122 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use rust_coinselect::{
+    algorithms::bnb::select_coin_bnb,
+    types::{CoinSelectionOpt, ExcessStrategy, FeeRate, OutputGroup},
+};
+
+fn benchmark_select_coin_bnb(c: &mut Criterion) {
+    // A large wallet, sorted by reference and searched by position rather than copied
+    let inputs: Vec<OutputGroup> = (1..=50_000)
+        .map(|i| OutputGroup {
+            value: (i * 7919) % 100_000 + 1000,
+            weight: 272,
+            input_count: 1,
+            creation_sequence: Some(i as u32),
+            ancestor_fee: 0,
+            ancestor_weight: 0,
+            block_height: None,
+        })
+        .collect();
+
+    let options = CoinSelectionOpt {
+        target_value: 2_500_000,
+        target_feerate: FeeRate::from_sat_per_wu(2.0),
+        long_term_feerate: Some(FeeRate::from_sat_per_wu(1.0)),
+        min_absolute_fee: 0,
+        base_weight: 44,
+        change_weight: 124,
+        change_cost: 300,
+        avg_input_weight: 272,
+        avg_output_weight: 124,
+        min_change_value: 1000,
+        excess_strategy: ExcessStrategy::ToChange,
+        max_inputs: None,
+        bnb_tries: None,
+        include_uneconomical: false,
+        max_excess: None,
+        max_tx_weight: None,
+        knapsack_iterations: None,
+        knapsack_stagnation: None,
+    };
+    // A single try leaves the sorting and the precomputed effective values as the bulk of the work
+    let setup_options = CoinSelectionOpt {
+        bnb_tries: Some(1),
+        ..options.clone()
+    };
+
+    let mut group = c.benchmark_group("select_coin_bnb");
+    group.sample_size(10);
+    group.bench_function("50000 inputs", |b| {
+        b.iter(|| {
+            let _ = select_coin_bnb(black_box(&inputs), black_box(&options));
+        })
+    });
+    group.bench_function("50000 inputs setup", |b| {
+        b.iter(|| {
+            let _ = select_coin_bnb(black_box(&inputs), black_box(&setup_options));
+        })
+    });
+    group.finish();
+}
+
+#[cfg(feature = "parallel")]
+fn benchmark_select_coin_bnb_parallel(c: &mut Criterion) {
+    use rust_coinselect::algorithms::bnb::select_coin_bnb_parallel;
+
+    // Even effective values never add up to an odd target, so both searches spend their whole budget
+    let inputs: Vec<OutputGroup> = (1..=5_000)
+        .map(|i| OutputGroup {
+            value: 2 * ((i * 7919) % 100_000 + 1000),
+            weight: 0,
+            input_count: 1,
+            creation_sequence: None,
+            ancestor_fee: 0,
+            ancestor_weight: 0,
+            block_height: None,
+        })
+        .collect();
+    let options = CoinSelectionOpt {
+        target_value: 2_500_001,
+        target_feerate: FeeRate::from_sat_per_wu(2.0),
+        long_term_feerate: Some(FeeRate::from_sat_per_wu(1.0)),
+        min_absolute_fee: 0,
+        base_weight: 0,
+        change_weight: 124,
+        change_cost: 0,
+        avg_input_weight: 272,
+        avg_output_weight: 124,
+        min_change_value: 1000,
+        excess_strategy: ExcessStrategy::ToChange,
+        max_inputs: None,
+        bnb_tries: None,
+        include_uneconomical: false,
+        max_excess: None,
+        max_tx_weight: None,
+        knapsack_iterations: None,
+        knapsack_stagnation: None,
+    };
+
+    let mut group = c.benchmark_group("select_coin_bnb_parallel");
+    group.sample_size(10);
+    group.bench_function("5000 inputs sequential", |b| {
+        b.iter(|| {
+            let _ = select_coin_bnb(black_box(&inputs), black_box(&options));
+        })
+    });
+    group.bench_function("5000 inputs parallel", |b| {
+        b.iter(|| {
+            let _ = select_coin_bnb_parallel(black_box(&inputs), black_box(&options));
+        })
+    });
+    group.finish();
+}
+
+#[cfg(not(feature = "parallel"))]
+fn benchmark_select_coin_bnb_parallel(_: &mut Criterion) {}
+
+criterion_group!(
+    benches,
+    benchmark_select_coin_bnb,
+    benchmark_select_coin_bnb_parallel
+);
+criterion_main!(benches);
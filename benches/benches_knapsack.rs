@@ -0,0 +1,92 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use rand::{rngs::StdRng, SeedableRng};
+use rust_coinselect::{
+    algorithms::knapsack::select_coin_knapsack_with_rng,
+    types::{CoinSelectionOpt, ExcessStrategy, FeeRate, OutputGroup},
+};
+
+fn benchmark_select_coin_knapsack_iterations(c: &mut Criterion) {
+    // The target takes a dozen coins, and the greedy pre-pass does not hit it exactly, so every iteration runs
+    let inputs: Vec<OutputGroup> = (1..=200)
+        .map(|i| OutputGroup {
+            value: i * 1000 + 1,
+            weight: 272,
+            input_count: 1,
+            creation_sequence: None,
+            ancestor_fee: 0,
+            ancestor_weight: 0,
+            block_height: None,
+        })
+        .collect();
+
+    let options = CoinSelectionOpt {
+        target_value: 2_500_000,
+        target_feerate: FeeRate::from_sat_per_wu(2.0),
+        long_term_feerate: Some(FeeRate::from_sat_per_wu(1.0)),
+        min_absolute_fee: 0,
+        base_weight: 44,
+        change_weight: 124,
+        change_cost: 300,
+        avg_input_weight: 272,
+        avg_output_weight: 124,
+        min_change_value: 1000,
+        excess_strategy: ExcessStrategy::ToChange,
+        max_inputs: None,
+        bnb_tries: None,
+        include_uneconomical: false,
+        max_excess: None,
+        max_tx_weight: None,
+        knapsack_iterations: None,
+        knapsack_stagnation: None,
+    };
+
+    let mut group = c.benchmark_group("knapsack_iterations");
+    for knapsack_iterations in [100, 1000] {
+        let options = CoinSelectionOpt {
+            knapsack_iterations: Some(knapsack_iterations),
+            ..options.clone()
+        };
+        group.bench_with_input(
+            BenchmarkId::from_parameter(knapsack_iterations),
+            &options,
+            |b, options| {
+                let mut rng = StdRng::seed_from_u64(0);
+                b.iter(|| {
+                    let _ = select_coin_knapsack_with_rng(
+                        black_box(&inputs),
+                        black_box(options),
+                        &mut rng,
+                    );
+                })
+            },
+        );
+    }
+    group.finish();
+
+    // Every pass against stopping after 100 passes without a better subset
+    let mut group = c.benchmark_group("knapsack_stagnation");
+    for knapsack_stagnation in [u32::MAX, 100] {
+        let options = CoinSelectionOpt {
+            knapsack_stagnation: Some(knapsack_stagnation),
+            ..options.clone()
+        };
+        group.bench_with_input(
+            BenchmarkId::from_parameter(knapsack_stagnation),
+            &options,
+            |b, options| {
+                let mut rng = StdRng::seed_from_u64(0);
+                b.iter(|| {
+                    let _ = select_coin_knapsack_with_rng(
+                        black_box(&inputs),
+                        black_box(options),
+                        &mut rng,
+                    );
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, benchmark_select_coin_knapsack_iterations);
+criterion_main!(benches);
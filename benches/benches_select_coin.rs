@@ -0,0 +1,53 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use rust_coinselect::{
+    selectcoin::select_coin,
+    types::{CoinSelectionOpt, ExcessStrategy, FeeRate, OutputGroup},
+};
+
+fn benchmark_select_coin(c: &mut Criterion) {
+    // A large wallet, the inputs are borrowed by every selection thread rather than cloned
+    let inputs: Vec<OutputGroup> = (1..=5000)
+        .map(|i| OutputGroup {
+            value: (i * 7919) % 100_000 + 1000,
+            weight: 272,
+            input_count: 1,
+            creation_sequence: Some(i as u32),
+            ancestor_fee: 0,
+            ancestor_weight: 0,
+            block_height: None,
+        })
+        .collect();
+
+    let options = CoinSelectionOpt {
+        target_value: 2_500_000,
+        target_feerate: FeeRate::from_sat_per_wu(2.0),
+        long_term_feerate: Some(FeeRate::from_sat_per_wu(1.0)),
+        min_absolute_fee: 0,
+        base_weight: 44,
+        change_weight: 124,
+        change_cost: 300,
+        avg_input_weight: 272,
+        avg_output_weight: 124,
+        min_change_value: 1000,
+        excess_strategy: ExcessStrategy::ToChange,
+        max_inputs: None,
+        bnb_tries: None,
+        include_uneconomical: false,
+        max_excess: None,
+        max_tx_weight: None,
+        knapsack_iterations: None,
+        knapsack_stagnation: None,
+    };
+
+    let mut group = c.benchmark_group("select_coin");
+    group.sample_size(10);
+    group.bench_function("5000 inputs", |b| {
+        b.iter(|| {
+            let _ = select_coin(black_box(&inputs), black_box(&options));
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, benchmark_select_coin);
+criterion_main!(benches);
@@ -0,0 +1,81 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rust_coinselect::{
+    algorithms::srd::select_coin_srd_best_of_with_rng,
+    types::{CoinSelectionOpt, ExcessStrategy, FeeRate, OutputGroup},
+};
+
+fn benchmark_select_coin_srd_best_of(c: &mut Criterion) {
+    let mut pool_rng = StdRng::seed_from_u64(0);
+    let inputs: Vec<OutputGroup> = (0..1000)
+        .map(|_| OutputGroup {
+            value: pool_rng.gen_range(1000..1_000_000),
+            weight: 272,
+            input_count: 1,
+            creation_sequence: None,
+            ancestor_fee: 0,
+            ancestor_weight: 0,
+            block_height: None,
+        })
+        .collect();
+
+    let options = CoinSelectionOpt {
+        target_value: 5_000_000,
+        target_feerate: FeeRate::from_sat_per_wu(5.0),
+        long_term_feerate: Some(FeeRate::from_sat_per_wu(2.5)),
+        min_absolute_fee: 0,
+        base_weight: 168,
+        change_weight: 124,
+        change_cost: 1000,
+        avg_input_weight: 272,
+        avg_output_weight: 124,
+        min_change_value: 1000,
+        excess_strategy: ExcessStrategy::ToChange,
+        max_inputs: None,
+        bnb_tries: None,
+        include_uneconomical: false,
+        max_excess: None,
+        max_tx_weight: None,
+        knapsack_iterations: None,
+        knapsack_stagnation: None,
+    };
+
+    // Criterion only measures the time, the waste saved by the extra draws is reported once up front
+    for draws in [1, 8] {
+        let mut rng = StdRng::seed_from_u64(1);
+        let runs = 200;
+        let total_waste: i64 = (0..runs)
+            .map(|_| {
+                select_coin_srd_best_of_with_rng(&inputs, &options, draws, &mut rng)
+                    .unwrap()
+                    .waste
+                    .0
+            })
+            .sum();
+        println!(
+            "srd_best_of/{}: mean waste {} sats over {} runs",
+            draws,
+            total_waste / runs,
+            runs
+        );
+    }
+
+    let mut group = c.benchmark_group("srd_best_of");
+    for draws in [1, 8] {
+        group.bench_with_input(BenchmarkId::from_parameter(draws), &draws, |b, &draws| {
+            let mut rng = StdRng::seed_from_u64(0);
+            b.iter(|| {
+                let _ = select_coin_srd_best_of_with_rng(
+                    black_box(&inputs),
+                    black_box(&options),
+                    draws,
+                    &mut rng,
+                );
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, benchmark_select_coin_srd_best_of);
+criterion_main!(benches);
@@ -11,7 +11,7 @@ use bitcoin::{
 };
 use rust_coinselect::{
     selectcoin::select_coin,
-    types::{CoinSelectionOpt, ExcessStrategy, OutputGroup},
+    types::{CoinSelectionOpt, ExcessStrategy, FeeRate, OutputGroup},
     utils::{calculate_base_weight_btc, calculate_fee},
 };
 use std::str::FromStr;
@@ -104,9 +104,10 @@ fn main() {
     };
 
     // Prepare CoinSelectionOpt
-    let long_term_feerate = 10.0;
+    // Feerates are quoted in sats per vbyte, the options take them per weight unit
+    let long_term_feerate = FeeRate::from_sat_per_vb(10.0);
     let change_weight = change_output.weight().to_wu();
-    let change_cost = calculate_fee(change_weight, long_term_feerate);
+    let change_cost = calculate_fee(change_weight, *long_term_feerate);
     let target_weight = target_output.weight().to_wu();
     let avg_output_weight = (change_weight + target_weight) / 2;
     let avg_input_weight = inputs
@@ -116,35 +117,30 @@ fn main() {
         / inputs.len() as u64;
 
     // Create coin selection options
-    let coin_selection_option = CoinSelectionOpt {
-        target_value: target,
-        target_feerate: 15.0,
-        long_term_feerate: Some(long_term_feerate),
-        min_absolute_fee: 4000,
-        base_weight: calculate_base_weight_btc(target_weight + change_weight),
-        change_weight,
-        change_cost,
-        avg_input_weight,
-        avg_output_weight,
-        min_change_value: 100,
-        excess_strategy: ExcessStrategy::ToChange,
-    };
+    let coin_selection_option = CoinSelectionOpt::builder()
+        .with_target_value(target)
+        .with_target_feerate(FeeRate::from_sat_per_vb(15.0))
+        .with_long_term_feerate(long_term_feerate)
+        .with_min_absolute_fee(4000)
+        .with_base_weight(calculate_base_weight_btc(target_weight + change_weight))
+        .with_change_weight(change_weight)
+        .with_change_cost(change_cost)
+        .with_avg_input_weight(avg_input_weight)
+        .with_avg_output_weight(avg_output_weight)
+        .with_min_change_value(100)
+        .with_excess_strategy(ExcessStrategy::ToChange)
+        .build()
+        .expect("target feerate is positive");
 
     // Mock values for each input
     let mock_input_values = vec![100_000, 3_000_000, 1_000_000, 500_000];
 
     // Create OutputGroups from each input
     let utxos: Vec<OutputGroup> = inputs
-        .clone()
-        .into_iter()
+        .iter()
         .zip(mock_input_values)
-        .map(|(input, value)| OutputGroup {
-            // In practice, the details about the UTXO, used as input, is obtained from the UTXO set maintained by a node.
-            value,
-            weight: input.segwit_weight().to_wu(),
-            input_count: 1,
-            creation_sequence: None,
-        })
+        // In practice, the details about the UTXO, used as input, is obtained from the UTXO set maintained by a node.
+        .map(|(input, value)| OutputGroup::from_txin(input, Amount::from_sat(value)))
         .collect();
 
     // Perform selection among the available UTXOs, create final transaction
@@ -152,7 +148,7 @@ fn main() {
     log_utxos(&utxos);
     match select_coin(&utxos, &coin_selection_option) {
         Ok(selection) => {
-            println!("Selected utxo index and waste metrics are: {:?}", selection);
+            println!("The selection is: {}", selection);
 
             let selected_txins: Vec<TxIn> = selection
                 .selected_inputs
@@ -168,23 +164,18 @@ fn main() {
             println!("The selected OutputGroups are......");
             log_utxos(&selected_output_groups);
 
-            let selected_txins_value: u64 = selection
-                .selected_inputs
-                .iter()
-                .map(|&i| utxos[i].value)
-                .sum();
+            let selected_txins_value = selection.selected_value;
 
-            // Create a transaction for the purpose of calculating the fee using the selected inputs
             let mut tx = Transaction {
                 version: transaction::Version::TWO,
                 lock_time: LockTime::ZERO,
                 input: selected_txins,
                 output: vec![target_output, change_output.clone()],
             };
-            let fee = calculate_fee(
-                tx.weight().to_wu(),
-                coin_selection_option.long_term_feerate.unwrap(),
-            );
+            // Pay the fee the selection was made for
+            let fee = selection
+                .estimated_fee
+                .max(coin_selection_option.min_absolute_fee);
 
             // update the change output with the actual change value
             let change_value = selected_txins_value - (target + fee);
@@ -0,0 +1,372 @@
+use crate::{
+    algorithms::srd::select_in_order,
+    types::{CoinSelectionOpt, SelectionError, SelectionOutput, WasteMetric, WeightedUtxo},
+    utils::{
+        calculate_change, calculate_selection_fee, calculate_waste, calculate_waste_detailed,
+        exceeds_max_inputs, exceeds_max_tx_weight, validate_feerate,
+    },
+};
+use alloc::vec::Vec;
+use num_traits::Float;
+#[cfg(feature = "std")]
+use rand::thread_rng;
+use rand::{seq::SliceRandom, Rng};
+
+/// Default number of moves [`select_coin_annealing`] proposes.
+pub const ANNEALING_ITERATIONS: u32 = 10_000;
+
+/// Performs coin selection via simulated annealing over the waste metric.
+///
+/// Uses [`ANNEALING_ITERATIONS`], see [`select_coin_annealing_with_iterations`].
+#[cfg(feature = "std")]
+pub fn select_coin_annealing<T: WeightedUtxo>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+) -> Result<SelectionOutput, SelectionError> {
+    select_coin_annealing_with_iterations(inputs, options, ANNEALING_ITERATIONS)
+}
+
+/// Performs coin selection via simulated annealing over the waste metric.
+///
+/// The search starts from a single random draw, then proposes `iterations` random moves, each adding an input,
+/// removing one, or swapping a selected input for an unselected one. Moves leaving the target, the fee and
+/// `min_change_value` uncovered, or spending more than `max_inputs` inputs or `max_tx_weight`, are rejected. A move
+/// lowering the waste is always accepted, and a move raising it by `delta` with probability
+/// `exp(-delta / temperature)`, where the temperature decays linearly from the waste of the starting selection down
+/// to zero. The lowest-waste selection seen is returned, so more iterations trade time for a better selection on
+/// large pools.
+///
+/// Returns `InsufficientFunds` if the inputs can not cover the target, and `ArithmeticOverflow` if the values of the
+/// starting selection or the target do not sum within a `u64`, see [`validate_target`](crate::utils::validate_target).
+#[cfg(feature = "std")]
+pub fn select_coin_annealing_with_iterations<T: WeightedUtxo>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+    iterations: u32,
+) -> Result<SelectionOutput, SelectionError> {
+    select_coin_annealing_with_rng(inputs, options, iterations, &mut thread_rng())
+}
+
+/// Performs coin selection via simulated annealing, drawing from `rng`, see
+/// [`select_coin_annealing_with_iterations`].
+pub fn select_coin_annealing_with_rng<T: WeightedUtxo, R: Rng>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+    iterations: u32,
+    rng: &mut R,
+) -> Result<SelectionOutput, SelectionError> {
+    validate_feerate(*options.target_feerate)?;
+    let mut search = Annealing::new(inputs, options, rng)?;
+    let initial_temperature = search.best_waste.unsigned_abs().max(1) as f64;
+    for iteration in 0..iterations {
+        let temperature = initial_temperature * (1.0 - iteration as f64 / iterations as f64);
+        search.step(temperature, rng);
+    }
+    Ok(search.best_output())
+}
+
+/// A move of the search: the position in `selected` of the input to remove, and the position in `unselected` of
+/// the input to add.
+type Move = (Option<usize>, Option<usize>);
+
+/// Accumulated value, weight and input count of a selection.
+type Totals = (u64, u64, usize);
+
+/// State of the annealing search.
+struct Annealing<'a, T> {
+    inputs: &'a [T],
+    options: &'a CoinSelectionOpt,
+    selected: Vec<usize>,
+    unselected: Vec<usize>,
+    accumulated_value: u64,
+    accumulated_weight: u64,
+    input_count: usize,
+    waste: i64,
+    best_selection: Vec<usize>,
+    best_waste: i64,
+}
+
+impl<'a, T: WeightedUtxo> Annealing<'a, T> {
+    /// Starts the search from a single random draw.
+    fn new<R: Rng>(
+        inputs: &'a [T],
+        options: &'a CoinSelectionOpt,
+        rng: &mut R,
+    ) -> Result<Self, SelectionError> {
+        let mut order: Vec<usize> = (0..inputs.len()).collect();
+        order.shuffle(rng);
+        let start = select_in_order(inputs, options, order)?;
+
+        let selected = start.selected_inputs;
+        let unselected = (0..inputs.len())
+            .filter(|index| !selected.contains(index))
+            .collect();
+        let accumulated_value = selected
+            .iter()
+            .try_fold(0u64, |total, &i| total.checked_add(inputs[i].value()))
+            .ok_or(SelectionError::ArithmeticOverflow)?;
+        let accumulated_weight = selected
+            .iter()
+            .try_fold(0u64, |total, &i| total.checked_add(inputs[i].weight()))
+            .ok_or(SelectionError::ArithmeticOverflow)?;
+        let input_count = selected.iter().map(|&i| inputs[i].input_count()).sum();
+        let waste = calculate_waste(
+            options,
+            accumulated_value,
+            accumulated_weight,
+            calculate_selection_fee(options, accumulated_weight),
+        );
+        Ok(Annealing {
+            inputs,
+            options,
+            best_selection: selected.clone(),
+            selected,
+            unselected,
+            accumulated_value,
+            accumulated_weight,
+            input_count,
+            waste,
+            best_waste: waste,
+        })
+    }
+
+    /// Proposes a random move, returning whether it was accepted.
+    fn step<R: Rng>(&mut self, temperature: f64, rng: &mut R) -> bool {
+        let proposed: Move = match rng.gen_range(0..3) {
+            0 => (None, self.random_position(&self.unselected, rng)),
+            1 => (self.random_position(&self.selected, rng), None),
+            _ => (
+                self.random_position(&self.selected, rng),
+                self.random_position(&self.unselected, rng),
+            ),
+        };
+        if proposed == (None, None) {
+            return false;
+        }
+        let Some((waste, totals)) = self.evaluate(proposed) else {
+            return false;
+        };
+
+        let accepted = waste <= self.waste
+            || rng.gen_bool(Float::exp(-((waste - self.waste) as f64) / temperature).min(1.0));
+        if accepted {
+            self.apply(proposed, totals);
+            self.waste = waste;
+            if waste < self.best_waste {
+                self.best_selection.clone_from(&self.selected);
+                self.best_waste = waste;
+            }
+        }
+        accepted
+    }
+
+    fn random_position<R: Rng>(&self, indices: &[usize], rng: &mut R) -> Option<usize> {
+        (!indices.is_empty()).then(|| rng.gen_range(0..indices.len()))
+    }
+
+    /// Returns the accumulated value, weight and input count after the move, or `None` if they do not fit in a `u64`.
+    fn totals_after(&self, proposed: Move) -> Option<Totals> {
+        let (mut value, mut weight, mut count) = (
+            self.accumulated_value,
+            self.accumulated_weight,
+            self.input_count,
+        );
+        if let Some(pos) = proposed.0 {
+            let input = &self.inputs[self.selected[pos]];
+            value -= input.value();
+            weight -= input.weight();
+            count -= input.input_count();
+        }
+        if let Some(pos) = proposed.1 {
+            let input = &self.inputs[self.unselected[pos]];
+            value = value.checked_add(input.value())?;
+            weight = weight.checked_add(input.weight())?;
+            count += input.input_count();
+        }
+        Some((value, weight, count))
+    }
+
+    /// Returns the waste and the totals after the move, or `None` if the move leads to an infeasible selection.
+    fn evaluate(&self, proposed: Move) -> Option<(i64, Totals)> {
+        let (value, weight, count) = self.totals_after(proposed)?;
+        let estimated_fee = calculate_selection_fee(self.options, weight);
+        let required = self
+            .options
+            .target_value
+            .checked_add(estimated_fee.max(self.options.min_absolute_fee))?
+            .checked_add(self.options.min_change_value)?;
+        if count == 0
+            || value < required
+            || exceeds_max_inputs(self.options, count)
+            || exceeds_max_tx_weight(self.options, weight)
+        {
+            return None;
+        }
+        let waste = calculate_waste(self.options, value, weight, estimated_fee);
+        Some((waste, (value, weight, count)))
+    }
+
+    /// Applies the move, `totals` being the ones returned by [`Self::evaluate`].
+    fn apply(&mut self, proposed: Move, totals: Totals) {
+        let (value, weight, count) = totals;
+        let removed = proposed.0.map(|pos| self.selected.swap_remove(pos));
+        if let Some(pos) = proposed.1 {
+            self.selected.push(self.unselected.swap_remove(pos));
+        }
+        if let Some(index) = removed {
+            self.unselected.push(index);
+        }
+        self.accumulated_value = value;
+        self.accumulated_weight = weight;
+        self.input_count = count;
+    }
+
+    fn best_output(mut self) -> SelectionOutput {
+        let accumulated_value: u64 = self
+            .best_selection
+            .iter()
+            .map(|&i| self.inputs[i].value())
+            .sum();
+        let accumulated_weight: u64 = self
+            .best_selection
+            .iter()
+            .map(|&i| self.inputs[i].weight())
+            .sum();
+        let estimated_fees = calculate_selection_fee(self.options, accumulated_weight);
+        let waste_breakdown = calculate_waste_detailed(
+            self.options,
+            accumulated_value,
+            accumulated_weight,
+            estimated_fees,
+        );
+        self.best_selection.sort_unstable();
+        SelectionOutput {
+            selected_inputs: self.best_selection,
+            waste: WasteMetric(waste_breakdown.total()),
+            waste_breakdown,
+            change_value: calculate_change(self.options, accumulated_value, estimated_fees),
+            estimated_fee: estimated_fees,
+            selected_value: accumulated_value,
+            selected_weight: accumulated_weight,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::Annealing;
+    #[cfg(feature = "std")]
+    use crate::{
+        algorithms::{
+            annealing::{select_coin_annealing, select_coin_annealing_with_rng},
+            srd::select_coin_srd,
+        },
+        types::SelectionError,
+    };
+    use crate::{
+        types::{CoinSelectionOpt, ExcessStrategy, FeeRate, OutputGroup},
+        utils::calculate_fee,
+    };
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    fn setup_output_groups(rng: &mut StdRng, count: usize) -> Vec<OutputGroup> {
+        (0..count)
+            .map(|_| OutputGroup {
+                value: rng.gen_range(1_000..50_000),
+                weight: rng.gen_range(50..300),
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            })
+            .collect()
+    }
+
+    fn setup_options(target_value: u64) -> CoinSelectionOpt {
+        CoinSelectionOpt {
+            target_value,
+            target_feerate: FeeRate::from_sat_per_wu(1.0),
+            long_term_feerate: Some(FeeRate::from_sat_per_wu(0.5)),
+            min_absolute_fee: 0,
+            base_weight: 10,
+            change_weight: 50,
+            change_cost: 10,
+            avg_input_weight: 20,
+            avg_output_weight: 10,
+            min_change_value: 500,
+            excess_strategy: ExcessStrategy::ToFee,
+            max_inputs: None,
+            bnb_tries: None,
+            include_uneconomical: false,
+            max_excess: None,
+            max_tx_weight: None,
+            knapsack_iterations: None,
+            knapsack_stagnation: None,
+        }
+    }
+
+    fn is_feasible(search: &Annealing<OutputGroup>) -> bool {
+        let fee = calculate_fee(search.accumulated_weight, *search.options.target_feerate);
+        search.accumulated_value
+            >= search.options.target_value + fee + search.options.min_change_value
+    }
+
+    #[test]
+    fn test_annealing_best_waste_never_increases() {
+        let mut rng = StdRng::seed_from_u64(5);
+        let inputs = setup_output_groups(&mut rng, 200);
+        let options = setup_options(300_000);
+        let mut search = Annealing::new(&inputs, &options, &mut rng).unwrap();
+        let initial_waste = search.best_waste;
+        let mut best_waste = search.best_waste;
+        let mut accepted_worse = 0;
+        for iteration in 0..5000 {
+            let temperature = initial_waste as f64 * (1.0 - iteration as f64 / 5000.0);
+            let waste_before = search.waste;
+            if search.step(temperature, &mut rng) && search.waste > waste_before {
+                accepted_worse += 1;
+            }
+            assert!(search.best_waste <= best_waste);
+            assert!(search.best_waste <= search.waste);
+            assert!(is_feasible(&search));
+            best_waste = search.best_waste;
+        }
+        assert!(best_waste < initial_waste);
+        assert!(accepted_worse > 0);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_annealing_is_reproducible() {
+        let mut rng = StdRng::seed_from_u64(9);
+        let inputs = setup_output_groups(&mut rng, 50);
+        let options = setup_options(100_000);
+        let result =
+            select_coin_annealing_with_rng(&inputs, &options, 2000, &mut StdRng::seed_from_u64(1))
+                .unwrap();
+        let repeated =
+            select_coin_annealing_with_rng(&inputs, &options, 2000, &mut StdRng::seed_from_u64(1))
+                .unwrap();
+        assert_eq!(result.selected_inputs, repeated.selected_inputs);
+
+        // Never worse than a single random draw on average
+        let srd_waste: i64 = (0..10)
+            .map(|_| select_coin_srd(&inputs, &options).unwrap().waste.0)
+            .sum();
+        let annealing_waste: i64 = (0..10)
+            .map(|_| select_coin_annealing(&inputs, &options).unwrap().waste.0)
+            .sum();
+        assert!(annealing_waste <= srd_waste);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_annealing_insufficient_funds() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let inputs = setup_output_groups(&mut rng, 5);
+        let result = select_coin_annealing(&inputs, &setup_options(1_000_000));
+        assert!(matches!(result, Err(SelectionError::InsufficientFunds)));
+    }
+}
@@ -0,0 +1,183 @@
+use crate::{
+    types::{CoinSelectionOpt, SelectionError, SelectionOutput, WasteMetric, WeightedUtxo},
+    utils::{
+        calculate_change, calculate_fee, calculate_selection_fee, calculate_waste_detailed,
+        effective_value, exceeds_max_inputs, exceeds_max_tx_weight, validate_feerate,
+        validate_target,
+    },
+};
+use alloc::vec::Vec;
+use core::cmp::Reverse;
+
+/// Performs coin selection using a greedy Best Fit algorithm.
+///
+/// At every step the remaining input whose effective value is closest to the still needed remainder,
+/// `target_value + min_change_value + fee(base_weight)` minus the effective value accumulated so far, is
+/// selected. Unlike Lowest Larger, which partitions the inputs once around the target, the remainder is
+/// recomputed after every pick. When two inputs are equally close, the larger one is picked.
+///
+/// Returns `InsufficientFunds` if the inputs can not cover the target, `NoSolutionFound` if covering it takes
+/// more than `max_inputs` inputs or goes over `max_tx_weight`, and `ArithmeticOverflow` if the values of the
+/// inputs or the target do not sum within a `u64`, see [`validate_target`].
+pub fn select_coin_bestfit<T: WeightedUtxo>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+) -> Result<SelectionOutput, SelectionError> {
+    validate_feerate(*options.target_feerate)?;
+    validate_target(inputs, options)?;
+    let target = options
+        .target_value
+        .checked_add(options.min_change_value)
+        .ok_or(SelectionError::ArithmeticOverflow)?;
+    let adjusted_target = target
+        .checked_add(calculate_fee(options.base_weight, *options.target_feerate))
+        .ok_or(SelectionError::ArithmeticOverflow)?;
+
+    // Inputs with zero effective value can never reduce the remainder.
+    let mut remaining_inputs: Vec<(usize, u64)> = inputs
+        .iter()
+        .enumerate()
+        .map(|(index, input)| (index, effective_value(input, *options.target_feerate)))
+        .filter(|&(_, value)| value > 0)
+        .collect();
+    let total_eff_value = remaining_inputs
+        .iter()
+        .try_fold(0u64, |total, &(_, value)| total.checked_add(value))
+        .ok_or(SelectionError::ArithmeticOverflow)?;
+    if total_eff_value < adjusted_target {
+        return Err(SelectionError::InsufficientFunds);
+    }
+
+    let mut accumulated_eff_value: u64 = 0;
+    let mut accumulated_value: u64 = 0;
+    let mut accumulated_weight: u64 = 0;
+    let mut input_count: usize = 0;
+    let mut selected_inputs: Vec<usize> = Vec::new();
+
+    while accumulated_eff_value < adjusted_target {
+        let remainder = adjusted_target - accumulated_eff_value;
+        let best_fit = match remaining_inputs
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, &(_, value))| (value.abs_diff(remainder), Reverse(value)))
+        {
+            Some((pos, _)) => pos,
+            None => return Err(SelectionError::InsufficientFunds),
+        };
+        let (index, value) = remaining_inputs.swap_remove(best_fit);
+        input_count += inputs[index].input_count();
+        if exceeds_max_inputs(options, input_count)
+            || exceeds_max_tx_weight(options, accumulated_weight + inputs[index].weight())
+        {
+            return Err(SelectionError::NoSolutionFound);
+        }
+        accumulated_eff_value = accumulated_eff_value
+            .checked_add(value)
+            .ok_or(SelectionError::ArithmeticOverflow)?;
+        accumulated_value = accumulated_value
+            .checked_add(inputs[index].value())
+            .ok_or(SelectionError::ArithmeticOverflow)?;
+        accumulated_weight = accumulated_weight
+            .checked_add(inputs[index].weight())
+            .ok_or(SelectionError::ArithmeticOverflow)?;
+        selected_inputs.push(index);
+    }
+
+    let estimated_fees = calculate_selection_fee(options, accumulated_weight);
+    if accumulated_value < target + estimated_fees.max(options.min_absolute_fee) {
+        return Err(SelectionError::InsufficientFunds);
+    }
+    let waste_breakdown = calculate_waste_detailed(
+        options,
+        accumulated_value,
+        accumulated_weight,
+        estimated_fees,
+    );
+    selected_inputs.sort_unstable();
+    Ok(SelectionOutput {
+        selected_inputs,
+        waste: WasteMetric(waste_breakdown.total()),
+        waste_breakdown,
+        change_value: calculate_change(options, accumulated_value, estimated_fees),
+        estimated_fee: estimated_fees,
+        selected_value: accumulated_value,
+        selected_weight: accumulated_weight,
+    })
+}
+
+#[cfg(test)]
+mod test {
+
+    use crate::{
+        algorithms::bestfit::select_coin_bestfit,
+        types::{CoinSelectionOpt, ExcessStrategy, FeeRate, OutputGroup, SelectionError},
+    };
+
+    fn setup_bestfit_output_groups(values: &[u64]) -> Vec<OutputGroup> {
+        values
+            .iter()
+            .map(|&value| OutputGroup {
+                value,
+                weight: 100,
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            })
+            .collect()
+    }
+
+    fn setup_options(target_value: u64) -> CoinSelectionOpt {
+        CoinSelectionOpt {
+            target_value,
+            target_feerate: FeeRate::from_sat_per_wu(1.0),
+            long_term_feerate: Some(FeeRate::from_sat_per_wu(0.4)),
+            min_absolute_fee: 0,
+            base_weight: 10,
+            change_weight: 50,
+            change_cost: 10,
+            avg_input_weight: 20,
+            avg_output_weight: 10,
+            min_change_value: 500,
+            excess_strategy: ExcessStrategy::ToChange,
+            max_inputs: None,
+            bnb_tries: None,
+            include_uneconomical: false,
+            max_excess: None,
+            max_tx_weight: None,
+            knapsack_iterations: None,
+            knapsack_stagnation: None,
+        }
+    }
+
+    #[test]
+    fn test_bestfit_adapts_to_remainder() {
+        // Effective values are 900, 2900, 4900 and 6900, the adjusted target is 5000 + 500 + 10 = 5510.
+        // 4900 is the closest to 5510, leaving 610 for which 900 is the closest.
+        let inputs = setup_bestfit_output_groups(&[1000, 3000, 5000, 7000]);
+        let result = select_coin_bestfit(&inputs, &setup_options(5000)).unwrap();
+        assert_eq!(result.selected_inputs, vec![0, 2]);
+
+        let options = CoinSelectionOpt {
+            max_inputs: Some(1),
+            ..setup_options(5000)
+        };
+        let result = select_coin_bestfit(&inputs, &options);
+        assert!(matches!(result, Err(SelectionError::NoSolutionFound)));
+    }
+
+    #[test]
+    fn test_bestfit_single_close_input() {
+        let inputs = setup_bestfit_output_groups(&[1000, 3000, 5000, 7000]);
+        let result = select_coin_bestfit(&inputs, &setup_options(6000)).unwrap();
+        assert_eq!(result.selected_inputs, vec![3]);
+    }
+
+    #[test]
+    fn test_bestfit_insufficient_funds() {
+        let inputs = setup_bestfit_output_groups(&[1000, 3000, 5000, 7000]);
+        let result = select_coin_bestfit(&inputs, &setup_options(16_000));
+        assert!(matches!(result, Err(SelectionError::InsufficientFunds)));
+    }
+}
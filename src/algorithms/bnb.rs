@@ -1,181 +1,803 @@
-use rand::{rngs::ThreadRng, thread_rng, Rng};
+use alloc::{vec, vec::Vec};
+#[cfg(feature = "std")]
+use rand::thread_rng;
+use rand::{rngs::StdRng, Rng, SeedableRng};
 
 use crate::{
-    types::{CoinSelectionOpt, OutputGroup, SelectionError, SelectionOutput, WasteMetric},
-    utils::{calculate_fee, calculate_waste, effective_value},
+    types::{
+        CoinSelectionOpt, ExcessStrategy, SelectionError, SelectionOutput, WasteMetric,
+        WeightedUtxo,
+    },
+    utils::{
+        calculate_change, calculate_fee, calculate_selection_fee, calculate_waste,
+        calculate_waste_detailed, economical_inputs, effective_value, exceeds_max_inputs,
+        exceeds_max_tx_weight, validate_feerate,
+    },
 };
 
-/// Struct MatchParameters encapsulates target_for_match, match_range, and target_feerate.
+/// Default number of nodes the search visits, see [`CoinSelectionOpt::bnb_tries`].
+pub const BNB_TOTAL_TRIES: u32 = 1_000_000;
+
+/// Number of the largest inputs whose inclusion or omission splits the search of [`select_coin_bnb_parallel`] into
+/// independent subtrees, 256 of them.
+#[cfg(feature = "parallel")]
+pub const BNB_PARALLEL_DEPTH: usize = 8;
+
+/// Struct MatchParameters encapsulates target_for_match, match_range, max_inputs and max_input_weight.
+///
+/// `max_input_weight` is the weight the selected inputs may add before the transaction, with its base and change
+/// weight, goes over `max_tx_weight`.
+/// `eff_value[depth]` holds the effective value of the sorted input at `depth`, computed once rather than at every
+/// node, and `remaining_eff_value[depth]` the sum of the effective values from `depth` onwards, used as the
+/// lookahead bound of the search. The inputs must have passed [`check_totals`], so that the sums do not overflow.
+/// `next_distinct_depth[depth]` is the depth the omission branch of the input at `depth` continues from, see
+/// [`next_distinct_depths`].
 #[derive(Debug)]
 struct MatchParameters {
     target_for_match: u64,
     match_range: u64,
-    target_feerate: f32,
+    max_inputs: Option<usize>,
+    max_input_weight: Option<u64>,
+    eff_value: Vec<u64>,
+    remaining_eff_value: Vec<u64>,
+    next_distinct_depth: Vec<usize>,
+}
+
+impl MatchParameters {
+    fn new<T: WeightedUtxo>(
+        inputs_in_desc_value: &[(usize, &T)],
+        target_for_match: u64,
+        match_range: u64,
+        target_feerate: f32,
+        max_inputs: Option<usize>,
+        max_input_weight: Option<u64>,
+    ) -> Self {
+        let eff_value: Vec<u64> = inputs_in_desc_value
+            .iter()
+            .map(|(_, input)| effective_value(*input, target_feerate))
+            .collect();
+        // Suffix sums, with a trailing zero
+        let mut remaining_eff_value = vec![0; eff_value.len() + 1];
+        for depth in (0..eff_value.len()).rev() {
+            remaining_eff_value[depth] = remaining_eff_value[depth + 1] + eff_value[depth];
+        }
+        let next_distinct_depth = next_distinct_depths(inputs_in_desc_value, &eff_value);
+        MatchParameters {
+            target_for_match,
+            match_range,
+            max_inputs,
+            max_input_weight,
+            eff_value,
+            remaining_eff_value,
+            next_distinct_depth,
+        }
+    }
+}
+
+/// Mutable state of the [`bnb`] walk, shared by every node it visits.
+#[derive(Debug)]
+struct SearchState {
+    /// Remaining number of nodes the search may visit.
+    bnb_tries: u32,
+    /// Selection with the smallest effective value overshooting the match range, with that effective value.
+    best_overshoot: Option<(Vec<usize>, u64)>,
+    /// Sum of the `input_count` of the currently selected inputs.
+    input_count: usize,
+    /// Sum of the weights of the currently selected inputs.
+    weight: u64,
+    /// Deepest node visited so far.
+    max_depth: usize,
+    /// Whether a node was left unexplored because the tries ran out.
+    budget_exhausted: bool,
+}
+
+impl SearchState {
+    fn new(bnb_tries: u32) -> Self {
+        SearchState {
+            bnb_tries,
+            best_overshoot: None,
+            input_count: 0,
+            weight: 0,
+            max_depth: 0,
+            budget_exhausted: false,
+        }
+    }
+}
+
+/// Statistics of a Branch and Bound search, see [`select_coin_bnb_with_stats`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct BnbStats {
+    /// Number of nodes whose branches were explored, at most `bnb_tries`.
+    pub tries: u32,
+    /// Depth of the deepest node visited, i.e. the number of inputs decided on.
+    pub max_depth: usize,
+    /// Number of changeless matches found, at most one as the search stops at the first.
+    pub matches: usize,
+    /// Whether the search ran out of tries before exploring the whole tree.
+    pub budget_exhausted: bool,
 }
 
 /// Perform Coinselection via Branch And Bound algorithm.
-pub fn select_coin_bnb(
-    inputs: &[OutputGroup],
+///
+/// The search is deterministic: inputs are sorted by descending effective value, ties broken by their index, and the
+/// inclusion branch is always explored before the omission branch, as in Algorithm 10 of Erhardt's thesis and Bitcoin
+/// Core's `SelectCoinsBnB`.
+/// Identical inputs and options always produce the same [`SelectionOutput`].
+/// Inputs costing more to spend than they are worth are skipped, unless `include_uneconomical` is set, and selections
+/// spending more than `max_inputs` inputs or heavier than `max_tx_weight` allows are pruned.
+///
+/// Returns `InsufficientFunds` without searching when the effective values of all the inputs sum below the
+/// match target, and `NoSolutionFound` when no selection lands within the match range.
+pub fn select_coin_bnb<T: WeightedUtxo>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+) -> Result<SelectionOutput, SelectionError> {
+    bnb_search(inputs, options, None::<&mut StdRng>)
+}
+
+/// Perform Coinselection via Branch And Bound algorithm, flipping a coin at every node to decide
+/// whether the inclusion or the omission branch is explored first.
+#[cfg(feature = "std")]
+pub fn select_coin_bnb_random<T: WeightedUtxo>(
+    inputs: &[T],
     options: &CoinSelectionOpt,
 ) -> Result<SelectionOutput, SelectionError> {
-    let mut selected_inputs: Vec<usize> = vec![];
+    select_coin_bnb_random_with_rng(inputs, options, &mut thread_rng())
+}
 
-    // Variable is mutable for decrement of bnb_tries for every iteration of fn bnb
-    let mut bnb_tries: u32 = 1_000_000;
+/// Same as [`select_coin_bnb_random`], with the coin flips drawn from `rng`.
+pub fn select_coin_bnb_random_with_rng<T: WeightedUtxo, R: Rng>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+    rng: &mut R,
+) -> Result<SelectionOutput, SelectionError> {
+    bnb_search(inputs, options, Some(rng))
+}
 
-    let rng = &mut thread_rng();
+/// Same as [`select_coin_bnb_random`], with the coin flips drawn from a [`StdRng`] seeded with `seed`.
+///
+/// Identical inputs, options and seed always produce the same [`SelectionOutput`], which makes the
+/// randomized exploration reproducible in tests.
+pub fn select_coin_bnb_seeded<T: WeightedUtxo>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+    seed: u64,
+) -> Result<SelectionOutput, SelectionError> {
+    select_coin_bnb_random_with_rng(inputs, options, &mut StdRng::seed_from_u64(seed))
+}
 
-    let cost_per_input = calculate_fee(options.avg_input_weight, options.target_feerate);
-    let cost_per_output = calculate_fee(options.avg_output_weight, options.target_feerate);
+/// Perform Coinselection via Branch And Bound algorithm, falling back to a selection with a change output
+/// when no changeless match exists.
+///
+/// The fallback searches for the lowest-waste selection whose effective value overshoots the match target
+/// by at least `min_change_value + change_cost`. Its waste always includes the `change_cost`, regardless of
+/// the configured [`ExcessStrategy`], so it can be compared fairly against the other algorithms.
+pub fn select_coin_bnb_with_change<T: WeightedUtxo>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+) -> Result<SelectionOutput, SelectionError> {
+    match select_coin_bnb(inputs, options) {
+        Err(SelectionError::NoSolutionFound) => {}
+        changeless => return changeless,
+    }
 
-    let match_parameters = MatchParameters {
-        target_for_match: options.target_value
-            + calculate_fee(options.base_weight, options.target_feerate),
-        match_range: cost_per_input + cost_per_output,
-        target_feerate: options.target_feerate,
+    let mut bnb_tries = options.bnb_tries.unwrap_or(BNB_TOTAL_TRIES);
+    let change_options = CoinSelectionOpt {
+        excess_strategy: ExcessStrategy::ToChange,
+        ..options.clone()
     };
+    let target_with_change = match_window(options)?
+        .0
+        .checked_add(options.min_change_value)
+        .and_then(|target| target.checked_add(options.change_cost))
+        .ok_or(SelectionError::ArithmeticOverflow)?;
 
-    let mut sorted_inputs: Vec<(usize, &OutputGroup)> = inputs.iter().enumerate().collect();
-    sorted_inputs.sort_by_key(|(_, input)| std::cmp::Reverse(input.value));
+    let mut sorted_inputs: Vec<(usize, &T)> = economical_inputs(inputs, options)
+        .into_iter()
+        .map(|index| (index, &inputs[index]))
+        .collect();
+    sorted_inputs.sort_by_key(|&(index, input)| {
+        (
+            core::cmp::Reverse(effective_value(input, *options.target_feerate)),
+            index,
+        )
+    });
+    check_totals(&sorted_inputs, *options.target_feerate)?;
 
-    let bnb_selected_coin = bnb(
+    let mut best: Option<(Vec<usize>, i64)> = None;
+    bnb_change(
         &sorted_inputs,
-        &mut selected_inputs,
-        0,
+        &mut vec![],
+        (0, 0, 0, 0),
         0,
         &mut bnb_tries,
+        target_with_change,
+        &change_options,
+        &mut best,
+    );
+    match best {
+        Some((selected_inputs, _)) => Ok(bnb_output(inputs, &change_options, selected_inputs)),
+        None => Err(SelectionError::NoSolutionFound),
+    }
+}
+
+/// Perform Coinselection via Branch And Bound algorithm, falling back to the smallest overshooting selection
+/// when no changeless match exists.
+///
+/// Every node of the search whose effective value exceeds the match range is a valid selection with change.
+/// The one with the smallest effective value seen during the walk is returned, so the caller always gets a
+/// usable selection as long as the inputs cover the target.
+pub fn select_coin_bnb_with_fallback<T: WeightedUtxo>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+) -> Result<SelectionOutput, SelectionError> {
+    validate_feerate(*options.target_feerate)?;
+    let mut state = SearchState::new(options.bnb_tries.unwrap_or(BNB_TOTAL_TRIES));
+    match bnb_walk(inputs, options, None::<&mut StdRng>, &mut state) {
+        Ok(selected_coin) => Ok(bnb_match_output(inputs, options, selected_coin)),
+        Err(SelectionError::NoSolutionFound) => match state.best_overshoot {
+            Some((selected_coin, _)) => Ok(bnb_output(inputs, options, selected_coin)),
+            None => Err(SelectionError::NoSolutionFound),
+        },
+        Err(err) => Err(err),
+    }
+}
+
+/// Same as [`select_coin_bnb`], also returning the [`BnbStats`] of the search.
+///
+/// Useful to tune `bnb_tries`: a search cut by the budget reports `budget_exhausted`, while one that explored
+/// the whole tree consumed fewer tries than allowed.
+pub fn select_coin_bnb_with_stats<T: WeightedUtxo>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+) -> (Result<SelectionOutput, SelectionError>, BnbStats) {
+    if let Err(err) = validate_feerate(*options.target_feerate) {
+        return (Err(err), BnbStats::default());
+    }
+    let bnb_tries = options.bnb_tries.unwrap_or(BNB_TOTAL_TRIES);
+    let mut state = SearchState::new(bnb_tries);
+    let selected_coin = bnb_walk(inputs, options, None::<&mut StdRng>, &mut state);
+    let stats = BnbStats {
+        tries: bnb_tries - state.bnb_tries,
+        max_depth: state.max_depth,
+        matches: usize::from(selected_coin.is_ok()),
+        budget_exhausted: state.budget_exhausted,
+    };
+    (
+        selected_coin.map(|selected_coin| bnb_match_output(inputs, options, selected_coin)),
+        stats,
+    )
+}
+
+/// Perform Coinselection via Branch And Bound algorithm, exploring the subtrees below the first
+/// [`BNB_PARALLEL_DEPTH`] inputs concurrently on the rayon thread pool.
+///
+/// Every combination of including or omitting the largest inputs roots an independent search, given an equal share
+/// of `bnb_tries`. Each search stops at its first match, and the match of lowest waste is returned, ties going to
+/// the subtree the sequential search visits first. The result does not depend on the scheduling of the tasks, so
+/// identical inputs and options always produce the same [`SelectionOutput`], though not necessarily the one of
+/// [`select_coin_bnb`].
+#[cfg(feature = "parallel")]
+pub fn select_coin_bnb_parallel<T: WeightedUtxo + Sync>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+) -> Result<SelectionOutput, SelectionError> {
+    use rayon::prelude::*;
+
+    validate_feerate(*options.target_feerate)?;
+    let (sorted_inputs, match_parameters) = bnb_prepare(inputs, options)?;
+    let split_depth = BNB_PARALLEL_DEPTH.min(sorted_inputs.len());
+    let subtrees = 1u32 << split_depth;
+    let bnb_tries = (options.bnb_tries.unwrap_or(BNB_TOTAL_TRIES) / subtrees).max(1);
+
+    // Subtrees are numbered in the order the inclusion-first search visits them, `collect` keeps that order
+    let matches: Vec<Option<Vec<usize>>> = (0..subtrees)
+        .into_par_iter()
+        .map(|subtree| {
+            let mut selected_inputs = Vec::new();
+            let mut acc_eff_value = 0;
+            let mut state = SearchState::new(bnb_tries);
+            for (depth, &input) in sorted_inputs.iter().enumerate().take(split_depth) {
+                if subtree & (1 << (split_depth - 1 - depth)) == 0 {
+                    include_input(input, &mut selected_inputs, &mut state);
+                    acc_eff_value += match_parameters.eff_value[depth];
+                }
+            }
+            bnb(
+                &sorted_inputs,
+                &mut selected_inputs,
+                acc_eff_value,
+                split_depth,
+                &mut state,
+                None::<&mut StdRng>,
+                &match_parameters,
+            )
+        })
+        .collect();
+
+    matches
+        .into_iter()
+        .flatten()
+        .map(|selected_coin| bnb_match_output(inputs, options, selected_coin))
+        .min_by_key(|selection| selection.waste)
+        .ok_or(SelectionError::NoSolutionFound)
+}
+
+fn bnb_search<T: WeightedUtxo, R: Rng>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+    rng: Option<&mut R>,
+) -> Result<SelectionOutput, SelectionError> {
+    validate_feerate(*options.target_feerate)?;
+    // State is mutable for decrement of bnb_tries at every node the walk expands
+    let mut state = SearchState::new(options.bnb_tries.unwrap_or(BNB_TOTAL_TRIES));
+    bnb_walk(inputs, options, rng, &mut state)
+        .map(|selected_coin| bnb_match_output(inputs, options, selected_coin))
+}
+
+/// Sorts the inputs by descending effective value and walks the search tree over them with [`bnb`].
+///
+/// Returns the errors of [`bnb_prepare`], and `NoSolutionFound` when the walk ends without a match.
+fn bnb_walk<T: WeightedUtxo, R: Rng>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+    rng: Option<&mut R>,
+    state: &mut SearchState,
+) -> Result<Vec<usize>, SelectionError> {
+    let (sorted_inputs, match_parameters) = bnb_prepare(inputs, options)?;
+    bnb(
+        &sorted_inputs,
+        &mut vec![],
+        0,
+        0,
+        state,
         rng,
         &match_parameters,
+    )
+    .ok_or(SelectionError::NoSolutionFound)
+}
+
+/// Inputs sorted by descending effective value, along with their index in the caller's slice.
+type SortedInputs<'a, T> = Vec<(usize, &'a T)>;
+
+/// Returns the economical inputs sorted by descending effective value, with the [`MatchParameters`] of the search over them.
+///
+/// Returns `InsufficientFunds` when all the inputs together can not reach the target, and `ArithmeticOverflow` when
+/// their totals do not fit in a `u64`.
+fn bnb_prepare<'a, T: WeightedUtxo>(
+    inputs: &'a [T],
+    options: &CoinSelectionOpt,
+) -> Result<(SortedInputs<'a, T>, MatchParameters), SelectionError> {
+    let (target_for_match, match_range) = match_window(options)?;
+
+    let mut sorted_inputs: Vec<(usize, &T)> = economical_inputs(inputs, options)
+        .into_iter()
+        .map(|index| (index, &inputs[index]))
+        .collect();
+    sorted_inputs.sort_by_key(|&(index, input)| {
+        (
+            core::cmp::Reverse(effective_value(input, *options.target_feerate)),
+            index,
+        )
+    });
+    check_totals(&sorted_inputs, *options.target_feerate)?;
+
+    let match_parameters = MatchParameters::new(
+        &sorted_inputs,
+        target_for_match,
+        match_range,
+        *options.target_feerate,
+        options.max_inputs,
+        options.max_tx_weight.map(|max_tx_weight| {
+            max_tx_weight.saturating_sub(options.base_weight.saturating_add(options.change_weight))
+        }),
     );
-    match bnb_selected_coin {
-        Some(selected_coin) => {
-            let accumulated_value: u64 = selected_coin
-                .iter()
-                .fold(0, |acc, &i| acc + inputs[i].value);
-            let accumulated_weight: u64 = selected_coin
-                .iter()
-                .fold(0, |acc, &i| acc + inputs[i].weight);
-            let estimated_fee = 0;
-            let waste = calculate_waste(
-                options,
-                accumulated_value,
-                accumulated_weight,
-                estimated_fee,
-            );
-            let selection_output = SelectionOutput {
-                selected_inputs: selected_coin,
-                waste: WasteMetric(waste),
-            };
-            Ok(selection_output)
-        }
-        None => Err(SelectionError::NoSolutionFound),
+    if match_parameters.remaining_eff_value[0] < target_for_match {
+        return Err(SelectionError::InsufficientFunds);
+    }
+    Ok((sorted_inputs, match_parameters))
+}
+
+/// Returns the effective value a changeless selection has to reach, and the excess it may carry on top.
+///
+/// As in Erhardt's thesis, the excess is bounded by `change_cost`, the cost of creating and later spending a
+/// change output, past which creating the change is cheaper than dropping the excess to fees. The fee for the
+/// base weight is raised to `min_absolute_fee`, so any match pays at least that much on top of the input fees.
+/// The excess is further bounded by `max_excess` when set.
+///
+/// Returns `ArithmeticOverflow` when the upper end of the window does not fit in a `u64`.
+pub(crate) fn match_window(options: &CoinSelectionOpt) -> Result<(u64, u64), SelectionError> {
+    let match_range = options
+        .max_excess
+        .map_or(options.change_cost, |max_excess| {
+            max_excess.min(options.change_cost)
+        });
+    options
+        .target_value
+        .checked_add(
+            calculate_fee(options.base_weight, *options.target_feerate)
+                .max(options.min_absolute_fee),
+        )
+        .filter(|target_for_match| target_for_match.checked_add(match_range).is_some())
+        .map(|target_for_match| (target_for_match, match_range))
+        .ok_or(SelectionError::ArithmeticOverflow)
+}
+
+/// Returns `ArithmeticOverflow` when the values or the weights of the inputs do not sum within a `u64`.
+///
+/// The sums over any selection of the inputs, accumulated during the search and in [`bnb_output`], then can not
+/// overflow either.
+fn check_totals<T: WeightedUtxo>(
+    inputs_in_desc_value: &[(usize, &T)],
+    target_feerate: f32,
+) -> Result<(), SelectionError> {
+    inputs_in_desc_value
+        .iter()
+        .try_fold(
+            (0u64, 0u64, 0u64),
+            |(value, weight, eff_value), (_, input)| {
+                Some((
+                    value.checked_add(input.value())?,
+                    weight.checked_add(input.weight())?,
+                    eff_value.checked_add(effective_value(*input, target_feerate))?,
+                ))
+            },
+        )
+        .map(|_| ())
+        .ok_or(SelectionError::ArithmeticOverflow)
+}
+
+/// Returns the [`SelectionOutput`] of a changeless match, whose excess is dropped to the fee even when the
+/// `excess_strategy` is [`ExcessStrategy::ToChange`] or [`ExcessStrategy::Split`], and counted as such in its waste.
+fn bnb_match_output<T: WeightedUtxo>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+    selected_coin: Vec<usize>,
+) -> SelectionOutput {
+    if !matches!(
+        options.excess_strategy,
+        ExcessStrategy::ToChange | ExcessStrategy::Split
+    ) {
+        return bnb_output(inputs, options, selected_coin);
     }
+    let changeless_options = CoinSelectionOpt {
+        excess_strategy: ExcessStrategy::ToFee,
+        ..options.clone()
+    };
+    bnb_output(inputs, &changeless_options, selected_coin)
+}
+
+fn bnb_output<T: WeightedUtxo>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+    mut selected_coin: Vec<usize>,
+) -> SelectionOutput {
+    let accumulated_value: u64 = selected_coin
+        .iter()
+        .fold(0, |acc, &i| acc + inputs[i].value());
+    let accumulated_weight: u64 = selected_coin
+        .iter()
+        .fold(0, |acc, &i| acc + inputs[i].weight());
+    let estimated_fee = calculate_selection_fee(options, accumulated_weight);
+    let waste_breakdown = calculate_waste_detailed(
+        options,
+        accumulated_value,
+        accumulated_weight,
+        estimated_fee,
+    );
+    selected_coin.sort_unstable();
+    SelectionOutput {
+        selected_inputs: selected_coin,
+        waste: WasteMetric(waste_breakdown.total()),
+        waste_breakdown,
+        change_value: calculate_change(options, accumulated_value, estimated_fee),
+        estimated_fee,
+        selected_value: accumulated_value,
+        selected_weight: accumulated_weight,
+    }
+}
+
+/// A node of the search tree whose branches are being explored.
+#[derive(Debug)]
+struct Branch<A> {
+    /// Depth of the node, the position of the input it decides on among the sorted inputs.
+    depth: usize,
+    /// Accumulated totals of the selection at this node.
+    accumulated: A,
+    /// Whether the inclusion branch is explored before the omission branch.
+    include_first: bool,
+    /// Whether the first branch was explored and the second one is under way.
+    on_second_branch: bool,
 }
 
-/// Return empty vec if no solutions are found
+/// Walks the search tree from the node at `depth`, returning the unsorted indices of the first changeless match, or
+/// `None` once the tree is exhausted or the tries run out.
 ///
-/// changing the selected_inputs : &[usize] -> &mut Vec<usize>
-fn bnb(
-    inputs_in_desc_value: &[(usize, &OutputGroup)],
+/// When `rng` is `None` the inclusion branch is always explored first, otherwise the order is picked at random.
+/// The tree is walked depth first with an explicit stack of [`Branch`]es rather than by recursion, so searching
+/// large pools needs no more call stack than small ones.
+fn bnb<T: WeightedUtxo, R: Rng>(
+    inputs_in_desc_value: &[(usize, &T)],
     selected_inputs: &mut Vec<usize>,
     acc_eff_value: u64,
     depth: usize,
-    bnb_tries: &mut u32,
-    rng: &mut ThreadRng,
+    state: &mut SearchState,
+    mut rng: Option<&mut R>,
     match_parameters: &MatchParameters,
 ) -> Option<Vec<usize>> {
+    let mut branches: Vec<Branch<u64>> = Vec::new();
+    // The node to visit next, by its accumulated effective value and depth, or `None` to backtrack
+    let mut next = Some((acc_eff_value, depth));
+    loop {
+        if let Some((acc_eff_value, depth)) = next.take() {
+            let expands = match bnb_expands(
+                inputs_in_desc_value,
+                selected_inputs,
+                acc_eff_value,
+                depth,
+                state,
+                match_parameters,
+            ) {
+                Some(expands) => expands,
+                None => return Some(selected_inputs.to_vec()),
+            };
+            if expands {
+                // Decrement of bnb_tries for every iteration
+                state.bnb_tries -= 1;
+                let include_first = match rng.as_deref_mut() {
+                    Some(rng) => rng.gen_bool(0.5),
+                    None => true,
+                };
+                branches.push(Branch {
+                    depth,
+                    accumulated: acc_eff_value,
+                    include_first,
+                    on_second_branch: false,
+                });
+                next = Some(if include_first {
+                    include_input(inputs_in_desc_value[depth], selected_inputs, state);
+                    (acc_eff_value + match_parameters.eff_value[depth], depth + 1)
+                } else {
+                    (acc_eff_value, match_parameters.next_distinct_depth[depth])
+                });
+                continue;
+            }
+        }
+
+        // The last visited node holds no match, moving on to the next branch of its parent
+        let branch = branches.last_mut()?;
+        let input = inputs_in_desc_value[branch.depth];
+        if branch.on_second_branch {
+            if !branch.include_first {
+                omit_input(input, selected_inputs, state); // popping out the selected utxo if it does not fit
+            }
+            branches.pop();
+        } else {
+            branch.on_second_branch = true;
+            next = Some(if branch.include_first {
+                omit_input(input, selected_inputs, state); // popping out the selected utxo if it does not fit
+                (
+                    branch.accumulated,
+                    match_parameters.next_distinct_depth[branch.depth],
+                )
+            } else {
+                include_input(input, selected_inputs, state);
+                (
+                    branch.accumulated + match_parameters.eff_value[branch.depth],
+                    branch.depth + 1,
+                )
+            });
+        }
+    }
+}
+
+/// Returns, for every depth, the depth of the first input after the one at that depth that differs from it in
+/// effective value or weight.
+///
+/// Once an input is omitted, selecting an identical one in its place only yields selections equivalent to those
+/// of the inclusion branch, so the omission branch skips identical inputs, as in Bitcoin Core. This keeps pools
+/// of many identical coins, such as coinjoin outputs or mining payouts, from exhausting the tries. The depths are
+/// computed once, backwards, so that skipping a long run of identical inputs does not rescan it at every node.
+fn next_distinct_depths<T: WeightedUtxo>(
+    inputs_in_desc_value: &[(usize, &T)],
+    eff_value: &[u64],
+) -> Vec<usize> {
+    let mut next_distinct_depth: Vec<usize> = (1..=eff_value.len()).collect();
+    for depth in (0..eff_value.len().saturating_sub(1)).rev() {
+        if eff_value[depth + 1] == eff_value[depth]
+            && inputs_in_desc_value[depth + 1].1.weight() == inputs_in_desc_value[depth].1.weight()
+        {
+            next_distinct_depth[depth] = next_distinct_depth[depth + 1];
+        }
+    }
+    next_distinct_depth
+}
+
+/// Visits a node of the [`bnb`] search, returning whether its branches are worth exploring.
+///
+/// Returns `None` when the selection at the node is a match, after which the search stops.
+fn bnb_expands<T: WeightedUtxo>(
+    inputs_in_desc_value: &[(usize, &T)],
+    selected_inputs: &[usize],
+    acc_eff_value: u64,
+    depth: usize,
+    state: &mut SearchState,
+    match_parameters: &MatchParameters,
+) -> Option<bool> {
+    state.max_depth = state.max_depth.max(depth);
+    // Selections spending too many inputs, or too heavy, are neither matches nor overshoots, and only grow deeper in
+    // the tree
+    if match_parameters
+        .max_inputs
+        .is_some_and(|max_inputs| state.input_count > max_inputs)
+        || match_parameters
+            .max_input_weight
+            .is_some_and(|max_input_weight| state.weight > max_input_weight)
+    {
+        return Some(false);
+    }
     if acc_eff_value > match_parameters.target_for_match + match_parameters.match_range {
-        return None;
+        // Overshooting selections are no match, but remain candidates for a selection with change
+        if state
+            .best_overshoot
+            .as_ref()
+            .is_none_or(|(_, best_eff_value)| acc_eff_value < *best_eff_value)
+        {
+            state.best_overshoot = Some((selected_inputs.to_vec(), acc_eff_value));
+        }
+        return Some(false);
     }
     if acc_eff_value >= match_parameters.target_for_match {
-        return Some(selected_inputs.to_vec());
+        return None;
+    }
+
+    if depth >= inputs_in_desc_value.len() {
+        return Some(false);
     }
 
+    // Lookahead: even including every remaining input can not reach the target
+    let reachable = acc_eff_value + match_parameters.remaining_eff_value[depth]
+        >= match_parameters.target_for_match;
     // Capping the number of iterations on the computation
-    if *bnb_tries == 0 || depth >= inputs_in_desc_value.len() {
-        return None;
+    if reachable && state.bnb_tries == 0 {
+        state.budget_exhausted = true;
+        return Some(false);
     }
+    Some(reachable)
+}
 
-    // Decrement of bnb_tries for every iteration
-    *bnb_tries -= 1;
+fn include_input<T: WeightedUtxo>(
+    (index, input): (usize, &T),
+    selected_inputs: &mut Vec<usize>,
+    state: &mut SearchState,
+) {
+    selected_inputs.push(index);
+    state.input_count += input.input_count();
+    state.weight += input.weight();
+}
 
-    if rng.gen_bool(0.5) {
-        // exploring the inclusion branch
-        // first include then omit
-        let new_effective_value = acc_eff_value
-            + effective_value(
-                inputs_in_desc_value[depth].1,
-                match_parameters.target_feerate,
-            );
-        selected_inputs.push(inputs_in_desc_value[depth].0);
-        let with_this = bnb(
-            inputs_in_desc_value,
-            selected_inputs,
-            new_effective_value,
-            depth + 1,
-            bnb_tries,
-            rng,
-            match_parameters,
-        );
-        match with_this {
-            Some(_) => with_this,
-            None => {
-                selected_inputs.pop(); // popping out the selected utxo if it does not fit
-                bnb(
-                    inputs_in_desc_value,
-                    selected_inputs,
-                    acc_eff_value,
-                    depth + 1,
-                    bnb_tries,
-                    rng,
-                    match_parameters,
-                )
+fn omit_input<T: WeightedUtxo>(
+    (_, input): (usize, &T),
+    selected_inputs: &mut Vec<usize>,
+    state: &mut SearchState,
+) {
+    selected_inputs.pop();
+    state.input_count -= input.input_count();
+    state.weight -= input.weight();
+}
+
+/// Walks the inclusion-first search tree and records the lowest-waste selection reaching `target_with_change`.
+///
+/// `accumulated` holds the effective value, value, weight and input count of the `selected_inputs`. Like
+/// [`bnb`], the tree is walked with an explicit stack rather than by recursion.
+#[allow(clippy::too_many_arguments)]
+fn bnb_change<T: WeightedUtxo>(
+    inputs_in_desc_value: &[(usize, &T)],
+    selected_inputs: &mut Vec<usize>,
+    accumulated: (u64, u64, u64, usize),
+    depth: usize,
+    bnb_tries: &mut u32,
+    target_with_change: u64,
+    options: &CoinSelectionOpt,
+    best: &mut Option<(Vec<usize>, i64)>,
+) {
+    let start_depth = depth;
+    let mut branches: Vec<Branch<(u64, u64, u64, usize)>> = Vec::new();
+    let mut next = Some(accumulated);
+    loop {
+        if let Some(accumulated) = next.take() {
+            let depth = start_depth + branches.len();
+            if bnb_change_expands(
+                inputs_in_desc_value,
+                selected_inputs,
+                accumulated,
+                depth,
+                bnb_tries,
+                target_with_change,
+                options,
+                best,
+            ) {
+                *bnb_tries -= 1;
+                let (acc_eff_value, acc_value, acc_weight, acc_input_count) = accumulated;
+                let (index, input) = inputs_in_desc_value[depth];
+                branches.push(Branch {
+                    depth,
+                    accumulated,
+                    include_first: true,
+                    on_second_branch: false,
+                });
+                selected_inputs.push(index);
+                next = Some((
+                    acc_eff_value + effective_value(input, *options.target_feerate),
+                    acc_value + input.value(),
+                    acc_weight + input.weight(),
+                    acc_input_count + input.input_count(),
+                ));
+                continue;
             }
         }
-    } else {
-        match bnb(
-            inputs_in_desc_value,
-            selected_inputs,
-            acc_eff_value,
-            depth + 1,
-            bnb_tries,
-            rng,
-            match_parameters,
-        ) {
-            Some(without_this) => Some(without_this),
-            None => {
-                let new_effective_value = acc_eff_value
-                    + effective_value(
-                        inputs_in_desc_value[depth].1,
-                        match_parameters.target_feerate,
-                    );
-                selected_inputs.push(inputs_in_desc_value[depth].0);
-                let with_this = bnb(
-                    inputs_in_desc_value,
-                    selected_inputs,
-                    new_effective_value,
-                    depth + 1,
-                    bnb_tries,
-                    rng,
-                    match_parameters,
-                );
-                match with_this {
-                    Some(_) => with_this,
-                    None => {
-                        selected_inputs.pop(); // poping out the selected utxo if it does not fit
-                        None
-                    }
-                }
-            }
+
+        let Some(branch) = branches.last_mut() else {
+            return;
+        };
+        if branch.on_second_branch {
+            branches.pop();
+        } else {
+            branch.on_second_branch = true;
+            selected_inputs.pop();
+            next = Some(branch.accumulated);
         }
     }
 }
 
+/// Visits a node of the [`bnb_change`] search, returning whether its branches are worth exploring.
+#[allow(clippy::too_many_arguments)]
+fn bnb_change_expands<T: WeightedUtxo>(
+    inputs_in_desc_value: &[(usize, &T)],
+    selected_inputs: &[usize],
+    accumulated: (u64, u64, u64, usize),
+    depth: usize,
+    bnb_tries: &u32,
+    target_with_change: u64,
+    options: &CoinSelectionOpt,
+    best: &mut Option<(Vec<usize>, i64)>,
+) -> bool {
+    let (acc_eff_value, acc_value, acc_weight, acc_input_count) = accumulated;
+    if exceeds_max_inputs(options, acc_input_count) || exceeds_max_tx_weight(options, acc_weight) {
+        return false;
+    }
+    if acc_eff_value >= target_with_change {
+        // Adding more inputs to a selection which already funds the change output only adds weight.
+        let estimated_fee = calculate_selection_fee(options, acc_weight);
+        let waste = calculate_waste(options, acc_value, acc_weight, estimated_fee);
+        if best
+            .as_ref()
+            .is_none_or(|(_, best_waste)| waste < *best_waste)
+        {
+            *best = Some((selected_inputs.to_vec(), waste));
+        }
+        return false;
+    }
+
+    // Capping the number of iterations on the computation
+    *bnb_tries > 0 && depth < inputs_in_desc_value.len()
+}
+
 #[cfg(test)]
 mod test {
+    use super::{bnb, match_window, MatchParameters, SearchState};
     use crate::{
-        algorithms::bnb::select_coin_bnb,
-        types::{CoinSelectionOpt, ExcessStrategy, OutputGroup, SelectionError},
+        algorithms::bnb::{
+            select_coin_bnb, select_coin_bnb_seeded, select_coin_bnb_with_change,
+            select_coin_bnb_with_fallback, select_coin_bnb_with_stats, BnbStats, BNB_TOTAL_TRIES,
+        },
+        types::{CoinSelectionOpt, ExcessStrategy, FeeRate, OutputGroup, SelectionError},
+        utils::calculate_fee,
+    };
+    #[cfg(feature = "std")]
+    use crate::{
+        selectcoin::{select_coin, select_coin_all, select_coin_with},
+        types::Algorithm,
     };
 
     fn setup_basic_output_groups() -> Vec<OutputGroup> {
@@ -185,18 +807,27 @@ mod test {
                 weight: 100,
                 input_count: 1,
                 creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
             },
             OutputGroup {
                 value: 2000,
                 weight: 200,
                 input_count: 1,
                 creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
             },
             OutputGroup {
                 value: 3000,
                 weight: 300,
                 input_count: 1,
                 creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
             },
         ]
     }
@@ -204,16 +835,23 @@ mod test {
     fn bnb_setup_options(target_value: u64) -> CoinSelectionOpt {
         CoinSelectionOpt {
             target_value,
-            target_feerate: 0.5, // Simplified feerate
+            target_feerate: FeeRate::from_sat_per_wu(0.5), // Simplified feerate
             long_term_feerate: None,
             min_absolute_fee: 0,
             base_weight: 10,
             change_weight: 50,
-            change_cost: 10,
+            change_cost: 30,
             avg_input_weight: 40,
             avg_output_weight: 20,
             min_change_value: 500,
             excess_strategy: ExcessStrategy::ToChange,
+            max_inputs: None,
+            bnb_tries: None,
+            include_uneconomical: false,
+            max_excess: None,
+            max_tx_weight: None,
+            knapsack_iterations: None,
+            knapsack_stagnation: None,
         }
     }
 
@@ -225,48 +863,72 @@ mod test {
                 weight: 500,
                 input_count: 1,
                 creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
             },
             OutputGroup {
                 value: 400,
                 weight: 200,
                 input_count: 1,
                 creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
             },
             OutputGroup {
                 value: 40000,
                 weight: 300,
                 input_count: 1,
                 creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
             },
             OutputGroup {
                 value: 25000,
                 weight: 100,
                 input_count: 1,
                 creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
             },
             OutputGroup {
                 value: 35000,
                 weight: 150,
                 input_count: 1,
                 creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
             },
             OutputGroup {
                 value: 600,
                 weight: 250,
                 input_count: 1,
                 creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
             },
             OutputGroup {
                 value: 30000,
                 weight: 120,
                 input_count: 1,
                 creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
             },
             OutputGroup {
                 value: 5000,
                 weight: 50,
                 input_count: 1,
                 creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
             },
         ];
 
@@ -274,17 +936,55 @@ mod test {
         let opt = bnb_setup_options(5730);
         let ans = select_coin_bnb(&values, &opt);
         if let Ok(selection_output) = ans {
-            let expected_solution = vec![7, 5, 1];
+            let expected_solution = vec![1, 5, 7];
             assert_eq!(
                 selection_output.selected_inputs, expected_solution,
                 "Expected solution {:?}, but got {:?}",
                 expected_solution, selection_output.selected_inputs
             );
+            // A changeless match, the excess is dropped to fees
+            assert_eq!(selection_output.change_value, None);
+            // The 10 weight units of the base and 500 of the inputs at 0.5 sats each
+            assert_eq!(selection_output.estimated_fee, 255);
+            assert_eq!(selection_output.selected_weight, 500);
+            assert!(selection_output.selected_value >= opt.target_value);
         } else {
             panic!("Failed to find a solution");
         }
     }
 
+    #[cfg(feature = "std")]
+    fn test_bnb_through_select_coin() {
+        let mut inputs = setup_basic_output_groups();
+        inputs[2].weight = 100;
+        // 3000 sats pay exactly 2750 sats, 200 sats for the base weight and 50 sats for the last input
+        let options = CoinSelectionOpt {
+            base_weight: 400,
+            ..bnb_setup_options(2750)
+        };
+        let (target_for_match, match_range) = match_window(&options).unwrap();
+        assert_eq!((target_for_match, match_range), (2950, 30));
+
+        let bnb = select_coin_with(&inputs, &options, Algorithm::Bnb).unwrap();
+        assert_eq!(bnb.selected_inputs, vec![2]);
+        assert_eq!(bnb.estimated_fee, 250);
+        assert_eq!(bnb.change_value, None);
+        assert_eq!(bnb.waste.0, 0);
+
+        // Without the base fee, the match reported its 200 sats as excess and ranked behind FIFO, which spends every
+        // input to create a change output
+        let fifo = select_coin_with(&inputs, &options, Algorithm::Fifo).unwrap();
+        assert_eq!(fifo.selected_inputs, vec![0, 1, 2]);
+        assert_eq!(fifo.waste.0, options.change_cost as i64);
+        let ranked = select_coin_all(&inputs, &options);
+        let rank = |algorithm| ranked.iter().position(|(a, _)| *a == algorithm).unwrap();
+        assert!(rank(Algorithm::Bnb) < rank(Algorithm::Fifo));
+
+        let result = select_coin(&inputs, &options).unwrap();
+        assert_eq!(result.selected_inputs, vec![2]);
+        assert_eq!(result.waste.0, 0);
+    }
+
     fn test_bnb_no_solution() {
         let inputs = setup_basic_output_groups();
         let total_input_value: u64 = inputs.iter().map(|input| input.value).sum();
@@ -292,15 +992,634 @@ mod test {
         let options = bnb_setup_options(impossible_target);
         let result = select_coin_bnb(&inputs, &options);
         assert!(
-            matches!(result, Err(SelectionError::NoSolutionFound)),
-            "Expected NoSolutionFound error, got {:?}",
+            matches!(result, Err(SelectionError::InsufficientFunds)),
+            "Expected InsufficientFunds error, got {:?}",
             result
         );
     }
 
+    const CENT: u64 = 1_000_000;
+
+    // Mirrors Bitcoin Core's zero-fee `coinselector_tests`: weightless inputs, where the cost of change
+    // is expressed through the average input weight at a feerate of 1 sat/wu.
+    fn bnb_setup_core_output_groups(values: &[u64]) -> Vec<OutputGroup> {
+        values
+            .iter()
+            .map(|&value| OutputGroup {
+                value,
+                weight: 0,
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            })
+            .collect()
+    }
+
+    fn bnb_setup_core_options(target_value: u64, cost_of_change: u64) -> CoinSelectionOpt {
+        CoinSelectionOpt {
+            target_value,
+            target_feerate: FeeRate::from_sat_per_wu(1.0),
+            long_term_feerate: None,
+            min_absolute_fee: 0,
+            base_weight: 0,
+            change_weight: 0,
+            change_cost: cost_of_change,
+            avg_input_weight: 0,
+            avg_output_weight: 0,
+            min_change_value: 0,
+            excess_strategy: ExcessStrategy::ToChange,
+            max_inputs: None,
+            bnb_tries: None,
+            include_uneconomical: false,
+            max_excess: None,
+            max_tx_weight: None,
+            knapsack_iterations: None,
+            knapsack_stagnation: None,
+        }
+    }
+
+    #[test]
+    fn test_bnb_core_vectors() {
+        // Empty utxo pool
+        let inputs = bnb_setup_core_output_groups(&[]);
+        let result = select_coin_bnb(&inputs, &bnb_setup_core_options(CENT, CENT / 2));
+        assert!(matches!(result, Err(SelectionError::InsufficientFunds)));
+
+        let mut inputs = bnb_setup_core_output_groups(&[CENT, 2 * CENT, 3 * CENT, 4 * CENT]);
+        let cases: [(u64, u64, Result<Vec<usize>, SelectionError>); 6] = [
+            // Select 1 Cent
+            (CENT, CENT / 2, Ok(vec![0])),
+            // Select 2 Cent
+            (2 * CENT, CENT / 2, Ok(vec![1])),
+            // Select 5 Cent
+            (5 * CENT, CENT / 2, Ok(vec![0, 3])),
+            // Select 11 Cent, not possible
+            (11 * CENT, CENT / 2, Err(SelectionError::InsufficientFunds)),
+            // Cost of change is greater than the difference between target value and utxo sum
+            (9 * CENT / 10, CENT / 2, Ok(vec![0])),
+            // Cost of change is less than the difference between target value and utxo sum
+            (9 * CENT / 10, 0, Err(SelectionError::NoSolutionFound)),
+        ];
+        for (target, cost_of_change, expected) in cases {
+            let options = bnb_setup_core_options(target, cost_of_change);
+            // Every run over the same inputs must produce the same selection
+            for _ in 0..10 {
+                let result = select_coin_bnb(&inputs, &options);
+                assert_eq!(result.map(|output| output.selected_inputs), expected);
+            }
+        }
+
+        // Select 10 Cent
+        inputs.extend(bnb_setup_core_output_groups(&[5 * CENT]));
+        let result = select_coin_bnb(&inputs, &bnb_setup_core_options(10 * CENT, CENT / 2));
+        assert_eq!(result.unwrap().selected_inputs, vec![0, 3, 4]);
+
+        // Select 0.25 Cent, not possible
+        let result = select_coin_bnb(&inputs, &bnb_setup_core_options(CENT / 4, CENT / 2));
+        assert!(matches!(result, Err(SelectionError::NoSolutionFound)));
+    }
+
+    #[test]
+    fn test_bnb_match_window_is_change_cost() {
+        let inputs = bnb_setup_core_output_groups(&[CENT, 2 * CENT]);
+        // One cent overshoots the target by 400 sats
+        let options = bnb_setup_core_options(CENT - 400, 500);
+        let result = select_coin_bnb(&inputs, &options).unwrap();
+        assert_eq!(result.selected_inputs, vec![0]);
+
+        let options = bnb_setup_core_options(CENT - 400, 300);
+        let result = select_coin_bnb(&inputs, &options);
+        assert!(matches!(result, Err(SelectionError::NoSolutionFound)));
+
+        // The average input and output weights no longer widen the window
+        let options = CoinSelectionOpt {
+            avg_input_weight: 1000,
+            avg_output_weight: 1000,
+            ..bnb_setup_core_options(CENT - 400, 300)
+        };
+        let result = select_coin_bnb(&inputs, &options);
+        assert!(matches!(result, Err(SelectionError::NoSolutionFound)));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_bnb_max_excess() {
+        // The only changeless match spends the 100000 sats input, overpaying by 5000 sats
+        let inputs = bnb_setup_core_output_groups(&[100_000, 60_000, 30_000]);
+        let options = bnb_setup_core_options(95_000, 10_000);
+        let result = select_coin_bnb(&inputs, &options).unwrap();
+        assert_eq!(result.selected_inputs, vec![0]);
+        // The excess is dropped to the fee rather than to a change output, and counted as waste
+        assert_eq!(result.change_value, None);
+        assert_eq!(result.waste.0, 5000);
+
+        let options = CoinSelectionOpt {
+            max_excess: Some(1000),
+            ..bnb_setup_core_options(95_000, 10_000)
+        };
+        assert_eq!(match_window(&options).unwrap(), (95_000, 1000));
+        assert!(matches!(
+            select_coin_bnb(&inputs, &options),
+            Err(SelectionError::NoSolutionFound)
+        ));
+        // Other algorithms create a change output instead
+        let result = select_coin(&inputs, &options).unwrap();
+        assert!(result.change_value.is_some());
+
+        let options = CoinSelectionOpt {
+            max_excess: Some(5000),
+            ..bnb_setup_core_options(95_000, 10_000)
+        };
+        assert_eq!(
+            select_coin_bnb(&inputs, &options).unwrap().selected_inputs,
+            vec![0]
+        );
+    }
+
+    #[test]
+    fn test_bnb_waste_accounts_for_fee() {
+        let inputs: Vec<OutputGroup> = [6000, 4000, 2500]
+            .iter()
+            .map(|&value| OutputGroup {
+                value,
+                weight: 100,
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            })
+            .collect();
+        let options = CoinSelectionOpt {
+            long_term_feerate: Some(FeeRate::from_sat_per_wu(0.5)),
+            excess_strategy: ExcessStrategy::ToFee,
+            ..bnb_setup_core_options(9700, 200)
+        };
+        // The effective values of the first two inputs, 5900 and 3900, match the target
+        let result = select_coin_bnb(&inputs, &options).unwrap();
+        assert_eq!(result.selected_inputs, vec![0, 1]);
+
+        let fee = calculate_fee(200, *options.target_feerate);
+        assert_eq!(result.estimated_fee, fee);
+        // The weight times the difference of feerates, plus the excess over the target and the fee
+        let expected_waste = calculate_fee(200, 0.5) + 10_000 - (options.target_value + fee);
+        assert_eq!(result.waste.0, expected_waste as i64);
+    }
+
+    #[test]
+    fn test_bnb_weight_above_u32_max() {
+        // Five inputs of 2^30 weight units, together heavier than u32::MAX
+        let inputs: Vec<OutputGroup> = (0..5)
+            .map(|_| OutputGroup {
+                value: 11_024,
+                weight: 1 << 30,
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            })
+            .collect();
+        // Feerates are powers of two so that fees are exact, an input pays 1024 at the target feerate
+        let options = CoinSelectionOpt {
+            target_feerate: FeeRate::from_sat_per_wu(1.0 / (1 << 20) as f32),
+            long_term_feerate: Some(FeeRate::from_sat_per_wu(1.0 / (1 << 21) as f32)),
+            excess_strategy: ExcessStrategy::ToFee,
+            ..bnb_setup_core_options(50_000, 0)
+        };
+        let result = select_coin_bnb(&inputs, &options).unwrap();
+        assert_eq!(result.selected_inputs.len(), 5);
+        assert_eq!(result.selected_weight, 5 << 30);
+        assert!(result.selected_weight > u32::MAX as u64);
+        assert_eq!(result.estimated_fee, 5 * 1024);
+        // No excess, only the weight times the difference of feerates
+        assert_eq!(result.waste.0, 5 * 512);
+    }
+
+    #[test]
+    fn test_bnb_min_absolute_fee() {
+        let inputs = setup_basic_output_groups();
+        let mut options = bnb_setup_options(2845);
+        let result = select_coin_bnb(&inputs, &options).unwrap();
+        assert!(result.selected_value - options.target_value < 955);
+
+        // Only spending 4000 sats leaves a fee of at least 955 sats
+        options.min_absolute_fee = 955;
+        let mut result = select_coin_bnb(&inputs, &options).unwrap();
+        result.selected_inputs.sort();
+        assert_eq!(result.selected_inputs, vec![0, 2]);
+        assert!(result.selected_value - options.target_value >= options.min_absolute_fee);
+
+        let mut result = select_coin_bnb_with_change(&inputs, &options).unwrap();
+        result.selected_inputs.sort();
+        assert_eq!(result.selected_inputs, vec![0, 2]);
+    }
+
+    #[test]
+    fn test_bnb_max_inputs() {
+        let inputs = bnb_setup_core_output_groups(&[CENT, 2 * CENT, 3 * CENT, 4 * CENT]);
+        // Only all four inputs match the target
+        let mut options = bnb_setup_core_options(10 * CENT, CENT / 2);
+        options.max_inputs = Some(4);
+        let result = select_coin_bnb(&inputs, &options).unwrap();
+        assert_eq!(result.selected_inputs.len(), 4);
+
+        options.max_inputs = Some(3);
+        let result = select_coin_bnb(&inputs, &options);
+        assert!(matches!(result, Err(SelectionError::NoSolutionFound)));
+        let result = select_coin_bnb_with_fallback(&inputs, &options);
+        assert!(matches!(result, Err(SelectionError::NoSolutionFound)));
+
+        // Two inputs still match a smaller target
+        options.target_value = 5 * CENT;
+        options.max_inputs = Some(2);
+        let result = select_coin_bnb(&inputs, &options).unwrap();
+        assert_eq!(result.selected_inputs.len(), 2);
+    }
+
+    #[test]
+    fn test_bnb_large_pool_does_not_overflow_stack() {
+        // Every input is needed, so the search goes as deep as the pool is large
+        let inputs = bnb_setup_core_output_groups(&[CENT; 50_000]);
+        let options = bnb_setup_core_options(50_000 * CENT, 0);
+        let result = select_coin_bnb(&inputs, &options).unwrap();
+        assert_eq!(result.selected_inputs.len(), 50_000);
+
+        // No changeless match, the fallback with change walks as deep
+        let options = bnb_setup_core_options(50_000 * CENT - CENT / 2, 0);
+        let result = select_coin_bnb_with_change(&inputs, &options).unwrap();
+        assert_eq!(result.selected_inputs.len(), 50_000);
+        assert_eq!(result.change_value, Some(CENT / 2));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_bnb_tries() {
+        // Including the largest inputs first, the search backtracks a long way before reaching the match
+        let values: Vec<u64> = (1..=20).map(|i| i * CENT).collect();
+        let inputs = bnb_setup_core_output_groups(&values);
+        let mut options = bnb_setup_core_options(3 * CENT + CENT / 2, CENT / 2);
+        let result = select_coin_bnb(&inputs, &options).unwrap();
+        assert_eq!(
+            result
+                .selected_inputs
+                .iter()
+                .map(|&i| inputs[i].value)
+                .sum::<u64>(),
+            4 * CENT
+        );
+
+        options.bnb_tries = Some(10);
+        let result = select_coin_bnb(&inputs, &options);
+        assert!(matches!(result, Err(SelectionError::NoSolutionFound)));
+        let result = select_coin_with(&inputs, &options, Algorithm::Bnb);
+        assert!(matches!(result, Err(SelectionError::NoSolutionFound)));
+
+        options.bnb_tries = Some(BNB_TOTAL_TRIES);
+        assert!(select_coin_bnb(&inputs, &options).is_ok());
+    }
+
+    #[test]
+    fn test_bnb_stats() {
+        let inputs = setup_basic_output_groups();
+        let options = bnb_setup_options(2845);
+        let (result, stats) = select_coin_bnb_with_stats(&inputs, &options);
+        assert_eq!(
+            result.unwrap().selected_inputs,
+            select_coin_bnb(&inputs, &options).unwrap().selected_inputs
+        );
+        assert_eq!(stats.matches, 1);
+        assert!(stats.tries < BNB_TOTAL_TRIES);
+        assert!(stats.max_depth <= inputs.len());
+        assert!(!stats.budget_exhausted);
+
+        // Even values never add up to an odd target
+        let values: Vec<u64> = (1..=30).map(|i| 2 * i).collect();
+        let inputs = bnb_setup_core_output_groups(&values);
+        let options = CoinSelectionOpt {
+            bnb_tries: Some(1000),
+            ..bnb_setup_core_options(401, 0)
+        };
+        let (result, stats) = select_coin_bnb_with_stats(&inputs, &options);
+        assert!(matches!(result, Err(SelectionError::NoSolutionFound)));
+        assert_eq!(stats.matches, 0);
+        assert_eq!(stats.tries, 1000);
+        assert!(stats.budget_exhausted);
+    }
+
+    #[test]
+    fn test_bnb_insufficient_funds() {
+        // The effective values 950 + 1900 + 2850 add up to exactly the match target
+        let inputs = setup_basic_output_groups();
+        let options = bnb_setup_options(5695);
+        assert_eq!(match_window(&options).unwrap().0, 5700);
+        let result = select_coin_bnb(&inputs, &options).unwrap();
+        assert_eq!(result.selected_inputs, vec![0, 1, 2]);
+
+        // One sat more is out of reach, reported without visiting a single node
+        let options = bnb_setup_options(5696);
+        let (result, stats) = select_coin_bnb_with_stats(&inputs, &options);
+        assert!(matches!(result, Err(SelectionError::InsufficientFunds)));
+        assert_eq!(stats, BnbStats::default());
+
+        // A large pool well short of the target is rejected just as quickly
+        let values: Vec<u64> = (1..=10_000).collect();
+        let inputs = bnb_setup_core_output_groups(&values);
+        let total: u64 = values.iter().sum();
+        let (result, stats) =
+            select_coin_bnb_with_stats(&inputs, &bnb_setup_core_options(2 * total, 0));
+        assert!(matches!(result, Err(SelectionError::InsufficientFunds)));
+        assert_eq!(stats.tries, 0);
+    }
+
+    #[test]
+    fn test_bnb_arithmetic_overflow() {
+        let inputs = setup_basic_output_groups();
+        // The base fee, then the change cost, push the match window past `u64::MAX`
+        for target_value in [u64::MAX, u64::MAX - 10] {
+            let options = bnb_setup_options(target_value);
+            assert_eq!(
+                match_window(&options),
+                Err(SelectionError::ArithmeticOverflow)
+            );
+            assert_eq!(
+                select_coin_bnb(&inputs, &options),
+                Err(SelectionError::ArithmeticOverflow)
+            );
+            assert_eq!(
+                select_coin_bnb_with_change(&inputs, &options),
+                Err(SelectionError::ArithmeticOverflow)
+            );
+            assert_eq!(
+                select_coin_bnb_with_fallback(&inputs, &options),
+                Err(SelectionError::ArithmeticOverflow)
+            );
+            assert_eq!(
+                select_coin_bnb_with_stats(&inputs, &options).0,
+                Err(SelectionError::ArithmeticOverflow)
+            );
+        }
+
+        // Values summing past `u64::MAX`
+        let inputs = bnb_setup_core_output_groups(&[u64::MAX, u64::MAX, CENT]);
+        let options = bnb_setup_core_options(CENT, 0);
+        assert_eq!(
+            select_coin_bnb(&inputs, &options),
+            Err(SelectionError::ArithmeticOverflow)
+        );
+        assert_eq!(
+            select_coin_bnb_with_change(&inputs, &options),
+            Err(SelectionError::ArithmeticOverflow)
+        );
+
+        // Weights summing past `u64::MAX`, once the uneconomical inputs are kept
+        let mut inputs = setup_basic_output_groups();
+        for input in inputs.iter_mut().take(2) {
+            input.weight = u64::MAX;
+        }
+        let options = CoinSelectionOpt {
+            include_uneconomical: true,
+            ..bnb_setup_options(2845)
+        };
+        assert_eq!(
+            select_coin_bnb(&inputs, &options),
+            Err(SelectionError::ArithmeticOverflow)
+        );
+        // Skipping them leaves the single input worth spending
+        let options = bnb_setup_options(2830);
+        assert_eq!(
+            select_coin_bnb(&inputs, &options).unwrap().selected_inputs,
+            vec![2]
+        );
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_bnb_parallel() {
+        use super::select_coin_bnb_parallel;
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+
+        let mut rng = StdRng::seed_from_u64(7);
+        let values: Vec<u64> = (0..40).map(|_| rng.gen_range(1000..100_000)).collect();
+        let inputs = bnb_setup_core_output_groups(&values);
+        for target in [50_000, 123_457, 400_000, 1_000_001] {
+            let options = bnb_setup_core_options(target, 50);
+            let sequential = select_coin_bnb(&inputs, &options).unwrap();
+            let parallel = select_coin_bnb_parallel(&inputs, &options).unwrap();
+            // The subtree holding the sequential match finds it too, unless a lower waste one is found elsewhere
+            assert!(parallel.waste <= sequential.waste);
+            let (target_for_match, match_range) = match_window(&options).unwrap();
+            assert!(parallel.selected_value >= target_for_match);
+            assert!(parallel.selected_value <= target_for_match + match_range);
+            assert_eq!(
+                select_coin_bnb_parallel(&inputs, &options).unwrap(),
+                parallel
+            );
+        }
+
+        // Fewer inputs than the split depth
+        let inputs = setup_basic_output_groups();
+        let result = select_coin_bnb_parallel(&inputs, &bnb_setup_options(2845)).unwrap();
+        assert_eq!(
+            result.selected_inputs,
+            select_coin_bnb(&inputs, &bnb_setup_options(2845))
+                .unwrap()
+                .selected_inputs
+        );
+        assert_eq!(
+            select_coin_bnb_parallel(&inputs, &bnb_setup_options(2000)),
+            Err(SelectionError::NoSolutionFound)
+        );
+        assert_eq!(
+            select_coin_bnb_parallel(&inputs, &bnb_setup_options(12000)),
+            Err(SelectionError::InsufficientFunds)
+        );
+    }
+
+    #[test]
+    fn test_bnb_with_change_fallback() {
+        let inputs = vec![
+            OutputGroup {
+                value: 10000,
+                weight: 100,
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            },
+            OutputGroup {
+                value: 20000,
+                weight: 100,
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            },
+            OutputGroup {
+                value: 25000,
+                weight: 300,
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            },
+        ];
+        let mut options = bnb_setup_options(12000);
+        options.long_term_feerate = Some(FeeRate::from_sat_per_wu(0.25));
+        options.excess_strategy = ExcessStrategy::ToFee;
+
+        // No combination lands in the changeless window
+        assert!(matches!(
+            select_coin_bnb(&inputs, &options),
+            Err(SelectionError::NoSolutionFound)
+        ));
+
+        // Both the 20000 and the 25000 inputs fund a change output, the lighter one wastes less
+        let result = select_coin_bnb_with_change(&inputs, &options).unwrap();
+        assert_eq!(result.selected_inputs, vec![1]);
+        let expected_waste = calculate_fee(100, 0.5 - 0.25) + options.change_cost;
+        assert_eq!(result.waste.0, expected_waste as i64);
+
+        // Nothing can fund the change output either
+        let options = bnb_setup_options(60000);
+        assert!(matches!(
+            select_coin_bnb_with_change(&inputs, &options),
+            Err(SelectionError::InsufficientFunds)
+        ));
+    }
+
+    #[test]
+    fn test_bnb_with_change_prefers_changeless_match() {
+        let inputs = setup_basic_output_groups();
+        // 2000 - 100 fee covers 1895 + 5 base fee exactly
+        let options = bnb_setup_options(1895);
+        let result = select_coin_bnb_with_change(&inputs, &options).unwrap();
+        assert_eq!(result.selected_inputs, vec![1]);
+    }
+
+    #[test]
+    fn test_bnb_seeded_is_reproducible() {
+        let inputs =
+            bnb_setup_core_output_groups(&[CENT, 2 * CENT, 3 * CENT, 4 * CENT, 5 * CENT, 6 * CENT]);
+        let options = bnb_setup_core_options(7 * CENT, CENT / 2);
+        for seed in 0..20 {
+            let first = select_coin_bnb_seeded(&inputs, &options, seed).unwrap();
+            for _ in 0..5 {
+                let again = select_coin_bnb_seeded(&inputs, &options, seed).unwrap();
+                assert_eq!(first.selected_inputs, again.selected_inputs);
+                assert_eq!(first.waste.0, again.waste.0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_bnb_skips_identical_inputs() {
+        // 500 identical inputs, every selection of the same size is equivalent
+        let inputs = bnb_setup_core_output_groups(&[10_000; 500]);
+        let options = bnb_setup_core_options(200_000, 0);
+        let result = select_coin_bnb(&inputs, &options).unwrap();
+        assert_eq!(result.selected_inputs.len(), 20);
+        assert_eq!(result.selected_value, 200_000);
+
+        // No match between 19 and 20 inputs, without skipping the search would try every subset of 19 inputs
+        let sorted_inputs: Vec<(usize, &OutputGroup)> = inputs.iter().enumerate().collect();
+        let match_parameters = MatchParameters::new(&sorted_inputs, 195_000, 0, 1.0, None, None);
+        let mut state = SearchState::new(BNB_TOTAL_TRIES);
+        let result = bnb(
+            &sorted_inputs,
+            &mut vec![],
+            0,
+            0,
+            &mut state,
+            None::<&mut rand::rngs::StdRng>,
+            &match_parameters,
+        );
+        assert!(result.is_none());
+        let tries_consumed = BNB_TOTAL_TRIES - state.bnb_tries;
+        assert!(tries_consumed <= 100, "consumed {} tries", tries_consumed);
+    }
+
+    #[test]
+    fn test_bnb_explores_by_effective_value() {
+        // Sorted by value the 10_000 sats input comes first, but heavy as it is it has the lower effective value
+        let mut inputs = bnb_setup_core_output_groups(&[10_000, 8000, 2000]);
+        inputs[0].weight = 4000;
+        inputs[1].weight = 100;
+        inputs[2].weight = 100;
+        // Both {8000} and {10_000, 2000} match 7900 exactly, the input of highest effective value is tried first
+        let options = bnb_setup_core_options(7900, 0);
+        let result = select_coin_bnb(&inputs, &options).unwrap();
+        assert_eq!(result.selected_inputs, vec![1]);
+    }
+
+    #[test]
+    fn test_bnb_lookahead_prunes_hopeless_branches() {
+        // 200 inputs of 1000 sats, the target needs all of them but lies inside no match window.
+        // Omitting any single input leaves the remaining ones short of the target.
+        let inputs = bnb_setup_core_output_groups(&[1000; 200]);
+        let options = bnb_setup_core_options(199_500, 0);
+        assert!(matches!(
+            select_coin_bnb(&inputs, &options),
+            Err(SelectionError::NoSolutionFound)
+        ));
+
+        let sorted_inputs: Vec<(usize, &OutputGroup)> = inputs.iter().enumerate().collect();
+        let match_parameters = MatchParameters::new(&sorted_inputs, 199_500, 0, 1.0, None, None);
+        let mut state = SearchState::new(1_000_000);
+        let result = bnb(
+            &sorted_inputs,
+            &mut vec![],
+            0,
+            0,
+            &mut state,
+            None::<&mut rand::rngs::StdRng>,
+            &match_parameters,
+        );
+        assert!(result.is_none());
+        // Without the lookahead bound the search exhausts the whole budget
+        let tries_consumed = 1_000_000 - state.bnb_tries;
+        assert!(tries_consumed <= 400, "consumed {} tries", tries_consumed);
+    }
+
+    #[test]
+    fn test_bnb_with_fallback() {
+        // No subset lands inside the match window, the fallback picks the smallest overshoot
+        let inputs = setup_basic_output_groups();
+        let options = bnb_setup_options(2000);
+        assert!(matches!(
+            select_coin_bnb(&inputs, &options),
+            Err(SelectionError::NoSolutionFound)
+        ));
+        let result = select_coin_bnb_with_fallback(&inputs, &options).unwrap();
+        assert_eq!(result.selected_inputs, vec![2]);
+
+        // An exact match is still preferred
+        let options = bnb_setup_options(2820);
+        assert_eq!(
+            select_coin_bnb_with_fallback(&inputs, &options)
+                .unwrap()
+                .selected_inputs,
+            select_coin_bnb(&inputs, &options).unwrap().selected_inputs
+        );
+
+        // The inputs can not cover the target at all
+        let options = bnb_setup_options(12000);
+        assert!(matches!(
+            select_coin_bnb_with_fallback(&inputs, &options),
+            Err(SelectionError::InsufficientFunds)
+        ));
+    }
+
     #[test]
     fn test_bnb() {
         test_bnb_solution();
         test_bnb_no_solution();
+        #[cfg(feature = "std")]
+        test_bnb_through_select_coin();
     }
 }
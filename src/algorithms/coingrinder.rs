@@ -0,0 +1,466 @@
+use crate::{
+    types::{CoinSelectionOpt, SelectionError, SelectionOutput, WasteMetric, WeightedUtxo},
+    utils::{
+        calculate_change, calculate_fee, calculate_selection_fee, calculate_waste_detailed,
+        effective_value, exceeds_max_inputs, exceeds_max_tx_weight, validate_feerate,
+        validate_target,
+    },
+};
+use alloc::{vec, vec::Vec};
+
+/// Upper bound on the number of nodes the CoinGrinder search visits, same as Bitcoin Core.
+const CG_TOTAL_TRIES: u32 = 100_000;
+
+/// Performs coin selection using the CoinGrinder algorithm.
+///
+/// CoinGrinder searches for the input set with the lowest total weight whose effective value covers
+/// the target, the minimum change and the fee for the base and change output weight, as the selection
+/// always creates a change output. The fee for the base weight is raised to `min_absolute_fee`, as in Branch
+/// and Bound. This is the preferred strategy in high feerate environments, where
+/// every additional weight unit is expensive. When two sets have the same weight, the one with the
+/// higher effective value wins. Sets spending more than `max_inputs` inputs or heavier than `max_tx_weight`
+/// allows are pruned.
+///
+/// Returns `InsufficientFunds` if the inputs can not cover the target and `NoSolutionFound`
+/// if no solution is found within the search budget. Returns `ArithmeticOverflow` if the effective values of the
+/// inputs or the target do not sum within a `u64`, see [`validate_target`].
+pub fn select_coin_coingrinder<T: WeightedUtxo>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+) -> Result<SelectionOutput, SelectionError> {
+    validate_feerate(*options.target_feerate)?;
+    validate_target(inputs, options)?;
+    let target = options
+        .target_value
+        .checked_add(options.min_change_value)
+        .and_then(|target| {
+            target.checked_add(
+                calculate_fee(options.base_weight, *options.target_feerate)
+                    .max(options.min_absolute_fee),
+            )
+        })
+        .and_then(|target| {
+            target.checked_add(calculate_fee(
+                options.change_weight,
+                *options.target_feerate,
+            ))
+        })
+        .ok_or(SelectionError::ArithmeticOverflow)?;
+
+    // Inputs with zero effective value can never help reaching the target, they only add weight.
+    // Sorting by descending effective value, and by ascending weight among equal values.
+    let mut sorted_inputs: Vec<(usize, u64, u64)> = inputs
+        .iter()
+        .enumerate()
+        .map(|(index, input)| {
+            (
+                index,
+                effective_value(input, *options.target_feerate),
+                input.weight(),
+            )
+        })
+        .filter(|&(_, value, _)| value > 0)
+        .collect();
+    sorted_inputs.sort_by(|a, b| b.1.cmp(&a.1).then(a.2.cmp(&b.2)));
+
+    // Lookahead tables: sum of the effective values and lowest weight among the inputs from a position onwards.
+    // Every selection sums to no more than all of the inputs, so the accumulators below can not overflow.
+    let len = sorted_inputs.len();
+    let mut remaining_value = vec![0u64; len + 1];
+    let mut min_tail_weight = vec![u64::MAX; len + 1];
+    for pos in (0..len).rev() {
+        remaining_value[pos] = remaining_value[pos + 1]
+            .checked_add(sorted_inputs[pos].1)
+            .ok_or(SelectionError::ArithmeticOverflow)?;
+        min_tail_weight[pos] = min_tail_weight[pos + 1].min(sorted_inputs[pos].2);
+    }
+
+    if remaining_value[0] < target {
+        return Err(SelectionError::InsufficientFunds);
+    }
+
+    // The selection holds positions in `sorted_inputs`.
+    let mut selection: Vec<usize> = Vec::new();
+    let mut acc_value: u64 = 0;
+    let mut acc_weight: u64 = 0;
+    let mut acc_count: usize = 0;
+    let mut best_selection: Vec<usize> = Vec::new();
+    let mut best_weight: u64 = u64::MAX;
+    let mut best_value: u64 = 0;
+    let mut next: usize = 0;
+    let mut tries: u32 = 0;
+
+    loop {
+        let mut backtrack = true;
+        if next < len && tries < CG_TOTAL_TRIES {
+            tries += 1;
+            // Exploring the inclusion branch of the input at `next`
+            let (index, value, weight) = sorted_inputs[next];
+            selection.push(next);
+            acc_value += value;
+            acc_weight += weight;
+            acc_count += inputs[index].input_count();
+            next += 1;
+
+            if exceeds_max_inputs(options, acc_count) || exceeds_max_tx_weight(options, acc_weight)
+            {
+                // Too many inputs or too heavy already
+            } else if acc_value >= target {
+                // Adding more inputs only increases the weight, record the candidate and backtrack.
+                if acc_weight < best_weight || (acc_weight == best_weight && acc_value > best_value)
+                {
+                    best_selection.clone_from(&selection);
+                    best_weight = acc_weight;
+                    best_value = acc_value;
+                }
+            } else if acc_weight >= best_weight
+                || acc_value + remaining_value[next] < target
+                || acc_weight.saturating_add(min_tail_weight[next]) > best_weight
+            {
+                // This branch can not reach the target, or can not get lighter than the best candidate.
+            } else {
+                backtrack = false;
+            }
+        } else if tries >= CG_TOTAL_TRIES {
+            break;
+        }
+
+        if backtrack {
+            // Exploring the omission branch of the last included input.
+            match selection.pop() {
+                Some(last) => {
+                    let (index, value, weight) = sorted_inputs[last];
+                    acc_value -= value;
+                    acc_weight -= weight;
+                    acc_count -= inputs[index].input_count();
+                    next = last + 1;
+                    // Omitting an input and then including an identical one leads to the same sets.
+                    while next < len
+                        && sorted_inputs[next].1 == value
+                        && sorted_inputs[next].2 == weight
+                    {
+                        next += 1;
+                    }
+                }
+                None => break,
+            }
+        }
+    }
+
+    if best_selection.is_empty() {
+        return Err(SelectionError::NoSolutionFound);
+    }
+
+    let mut selected_inputs: Vec<usize> = best_selection
+        .iter()
+        .map(|&pos| sorted_inputs[pos].0)
+        .collect();
+    let accumulated_value = selected_inputs
+        .iter()
+        .try_fold(0u64, |total, &i| total.checked_add(inputs[i].value()))
+        .ok_or(SelectionError::ArithmeticOverflow)?;
+    let accumulated_weight: u64 = selected_inputs.iter().map(|&i| inputs[i].weight()).sum();
+    let estimated_fees = calculate_selection_fee(options, accumulated_weight);
+    let waste_breakdown = calculate_waste_detailed(
+        options,
+        accumulated_value,
+        accumulated_weight,
+        estimated_fees,
+    );
+    selected_inputs.sort_unstable();
+    Ok(SelectionOutput {
+        selected_inputs,
+        waste: WasteMetric(waste_breakdown.total()),
+        waste_breakdown,
+        change_value: calculate_change(options, accumulated_value, estimated_fees),
+        estimated_fee: estimated_fees,
+        selected_value: accumulated_value,
+        selected_weight: accumulated_weight,
+    })
+}
+
+#[cfg(test)]
+mod test {
+
+    #[cfg(feature = "std")]
+    use crate::algorithms::{knapsack::select_coin_knapsack, srd::select_coin_srd};
+    use crate::{
+        algorithms::coingrinder::select_coin_coingrinder,
+        types::{CoinSelectionOpt, ExcessStrategy, FeeRate, OutputGroup, SelectionError},
+    };
+
+    fn setup_coingrinder_output_groups() -> Vec<OutputGroup> {
+        vec![
+            OutputGroup {
+                value: 5200,
+                weight: 40,
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            },
+            OutputGroup {
+                value: 2600,
+                weight: 300,
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            },
+            OutputGroup {
+                value: 2800,
+                weight: 300,
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            },
+            OutputGroup {
+                value: 2900,
+                weight: 300,
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            },
+        ]
+    }
+
+    fn setup_options(target_value: u64) -> CoinSelectionOpt {
+        CoinSelectionOpt {
+            target_value,
+            target_feerate: FeeRate::from_sat_per_wu(1.0),
+            long_term_feerate: Some(FeeRate::from_sat_per_wu(0.4)),
+            min_absolute_fee: 0,
+            base_weight: 10,
+            change_weight: 0,
+            change_cost: 10,
+            avg_input_weight: 20,
+            avg_output_weight: 10,
+            min_change_value: 500,
+            excess_strategy: ExcessStrategy::ToChange,
+            max_inputs: None,
+            bnb_tries: None,
+            include_uneconomical: false,
+            max_excess: None,
+            max_tx_weight: None,
+            knapsack_iterations: None,
+            knapsack_stagnation: None,
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn selected_weight(inputs: &[OutputGroup], selected_inputs: &[usize]) -> u64 {
+        selected_inputs.iter().map(|&i| inputs[i].weight).sum()
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_coingrinder_beats_knapsack_on_weight() {
+        // Adjusted target is 4490 + 500 + 10 = 5000. The single light coin covers it, while
+        // knapsack only considers coins smaller than the adjusted target, all of them heavy.
+        let inputs = setup_coingrinder_output_groups();
+        let options = setup_options(4490);
+        let coingrinder = select_coin_coingrinder(&inputs, &options).unwrap();
+        assert_eq!(coingrinder.selected_inputs, vec![0]);
+        for _ in 0..10 {
+            let knapsack = select_coin_knapsack(&inputs, &options).unwrap();
+            assert!(
+                selected_weight(&inputs, &coingrinder.selected_inputs)
+                    < selected_weight(&inputs, &knapsack.selected_inputs)
+            );
+        }
+    }
+
+    #[test]
+    fn test_coingrinder_prefers_lighter_combination() {
+        // Two heavy coins reach the target, as does a heavy and a light coin combined.
+        let inputs = vec![
+            OutputGroup {
+                value: 4000,
+                weight: 400,
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            },
+            OutputGroup {
+                value: 3900,
+                weight: 400,
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            },
+            OutputGroup {
+                value: 1600,
+                weight: 60,
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            },
+        ];
+        let options = setup_options(4490);
+        let result = select_coin_coingrinder(&inputs, &options).unwrap();
+        let mut selected_inputs = result.selected_inputs.clone();
+        selected_inputs.sort();
+        assert_eq!(selected_inputs, vec![0, 2]);
+
+        // The lighter combination no longer pays the minimum absolute fee
+        let options = CoinSelectionOpt {
+            min_absolute_fee: 200,
+            ..setup_options(4490)
+        };
+        let result = select_coin_coingrinder(&inputs, &options).unwrap();
+        assert_eq!(result.selected_inputs, vec![0, 1]);
+        assert!(
+            result.selected_value
+                >= options.target_value + options.min_change_value + options.min_absolute_fee
+        );
+    }
+
+    #[test]
+    fn test_coingrinder_max_inputs() {
+        // The light coin is a group of three UTXOs
+        let inputs = vec![
+            OutputGroup {
+                value: 4000,
+                weight: 400,
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            },
+            OutputGroup {
+                value: 3900,
+                weight: 400,
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            },
+            OutputGroup {
+                value: 1600,
+                weight: 60,
+                input_count: 3,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            },
+        ];
+        let mut options = setup_options(4490);
+        options.max_inputs = Some(3);
+        let result = select_coin_coingrinder(&inputs, &options).unwrap();
+        let mut selected_inputs = result.selected_inputs.clone();
+        selected_inputs.sort();
+        assert_eq!(selected_inputs, vec![0, 1]);
+
+        options.max_inputs = Some(1);
+        let result = select_coin_coingrinder(&inputs, &options);
+        assert!(matches!(result, Err(SelectionError::NoSolutionFound)));
+    }
+
+    #[test]
+    fn test_coingrinder_weight_tie_breaks_on_value() {
+        let inputs = vec![
+            OutputGroup {
+                value: 5500,
+                weight: 100,
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            },
+            OutputGroup {
+                value: 6000,
+                weight: 100,
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            },
+        ];
+        let options = setup_options(4490);
+        let result = select_coin_coingrinder(&inputs, &options).unwrap();
+        assert_eq!(result.selected_inputs, vec![1]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_coingrinder_beats_knapsack_and_srd_on_legacy_pool() {
+        // Many heavy legacy P2PKH inputs (148 vB) and a few light P2WPKH inputs (68 vB)
+        let mut inputs: Vec<OutputGroup> = (0..20)
+            .map(|_| OutputGroup {
+                value: 10_000,
+                weight: 592,
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            })
+            .collect();
+        inputs.extend((0..3).map(|_| OutputGroup {
+            value: 30_000,
+            weight: 272,
+            input_count: 1,
+            creation_sequence: None,
+            ancestor_fee: 0,
+            ancestor_weight: 0,
+            block_height: None,
+        }));
+        let options = CoinSelectionOpt {
+            target_value: 50_000,
+            target_feerate: FeeRate::from_sat_per_wu(10.0),
+            long_term_feerate: Some(FeeRate::from_sat_per_wu(1.0)),
+            min_absolute_fee: 0,
+            base_weight: 40,
+            change_weight: 124,
+            change_cost: 300,
+            avg_input_weight: 272,
+            avg_output_weight: 124,
+            min_change_value: 500,
+            excess_strategy: ExcessStrategy::ToChange,
+            max_inputs: None,
+            bnb_tries: None,
+            include_uneconomical: false,
+            max_excess: None,
+            max_tx_weight: None,
+            knapsack_iterations: None,
+            knapsack_stagnation: None,
+        };
+
+        let coingrinder = select_coin_coingrinder(&inputs, &options).unwrap();
+        let mut selected_inputs = coingrinder.selected_inputs.clone();
+        selected_inputs.sort();
+        assert_eq!(selected_inputs, vec![20, 21]);
+        let coingrinder_weight = selected_weight(&inputs, &coingrinder.selected_inputs);
+        for _ in 0..10 {
+            let knapsack = select_coin_knapsack(&inputs, &options).unwrap();
+            assert!(coingrinder_weight <= selected_weight(&inputs, &knapsack.selected_inputs));
+            let srd = select_coin_srd(&inputs, &options).unwrap();
+            assert!(coingrinder_weight <= selected_weight(&inputs, &srd.selected_inputs));
+        }
+    }
+
+    #[test]
+    fn test_coingrinder_insufficient_funds() {
+        let inputs = setup_coingrinder_output_groups();
+        let options = setup_options(20000);
+        let result = select_coin_coingrinder(&inputs, &options);
+        assert!(matches!(result, Err(SelectionError::InsufficientFunds)));
+    }
+}
@@ -0,0 +1,346 @@
+use crate::{
+    types::{
+        CoinSelectionOpt, ExcessStrategy, SelectionError, SelectionOutput, WasteMetric,
+        WeightedUtxo,
+    },
+    utils::{
+        calculate_change, calculate_selection_fee, calculate_waste_detailed, effective_value,
+        exceeds_max_inputs, exceeds_max_tx_weight, validate_feerate,
+    },
+};
+use alloc::vec::Vec;
+
+/// Performs coin selection consolidating as many inputs as possible.
+///
+/// When the current feerate is below the long term feerate, spending an input now is cheaper than spending it later.
+/// Every input with a positive effective value is then selected, as long as the selection still funds a change
+/// output of at least `min_change_value`. The selection maximizes the number of inputs rather than minimizing the waste.
+///
+/// Returns `NoSolutionFound` if consolidation is not beneficial, i.e. `long_term_feerate` is `None` or not higher
+/// than `target_feerate` or the economical inputs are more than `max_inputs` or heavier than `max_tx_weight` allows,
+/// and `InsufficientFunds` if the economical inputs can not cover the target.
+pub fn select_coin_consolidate<T: WeightedUtxo>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+) -> Result<SelectionOutput, SelectionError> {
+    validate_feerate(*options.target_feerate)?;
+    match options.long_term_feerate {
+        Some(long_term_feerate) if long_term_feerate > options.target_feerate => {}
+        _ => return Err(SelectionError::NoSolutionFound),
+    }
+
+    let mut accumulated_value: u64 = 0;
+    let mut accumulated_weight: u64 = 0;
+    let mut input_count: usize = 0;
+    let mut selected_inputs: Vec<usize> = Vec::new();
+
+    for (index, input) in inputs.iter().enumerate() {
+        if effective_value(input, *options.target_feerate) > 0 {
+            accumulated_value += input.value();
+            accumulated_weight += input.weight();
+            input_count += input.input_count();
+            selected_inputs.push(index);
+        }
+    }
+    if exceeds_max_inputs(options, input_count)
+        || exceeds_max_tx_weight(options, accumulated_weight)
+    {
+        return Err(SelectionError::NoSolutionFound);
+    }
+
+    let estimated_fees = calculate_selection_fee(options, accumulated_weight);
+    if accumulated_value
+        < options.target_value
+            + estimated_fees.max(options.min_absolute_fee)
+            + options.min_change_value
+    {
+        return Err(SelectionError::InsufficientFunds);
+    }
+
+    let waste_breakdown = calculate_waste_detailed(
+        options,
+        accumulated_value,
+        accumulated_weight,
+        estimated_fees,
+    );
+    selected_inputs.sort_unstable();
+    Ok(SelectionOutput {
+        selected_inputs,
+        waste: WasteMetric(waste_breakdown.total()),
+        waste_breakdown,
+        change_value: calculate_change(options, accumulated_value, estimated_fees),
+        estimated_fee: estimated_fees,
+        selected_value: accumulated_value,
+        selected_weight: accumulated_weight,
+    })
+}
+
+/// Appends extra inputs to the `selection` of any algorithm, while spending them now is cheaper than later.
+///
+/// When the `target_feerate` is below the `long_term_feerate`, every input added to a selection lowers its waste by
+/// `weight * (long_term_feerate - target_feerate)`. Unless the excess goes to a change output, the effective value of
+/// the input also adds to the excess, which usually outweighs that saving. The unselected inputs with a positive
+/// effective value are visited from the smallest to the largest, and appended as long as their marginal waste is
+/// negative, the added weight stays within `max_added_weight` and the selection within `max_inputs` and
+/// `max_tx_weight`.
+///
+/// The waste of the returned [`SelectionOutput`] is recomputed. The selection is returned untouched when
+/// consolidating is not beneficial.
+pub fn consolidate_selection<T: WeightedUtxo>(
+    inputs: &[T],
+    selection: SelectionOutput,
+    options: &CoinSelectionOpt,
+    max_added_weight: u64,
+) -> SelectionOutput {
+    let long_term_feerate = match options.long_term_feerate {
+        Some(long_term_feerate) if long_term_feerate > options.target_feerate => long_term_feerate,
+        _ => return selection,
+    };
+
+    let mut selected_inputs = selection.selected_inputs;
+    let mut input_count: usize = selected_inputs
+        .iter()
+        .map(|&i| inputs[i].input_count())
+        .sum();
+    let mut candidates: Vec<(usize, u64)> = (0..inputs.len())
+        .filter(|index| !selected_inputs.contains(index))
+        .map(|index| {
+            (
+                index,
+                effective_value(&inputs[index], *options.target_feerate),
+            )
+        })
+        .filter(|&(_, value)| value > 0)
+        .collect();
+    candidates.sort_by_key(|&(_, value)| value);
+
+    let selected_weight: u64 = selected_inputs.iter().map(|&i| inputs[i].weight()).sum();
+    let mut added_weight: u64 = 0;
+    for (index, value) in candidates {
+        let input = &inputs[index];
+        // The fee saved by spending the input now rather than later, hence negative
+        let mut marginal_waste =
+            input.weight() as f64 * (*options.target_feerate as f64 - *long_term_feerate as f64);
+        if !matches!(
+            options.excess_strategy,
+            ExcessStrategy::ToChange | ExcessStrategy::Split
+        ) {
+            marginal_waste += value as f64;
+        }
+        if marginal_waste >= 0.0
+            || added_weight + input.weight() > max_added_weight
+            || exceeds_max_inputs(options, input_count + input.input_count())
+            || exceeds_max_tx_weight(options, selected_weight + added_weight + input.weight())
+        {
+            continue;
+        }
+        selected_inputs.push(index);
+        added_weight += input.weight();
+        input_count += input.input_count();
+    }
+
+    let accumulated_value: u64 = selected_inputs.iter().map(|&i| inputs[i].value()).sum();
+    let accumulated_weight: u64 = selected_inputs.iter().map(|&i| inputs[i].weight()).sum();
+    let estimated_fees = calculate_selection_fee(options, accumulated_weight);
+    let waste_breakdown = calculate_waste_detailed(
+        options,
+        accumulated_value,
+        accumulated_weight,
+        estimated_fees,
+    );
+    selected_inputs.sort_unstable();
+    SelectionOutput {
+        selected_inputs,
+        waste: WasteMetric(waste_breakdown.total()),
+        waste_breakdown,
+        change_value: calculate_change(options, accumulated_value, estimated_fees),
+        estimated_fee: estimated_fees,
+        selected_value: accumulated_value,
+        selected_weight: accumulated_weight,
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use crate::{
+        algorithms::{
+            consolidate::{consolidate_selection, select_coin_consolidate},
+            largestfirst::select_coin_largestfirst,
+        },
+        types::{CoinSelectionOpt, ExcessStrategy, FeeRate, OutputGroup, SelectionError},
+    };
+
+    fn setup_consolidate_output_groups() -> Vec<OutputGroup> {
+        vec![
+            OutputGroup {
+                value: 1000,
+                weight: 100,
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            },
+            OutputGroup {
+                value: 40,
+                weight: 100,
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            },
+            OutputGroup {
+                value: 3000,
+                weight: 300,
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            },
+            OutputGroup {
+                value: 800,
+                weight: 100,
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            },
+        ]
+    }
+
+    fn setup_options(target_value: u64, long_term_feerate: Option<FeeRate>) -> CoinSelectionOpt {
+        CoinSelectionOpt {
+            target_value,
+            target_feerate: FeeRate::from_sat_per_wu(0.5),
+            long_term_feerate,
+            min_absolute_fee: 0,
+            base_weight: 10,
+            change_weight: 50,
+            change_cost: 10,
+            avg_input_weight: 20,
+            avg_output_weight: 10,
+            min_change_value: 500,
+            excess_strategy: ExcessStrategy::ToChange,
+            max_inputs: None,
+            bnb_tries: None,
+            include_uneconomical: false,
+            max_excess: None,
+            max_tx_weight: None,
+            knapsack_iterations: None,
+            knapsack_stagnation: None,
+        }
+    }
+
+    #[test]
+    fn test_consolidate_selects_every_economical_input() {
+        let inputs = setup_consolidate_output_groups();
+        let options = setup_options(1000, Some(FeeRate::from_sat_per_wu(2.0)));
+        let result = select_coin_consolidate(&inputs, &options).unwrap();
+        // The 40 sats input costs 50 sats to spend
+        assert_eq!(result.selected_inputs, vec![0, 2, 3]);
+    }
+
+    #[test]
+    fn test_consolidate_waste_is_negative() {
+        let inputs = setup_consolidate_output_groups();
+        let options = CoinSelectionOpt {
+            target_feerate: FeeRate::from_sat_per_wu(1.0),
+            ..setup_options(1000, Some(FeeRate::from_sat_per_wu(5.0)))
+        };
+        let result = select_coin_consolidate(&inputs, &options).unwrap();
+        assert_eq!(result.selected_inputs, vec![0, 2, 3]);
+        // Spending 500 weight units now saves 4 sats each, far more than the cost of change
+        assert_eq!(result.waste.0, -4 * 500 + options.change_cost as i64);
+
+        // Spending fewer inputs forgoes part of the saving
+        let largest_first = select_coin_largestfirst(&inputs, &options).unwrap();
+        assert!(largest_first.waste.0 < 0);
+        assert!(result.waste.0 < largest_first.waste.0);
+    }
+
+    #[test]
+    fn test_consolidate_not_beneficial() {
+        let inputs = setup_consolidate_output_groups();
+        for long_term_feerate in [
+            None,
+            Some(FeeRate::from_sat_per_wu(0.5)),
+            Some(FeeRate::from_sat_per_wu(0.2)),
+        ] {
+            let options = setup_options(1000, long_term_feerate);
+            let result = select_coin_consolidate(&inputs, &options);
+            assert!(matches!(result, Err(SelectionError::NoSolutionFound)));
+        }
+    }
+
+    #[test]
+    fn test_consolidate_insufficient() {
+        let inputs = setup_consolidate_output_groups();
+        // 4800 sats minus 250 sats fee can not fund 4100 sats and a 500 sats change
+        let options = setup_options(4100, Some(FeeRate::from_sat_per_wu(2.0)));
+        let result = select_coin_consolidate(&inputs, &options);
+        assert!(matches!(result, Err(SelectionError::InsufficientFunds)));
+    }
+
+    fn setup_opportunistic_output_groups() -> Vec<OutputGroup> {
+        // P2WPKH inputs of 68 vB, the last one costs more to spend than it is worth
+        [20_000, 1500, 1200, 1000, 50]
+            .iter()
+            .map(|&value| OutputGroup {
+                value,
+                weight: 272,
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_consolidate_selection_adds_small_inputs() {
+        let inputs = setup_opportunistic_output_groups();
+        // 1 sat/vB now, 10 sat/vB in the long term
+        let options = CoinSelectionOpt {
+            target_feerate: FeeRate::from_sat_per_vb(1.0),
+            ..setup_options(10_000, Some(FeeRate::from_sat_per_vb(10.0)))
+        };
+        let selection = select_coin_largestfirst(&inputs, &options).unwrap();
+        assert_eq!(selection.selected_inputs, vec![0]);
+
+        let result = consolidate_selection(&inputs, selection, &options, 10_000);
+        assert_eq!(result.selected_inputs, vec![0, 1, 2, 3]);
+
+        // Only two inputs fit within the added weight
+        let selection = select_coin_largestfirst(&inputs, &options).unwrap();
+        let result = consolidate_selection(&inputs, selection, &options, 600);
+        assert_eq!(result.selected_inputs, vec![0, 2, 3]);
+    }
+
+    #[test]
+    fn test_consolidate_selection_not_beneficial() {
+        let inputs = setup_opportunistic_output_groups();
+        // 10 sat/vB now, 1 sat/vB in the long term
+        let options = CoinSelectionOpt {
+            target_feerate: FeeRate::from_sat_per_vb(10.0),
+            ..setup_options(10_000, Some(FeeRate::from_sat_per_vb(1.0)))
+        };
+        let selection = select_coin_largestfirst(&inputs, &options).unwrap();
+        let result = consolidate_selection(&inputs, selection, &options, 10_000);
+        assert_eq!(result.selected_inputs, vec![0]);
+
+        // Dropping the extra value to fees outweighs the savings
+        let options = CoinSelectionOpt {
+            target_feerate: FeeRate::from_sat_per_vb(1.0),
+            excess_strategy: ExcessStrategy::ToFee,
+            ..setup_options(10_000, Some(FeeRate::from_sat_per_vb(10.0)))
+        };
+        let selection = select_coin_largestfirst(&inputs, &options).unwrap();
+        let result = consolidate_selection(&inputs, selection, &options, 10_000);
+        assert_eq!(result.selected_inputs, vec![0]);
+    }
+}
@@ -0,0 +1,281 @@
+use crate::{
+    types::{CoinSelectionOpt, SelectionError, SelectionOutput, WasteMetric, WeightedUtxo},
+    utils::{
+        calculate_change, calculate_fee, calculate_selection_fee, calculate_waste_detailed,
+        effective_value, exceeds_max_inputs, exceeds_max_tx_weight, validate_feerate,
+        validate_target,
+    },
+};
+use alloc::{vec, vec::Vec};
+
+/// Default size, in sats, of the buckets the effective values are rounded to.
+pub const DP_GRANULARITY: u64 = 10;
+
+/// Default upper bound, in sats, on the adjusted target the dynamic programming table is built for.
+///
+/// The table holds twice as many entries as the target has buckets.
+pub const DP_MAX_TARGET: u64 = 5_000_000;
+
+/// Performs coin selection via dynamic programming over the achievable effective-value sums.
+///
+/// Uses [`DP_GRANULARITY`] and [`DP_MAX_TARGET`], see [`select_coin_dp_with_limits`].
+pub fn select_coin_dp<T: WeightedUtxo>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+) -> Result<SelectionOutput, SelectionError> {
+    select_coin_dp_with_limits(inputs, options, DP_GRANULARITY, DP_MAX_TARGET)
+}
+
+/// Performs coin selection via dynamic programming over the achievable effective-value sums.
+///
+/// Effective values are rounded down to multiples of `granularity` sats, and the table records which sums
+/// are reachable by a subset of the inputs. The subset with the smallest sum covering the adjusted target
+/// `target_value + min_change_value + fee(base_weight)` is reconstructed from the table, the fee for the base
+/// weight being raised to `min_absolute_fee`. Rounding down keeps
+/// every returned selection valid, at the cost of an excess of up to `granularity` sats per selected input.
+///
+/// Returns `InsufficientFunds` if the inputs can not cover the adjusted target, and `NoSolutionFound` if the
+/// adjusted target exceeds `max_target`, is lost to the rounding, or if the subset spends more than `max_inputs`
+/// inputs or goes over `max_tx_weight`, which the table does not track. Returns `ArithmeticOverflow` if the
+/// effective values of the inputs or the target do not sum within a `u64`, see [`validate_target`].
+pub fn select_coin_dp_with_limits<T: WeightedUtxo>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+    granularity: u64,
+    max_target: u64,
+) -> Result<SelectionOutput, SelectionError> {
+    validate_feerate(*options.target_feerate)?;
+    validate_target(inputs, options)?;
+    let adjusted_target = options
+        .target_value
+        .checked_add(options.min_change_value)
+        .and_then(|target| {
+            target.checked_add(
+                calculate_fee(options.base_weight, *options.target_feerate)
+                    .max(options.min_absolute_fee),
+            )
+        })
+        .ok_or(SelectionError::ArithmeticOverflow)?;
+    let effective_values: Vec<u64> = inputs
+        .iter()
+        .map(|input| effective_value(input, *options.target_feerate))
+        .collect();
+    let total_effective_value = effective_values
+        .iter()
+        .try_fold(0u64, |total, &value| total.checked_add(value))
+        .ok_or(SelectionError::ArithmeticOverflow)?;
+    if total_effective_value < adjusted_target {
+        return Err(SelectionError::InsufficientFunds);
+    }
+    if adjusted_target > max_target || granularity == 0 {
+        return Err(SelectionError::NoSolutionFound);
+    }
+
+    let bucketed_values: Vec<u64> = effective_values
+        .iter()
+        .map(|value| value / granularity)
+        .collect();
+    let mut selected_inputs =
+        min_overshoot_subset(&bucketed_values, adjusted_target.div_ceil(granularity))
+            .ok_or(SelectionError::NoSolutionFound)?;
+    let input_count: usize = selected_inputs
+        .iter()
+        .map(|&i| inputs[i].input_count())
+        .sum();
+    let accumulated_weight: u64 = selected_inputs.iter().map(|&i| inputs[i].weight()).sum();
+    if exceeds_max_inputs(options, input_count)
+        || exceeds_max_tx_weight(options, accumulated_weight)
+    {
+        return Err(SelectionError::NoSolutionFound);
+    }
+
+    let accumulated_value = selected_inputs
+        .iter()
+        .try_fold(0u64, |total, &i| total.checked_add(inputs[i].value()))
+        .ok_or(SelectionError::ArithmeticOverflow)?;
+    let estimated_fee = calculate_selection_fee(options, accumulated_weight);
+    let waste_breakdown = calculate_waste_detailed(
+        options,
+        accumulated_value,
+        accumulated_weight,
+        estimated_fee,
+    );
+    selected_inputs.sort_unstable();
+    Ok(SelectionOutput {
+        selected_inputs,
+        waste: WasteMetric(waste_breakdown.total()),
+        waste_breakdown,
+        change_value: calculate_change(options, accumulated_value, estimated_fee),
+        estimated_fee,
+        selected_value: accumulated_value,
+        selected_weight: accumulated_weight,
+    })
+}
+
+/// Returns the positions of the subset of `values` with the smallest sum not below `target`.
+///
+/// The table spans the sums up to `2 * target`: any value above `target` alone covers it, so larger sums
+/// are never the smallest covering one. Runs in `O(values.len() * target)` time and `O(target)` memory.
+pub(crate) fn min_overshoot_subset(values: &[u64], target: u64) -> Option<Vec<usize>> {
+    if target == 0 {
+        return Some(Vec::new());
+    }
+    let cap = target.checked_mul(2)?;
+    // `reached_by[sum]` holds the position of the value that first reached `sum`, the sum without it was
+    // reached by values at lower positions only.
+    let mut reached_by: Vec<Option<usize>> = vec![None; cap as usize + 1];
+    let mut reachable = vec![false; cap as usize + 1];
+    reachable[0] = true;
+    for (pos, &value) in values.iter().enumerate() {
+        let value = value.min(cap);
+        if value == 0 {
+            continue;
+        }
+        for sum in (value..=cap).rev() {
+            if !reachable[sum as usize] && reachable[(sum - value) as usize] {
+                reachable[sum as usize] = true;
+                reached_by[sum as usize] = Some(pos);
+            }
+        }
+    }
+
+    let mut sum = (target..=cap).find(|&sum| reachable[sum as usize])?;
+    let mut subset = Vec::new();
+    while sum > 0 {
+        let pos = reached_by[sum as usize]?;
+        subset.push(pos);
+        sum -= values[pos].min(cap);
+    }
+    subset.reverse();
+    Some(subset)
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::min_overshoot_subset;
+    use crate::{
+        algorithms::dp::{select_coin_dp, select_coin_dp_with_limits},
+        types::{CoinSelectionOpt, ExcessStrategy, FeeRate, OutputGroup, SelectionError},
+    };
+    #[cfg(feature = "std")]
+    use crate::{algorithms::knapsack::select_coin_knapsack, utils::effective_value};
+    #[cfg(feature = "std")]
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    fn setup_output_groups(values: &[u64]) -> Vec<OutputGroup> {
+        values
+            .iter()
+            .map(|&value| OutputGroup {
+                value,
+                weight: 0,
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            })
+            .collect()
+    }
+
+    fn setup_options(target_value: u64) -> CoinSelectionOpt {
+        CoinSelectionOpt {
+            target_value,
+            target_feerate: FeeRate::from_sat_per_wu(1.0),
+            long_term_feerate: Some(FeeRate::from_sat_per_wu(0.5)),
+            min_absolute_fee: 0,
+            base_weight: 10,
+            change_weight: 50,
+            change_cost: 10,
+            avg_input_weight: 20,
+            avg_output_weight: 10,
+            min_change_value: 500,
+            excess_strategy: ExcessStrategy::ToChange,
+            max_inputs: None,
+            bnb_tries: None,
+            include_uneconomical: false,
+            max_excess: None,
+            max_tx_weight: None,
+            knapsack_iterations: None,
+            knapsack_stagnation: None,
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn excess(
+        inputs: &[OutputGroup],
+        selected_inputs: &[usize],
+        options: &CoinSelectionOpt,
+    ) -> u64 {
+        let accumulated: u64 = selected_inputs
+            .iter()
+            .map(|&i| effective_value(&inputs[i], *options.target_feerate))
+            .sum();
+        accumulated - (options.target_value + options.min_change_value + 10)
+    }
+
+    #[test]
+    fn test_min_overshoot_subset() {
+        assert_eq!(min_overshoot_subset(&[5, 9, 4], 12), Some(vec![1, 2]));
+        assert_eq!(min_overshoot_subset(&[5, 9, 4], 9), Some(vec![1]));
+        assert_eq!(min_overshoot_subset(&[5, 40], 12), Some(vec![1]));
+        assert_eq!(min_overshoot_subset(&[5, 4], 12), None);
+        assert_eq!(min_overshoot_subset(&[5, 4], u64::MAX), None);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_dp_beats_knapsack_on_adversarial_pool() {
+        // 40 coins of odd sizes, the adjusted target is the value of a handful of them
+        let mut rng = StdRng::seed_from_u64(3);
+        let values: Vec<u64> = (0..40).map(|_| rng.gen_range(10_000..100_000)).collect();
+        let adjusted_target: u64 = values[3] + values[11] + values[17] + values[29] + values[36];
+        let inputs = setup_output_groups(&values);
+        let options = setup_options(adjusted_target - 510);
+
+        let dp = select_coin_dp_with_limits(&inputs, &options, 1, u64::MAX).unwrap();
+        let dp_excess = excess(&inputs, &dp.selected_inputs, &options);
+        assert_eq!(dp_excess, 0);
+        for _ in 0..10 {
+            let knapsack = select_coin_knapsack(&inputs, &options).unwrap();
+            assert!(dp_excess <= excess(&inputs, &knapsack.selected_inputs, &options));
+        }
+
+        // Bucketing by 10 sats costs at most 10 sats of excess per selected input
+        let dp = select_coin_dp(&inputs, &options).unwrap();
+        assert!(
+            excess(&inputs, &dp.selected_inputs, &options) < 10 * dp.selected_inputs.len() as u64
+        );
+    }
+
+    #[test]
+    fn test_dp_limits() {
+        let inputs = setup_output_groups(&[3000, 5000, 2000]);
+        let result = select_coin_dp(&inputs, &setup_options(20_000));
+        assert!(matches!(result, Err(SelectionError::InsufficientFunds)));
+
+        let result = select_coin_dp_with_limits(&inputs, &setup_options(6000), 10, 5000);
+        assert!(matches!(result, Err(SelectionError::NoSolutionFound)));
+
+        let result = select_coin_dp_with_limits(&inputs, &setup_options(6000), 10, 10_000).unwrap();
+        let mut selected_inputs = result.selected_inputs.clone();
+        selected_inputs.sort();
+        assert_eq!(selected_inputs, vec![1, 2]);
+    }
+
+    #[test]
+    fn test_dp_min_absolute_fee() {
+        let inputs = setup_output_groups(&[3000, 5000, 2000]);
+        // Without a minimum fee, the coins of 5000 and 2000 sats are enough, see `test_dp_limits`
+        let options = CoinSelectionOpt {
+            min_absolute_fee: 1500,
+            ..setup_options(6000)
+        };
+        let result = select_coin_dp(&inputs, &options).unwrap();
+        assert_eq!(result.selected_inputs, vec![0, 1]);
+        assert!(
+            result.selected_value
+                >= options.target_value + options.min_change_value + options.min_absolute_fee
+        );
+    }
+}
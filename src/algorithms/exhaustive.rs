@@ -0,0 +1,348 @@
+use crate::{
+    types::{CoinSelectionOpt, SelectionError, SelectionOutput, WasteMetric, WeightedUtxo},
+    utils::{
+        calculate_change, calculate_selection_fee, calculate_waste, calculate_waste_detailed,
+        exceeds_max_inputs, exceeds_max_tx_weight, validate_feerate, validate_target,
+    },
+};
+use alloc::{vec, vec::Vec};
+
+/// Default bound on the size of the pool [`select_coin_exhaustive`] enumerates the subsets of.
+pub const EXHAUSTIVE_MAX_INPUTS: usize = 20;
+
+/// Performs coin selection by enumerating every subset of the inputs and returning the one with the lowest waste.
+///
+/// This is only feasible for small pools, see [`select_coin_exhaustive_with_limit`].
+pub fn select_coin_exhaustive<T: WeightedUtxo>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+) -> Result<SelectionOutput, SelectionError> {
+    select_coin_exhaustive_with_limit(inputs, options, EXHAUSTIVE_MAX_INPUTS)
+}
+
+/// Performs coin selection by enumerating every subset of the inputs and returning the one with the lowest waste.
+///
+/// A subset is feasible when its value pays for the target and the fee of the whole transaction weight,
+/// at least `min_absolute_fee`. Since the result is provably optimal it serves as a ground truth for the
+/// other algorithms. Subsets spending more than the `max_inputs` of the options, or heavier than their
+/// `max_tx_weight` allows, are not feasible.
+///
+/// Returns `NoSolutionFound` if the pool holds more than `max_pool_size` inputs, or if no subset is feasible, and
+/// `ArithmeticOverflow` if the values of the inputs or the target do not sum within a `u64`, see [`validate_target`].
+pub fn select_coin_exhaustive_with_limit<T: WeightedUtxo>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+    max_pool_size: usize,
+) -> Result<SelectionOutput, SelectionError> {
+    validate_feerate(*options.target_feerate)?;
+    validate_target(inputs, options)?;
+    if inputs.len() > max_pool_size {
+        return Err(SelectionError::NoSolutionFound);
+    }
+
+    // Every subset sums to no more than all of the inputs, so the accumulated value can not overflow below.
+    let mut remaining_value = vec![0u64; inputs.len() + 1];
+    for index in (0..inputs.len()).rev() {
+        remaining_value[index] = remaining_value[index + 1]
+            .checked_add(inputs[index].value())
+            .ok_or(SelectionError::ArithmeticOverflow)?;
+    }
+
+    let mut best: Option<(Vec<usize>, i64)> = None;
+    enumerate_subsets(
+        inputs,
+        options,
+        &remaining_value,
+        &mut vec![],
+        (0, 0, 0),
+        0,
+        &mut best,
+    );
+
+    let (mut selected_inputs, _) = best.ok_or(SelectionError::NoSolutionFound)?;
+    let accumulated_value = selected_inputs
+        .iter()
+        .try_fold(0u64, |total, &i| total.checked_add(inputs[i].value()))
+        .ok_or(SelectionError::ArithmeticOverflow)?;
+    let accumulated_weight = selected_inputs
+        .iter()
+        .try_fold(0u64, |total, &i| total.checked_add(inputs[i].weight()))
+        .ok_or(SelectionError::ArithmeticOverflow)?;
+    let estimated_fee = calculate_selection_fee(options, accumulated_weight);
+    let waste_breakdown = calculate_waste_detailed(
+        options,
+        accumulated_value,
+        accumulated_weight,
+        estimated_fee.max(options.min_absolute_fee),
+    );
+    selected_inputs.sort_unstable();
+    Ok(SelectionOutput {
+        selected_inputs,
+        waste: WasteMetric(waste_breakdown.total()),
+        waste_breakdown,
+        change_value: calculate_change(options, accumulated_value, estimated_fee),
+        estimated_fee,
+        selected_value: accumulated_value,
+        selected_weight: accumulated_weight,
+    })
+}
+
+/// Walks every include/omit decision from `index` onwards, recording the lowest-waste feasible subset in `best`.
+///
+/// `accumulated` holds the value, weight and input count of the `selected_inputs`.
+fn enumerate_subsets<T: WeightedUtxo>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+    remaining_value: &[u64],
+    selected_inputs: &mut Vec<usize>,
+    accumulated: (u64, u64, usize),
+    index: usize,
+    best: &mut Option<(Vec<usize>, i64)>,
+) {
+    let (accumulated_value, accumulated_weight, input_count) = accumulated;
+    if exceeds_max_inputs(options, input_count)
+        || exceeds_max_tx_weight(options, accumulated_weight)
+    {
+        return;
+    }
+    let estimated_fee =
+        calculate_selection_fee(options, accumulated_weight).max(options.min_absolute_fee);
+    let Some(required_value) = options.target_value.checked_add(estimated_fee) else {
+        // No subset can pay for a target beyond a `u64`
+        return;
+    };
+
+    if !selected_inputs.is_empty() && accumulated_value >= required_value {
+        let waste = calculate_waste(
+            options,
+            accumulated_value,
+            accumulated_weight,
+            estimated_fee,
+        );
+        if best
+            .as_ref()
+            .is_none_or(|(_, best_waste)| waste < *best_waste)
+        {
+            *best = Some((selected_inputs.clone(), waste));
+        }
+        // Extending a feasible subset is still explored, an added input can turn a large excess into a cheaper
+        // change output
+    }
+
+    if index == inputs.len()
+        || remaining_value[index] < required_value.saturating_sub(accumulated_value)
+    {
+        return;
+    }
+
+    selected_inputs.push(index);
+    enumerate_subsets(
+        inputs,
+        options,
+        remaining_value,
+        selected_inputs,
+        (
+            accumulated_value + inputs[index].value(),
+            accumulated_weight + inputs[index].weight(),
+            input_count + inputs[index].input_count(),
+        ),
+        index + 1,
+        best,
+    );
+    selected_inputs.pop();
+    enumerate_subsets(
+        inputs,
+        options,
+        remaining_value,
+        selected_inputs,
+        accumulated,
+        index + 1,
+        best,
+    );
+}
+
+#[cfg(test)]
+mod test {
+
+    use crate::{
+        algorithms::exhaustive::{select_coin_exhaustive, select_coin_exhaustive_with_limit},
+        types::{CoinSelectionOpt, ExcessStrategy, FeeRate, OutputGroup, SelectionError},
+    };
+    #[cfg(feature = "std")]
+    use crate::{
+        algorithms::{
+            bnb::{select_coin_bnb, select_coin_bnb_with_change},
+            coingrinder::select_coin_coingrinder,
+            fifo::select_coin_fifo,
+            knapsack::select_coin_knapsack,
+            largestfirst::select_coin_largestfirst,
+            lowestlarger::select_coin_lowestlarger,
+            mininputs::select_coin_min_inputs,
+            smallestfirst::select_coin_smallestfirst,
+            srd::select_coin_srd,
+        },
+        selectcoin::select_coin_with,
+        types::{Algorithm, SelectionOutput},
+    };
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    #[cfg(feature = "std")]
+    type CoinSelectionFn =
+        fn(&[OutputGroup], &CoinSelectionOpt) -> Result<SelectionOutput, SelectionError>;
+
+    fn setup_options(target_value: u64) -> CoinSelectionOpt {
+        CoinSelectionOpt {
+            target_value,
+            target_feerate: FeeRate::from_sat_per_wu(1.0),
+            long_term_feerate: Some(FeeRate::from_sat_per_wu(0.5)),
+            min_absolute_fee: 0,
+            base_weight: 10,
+            change_weight: 50,
+            change_cost: 10,
+            avg_input_weight: 20,
+            avg_output_weight: 10,
+            min_change_value: 500,
+            excess_strategy: ExcessStrategy::ToChange,
+            max_inputs: None,
+            bnb_tries: None,
+            include_uneconomical: false,
+            max_excess: None,
+            max_tx_weight: None,
+            knapsack_iterations: None,
+            knapsack_stagnation: None,
+        }
+    }
+
+    fn setup_random_output_groups(rng: &mut StdRng, count: usize) -> Vec<OutputGroup> {
+        (0..count)
+            .map(|_| OutputGroup {
+                value: rng.gen_range(1_000..50_000),
+                weight: rng.gen_range(50..300),
+                input_count: 1,
+                creation_sequence: Some(rng.gen_range(0..100)),
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_exhaustive_finds_lightest_selection() {
+        let inputs = vec![
+            OutputGroup {
+                value: 6000,
+                weight: 400,
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            },
+            OutputGroup {
+                value: 3000,
+                weight: 100,
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            },
+            OutputGroup {
+                value: 3000,
+                weight: 100,
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            },
+        ];
+        let options = setup_options(5000);
+        let result = select_coin_exhaustive(&inputs, &options).unwrap();
+        assert_eq!(result.selected_inputs, vec![1, 2]);
+        assert_eq!(result.waste.0, 100 + 10);
+    }
+
+    #[test]
+    fn test_exhaustive_refuses_large_pools() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let inputs = setup_random_output_groups(&mut rng, 8);
+        let options = setup_options(5000);
+        assert!(select_coin_exhaustive_with_limit(&inputs, &options, 8).is_ok());
+        assert!(matches!(
+            select_coin_exhaustive_with_limit(&inputs, &options, 7),
+            Err(SelectionError::NoSolutionFound)
+        ));
+    }
+
+    #[test]
+    fn test_exhaustive_no_solution() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let inputs = setup_random_output_groups(&mut rng, 5);
+        let options = setup_options(1_000_000);
+        assert!(matches!(
+            select_coin_exhaustive(&inputs, &options),
+            Err(SelectionError::NoSolutionFound)
+        ));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_exhaustive_is_never_beaten() {
+        let mut rng = StdRng::seed_from_u64(42);
+        for _ in 0..100 {
+            let pool_size = rng.gen_range(2..10);
+            let inputs = setup_random_output_groups(&mut rng, pool_size);
+            let options = setup_options(rng.gen_range(5_000..150_000));
+            let exhaustive = select_coin_exhaustive(&inputs, &options);
+            for algorithm in Algorithm::ALL {
+                if let Ok(result) = select_coin_with(&inputs, &options, algorithm) {
+                    let exhaustive = exhaustive.as_ref().unwrap();
+                    assert!(exhaustive.waste.0 <= result.waste.0, "{:?}", algorithm);
+                }
+            }
+            // The algorithms outside of `select_coin` too
+            for algorithm in [select_coin_bnb_with_change, select_coin_min_inputs] {
+                if let Ok(result) = algorithm(&inputs, &options) {
+                    let exhaustive = exhaustive.as_ref().unwrap();
+                    assert!(exhaustive.waste.0 <= result.waste.0);
+                }
+            }
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_max_inputs_is_respected() {
+        let algorithms: Vec<CoinSelectionFn> = vec![
+            select_coin_bnb,
+            select_coin_bnb_with_change,
+            select_coin_coingrinder,
+            select_coin_exhaustive,
+            select_coin_fifo,
+            select_coin_knapsack,
+            select_coin_largestfirst,
+            select_coin_lowestlarger,
+            select_coin_min_inputs,
+            select_coin_smallestfirst,
+            select_coin_srd,
+        ];
+        let mut rng = StdRng::seed_from_u64(7);
+        for _ in 0..20 {
+            let inputs = setup_random_output_groups(&mut rng, 10);
+            let options = CoinSelectionOpt {
+                max_inputs: Some(3),
+                ..setup_options(rng.gen_range(5_000..100_000))
+            };
+            let exhaustive = select_coin_exhaustive(&inputs, &options);
+            for algorithm in &algorithms {
+                if let Ok(result) = algorithm(&inputs, &options) {
+                    assert!(result.selected_inputs.len() <= 3);
+                    let exhaustive = exhaustive.as_ref().unwrap();
+                    assert!(exhaustive.waste.0 <= result.waste.0);
+                }
+            }
+        }
+    }
+}
@@ -1,66 +1,100 @@
 use crate::{
-    types::{CoinSelectionOpt, OutputGroup, SelectionError, SelectionOutput, WasteMetric},
-    utils::{calculate_fee, calculate_waste},
+    types::{CoinSelectionOpt, SelectionError, SelectionOutput, WasteMetric, WeightedUtxo},
+    utils::{
+        calculate_bump_fee, calculate_change, calculate_selection_fee, calculate_waste_detailed,
+        economical_inputs, exceeds_max_inputs, exceeds_max_tx_weight, funds_change,
+        validate_feerate, validate_target, within_changeless_window,
+    },
 };
+use alloc::vec::Vec;
 
 /// Performs coin selection using the First-In-First-Out (FIFO) algorithm.
 ///
-/// Returns `NoSolutionFound` if no solution is found.
-pub fn select_coin_fifo(
-    inputs: &[OutputGroup],
+/// Inputs are spent by ascending `creation_sequence` first, then the inputs without a `creation_sequence` by ascending
+/// `block_height`, and the inputs with neither last. The `creation_sequence` takes precedence when both are set, as
+/// sequences and heights are not comparable. Ties, including among the inputs with neither, are broken by the index
+/// in `inputs`, so the selection never depends on the sorting algorithm.
+///
+/// Inputs costing more to spend than they are worth are skipped, unless `include_uneconomical` is set. The estimated
+/// fee covers the `base_weight` and the weight of every selected input, the last one included, plus the fee lifting
+/// their unconfirmed ancestors to the `target_feerate`, see [`calculate_bump_fee`]. The selection stops once it covers
+/// the target, the fee and a change output, see [`funds_change`], or as soon as it pays them without a change output,
+/// see [`within_changeless_window`].
+///
+/// Returns `NoSolutionFound` if no solution is found, or if covering the target takes more than `max_inputs` inputs
+/// or goes over `max_tx_weight`, and `ArithmeticOverflow` if the values of the selected inputs or the target do not
+/// sum within a `u64`, see [`validate_target`].
+pub fn select_coin_fifo<T: WeightedUtxo>(
+    inputs: &[T],
     options: &CoinSelectionOpt,
 ) -> Result<SelectionOutput, SelectionError> {
+    validate_feerate(*options.target_feerate)?;
+    validate_target(inputs, options)?;
     let mut accumulated_value: u64 = 0;
     let mut accumulated_weight: u64 = 0;
     let mut selected_inputs: Vec<usize> = Vec::new();
     let mut estimated_fees: u64 = 0;
+    let mut bump_fees: u64 = 0;
+    let mut input_count: usize = 0;
 
-    // Sorting the inputs vector based on creation_sequence
-    let mut sorted_inputs: Vec<_> = inputs
-        .iter()
-        .enumerate()
-        .filter(|(_, og)| og.creation_sequence.is_some())
-        .collect();
-
-    sorted_inputs.sort_by(|a, b| a.1.creation_sequence.cmp(&b.1.creation_sequence));
-
-    let inputs_without_sequence: Vec<_> = inputs
-        .iter()
-        .enumerate()
-        .filter(|(_, og)| og.creation_sequence.is_none())
+    let mut sorted_inputs: Vec<(usize, &T)> = economical_inputs(inputs, options)
+        .into_iter()
+        .map(|index| (index, &inputs[index]))
         .collect();
 
-    sorted_inputs.extend(inputs_without_sequence);
+    // Sorting by creation_sequence, then by block_height, the inputs with neither last, ties broken by the index
+    sorted_inputs.sort_unstable_by_key(|&(index, og)| {
+        let (rank, age) = match (og.creation_sequence(), og.block_height()) {
+            (Some(creation_sequence), _) => (0, creation_sequence),
+            (None, Some(block_height)) => (1, block_height),
+            (None, None) => (2, 0),
+        };
+        (rank, age, index)
+    });
 
-    for (index, inputs) in sorted_inputs {
-        estimated_fees = calculate_fee(accumulated_weight, options.target_feerate);
-        if accumulated_value
-            >= (options.target_value
-                + estimated_fees.max(options.min_absolute_fee)
-                + options.min_change_value)
+    for (index, input) in sorted_inputs {
+        input_count += input.input_count();
+        if exceeds_max_inputs(options, input_count)
+            || exceeds_max_tx_weight(options, accumulated_weight + input.weight())
         {
-            break;
+            return Err(SelectionError::NoSolutionFound);
         }
-        accumulated_value += inputs.value;
-        accumulated_weight += inputs.weight;
+        accumulated_value = accumulated_value
+            .checked_add(input.value())
+            .ok_or(SelectionError::ArithmeticOverflow)?;
+        accumulated_weight += input.weight();
+        bump_fees += calculate_bump_fee(input, *options.target_feerate);
         selected_inputs.push(index);
+        // The fee is recomputed once the input is added, so the check accounts for its weight
+        estimated_fees = calculate_selection_fee(options, accumulated_weight) + bump_fees;
+        if funds_change(options, accumulated_value, estimated_fees)
+            || within_changeless_window(options, accumulated_value, estimated_fees)
+        {
+            break;
+        }
     }
-    if accumulated_value
-        < (options.target_value
-            + estimated_fees.max(options.min_absolute_fee)
-            + options.min_change_value)
+    if !funds_change(options, accumulated_value, estimated_fees)
+        && !within_changeless_window(options, accumulated_value, estimated_fees)
     {
         Err(SelectionError::InsufficientFunds)
     } else {
-        let waste: u64 = calculate_waste(
+        let mut waste_breakdown = calculate_waste_detailed(
             options,
             accumulated_value,
             accumulated_weight,
             estimated_fees,
         );
+        // Lifting the unconfirmed ancestors to the target feerate is paid only because the inputs are spent now
+        waste_breakdown.feerate_diff += bump_fees as i64;
+        selected_inputs.sort_unstable();
         Ok(SelectionOutput {
             selected_inputs,
-            waste: WasteMetric(waste),
+            waste: WasteMetric(waste_breakdown.total()),
+            waste_breakdown,
+            change_value: calculate_change(options, accumulated_value, estimated_fees),
+            estimated_fee: estimated_fees,
+            selected_value: accumulated_value,
+            selected_weight: accumulated_weight,
         })
     }
 }
@@ -68,9 +102,11 @@ pub fn select_coin_fifo(
 #[cfg(test)]
 mod test {
 
+    #[cfg(feature = "std")]
+    use crate::algorithms::srd::select_coin_srd;
     use crate::{
-        algorithms::{fifo::select_coin_fifo, srd::select_coin_srd},
-        types::{CoinSelectionOpt, ExcessStrategy, OutputGroup, SelectionError},
+        algorithms::fifo::select_coin_fifo,
+        types::{CoinSelectionOpt, ExcessStrategy, FeeRate, OutputGroup, SelectionError},
     };
 
     fn setup_basic_output_groups() -> Vec<OutputGroup> {
@@ -80,18 +116,27 @@ mod test {
                 weight: 100,
                 input_count: 1,
                 creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
             },
             OutputGroup {
                 value: 2000,
                 weight: 200,
                 input_count: 1,
                 creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
             },
             OutputGroup {
                 value: 3000,
                 weight: 300,
                 input_count: 1,
                 creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
             },
         ]
     }
@@ -102,24 +147,36 @@ mod test {
                 weight: 100,
                 input_count: 1,
                 creation_sequence: Some(1),
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
             },
             OutputGroup {
                 value: 2000,
                 weight: 200,
                 input_count: 1,
                 creation_sequence: Some(5000),
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
             },
             OutputGroup {
                 value: 3000,
                 weight: 300,
                 input_count: 1,
                 creation_sequence: Some(1001),
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
             },
             OutputGroup {
                 value: 1500,
                 weight: 150,
                 input_count: 1,
                 creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
             },
         ]
     }
@@ -127,8 +184,8 @@ mod test {
     fn setup_options(target_value: u64) -> CoinSelectionOpt {
         CoinSelectionOpt {
             target_value,
-            target_feerate: 0.4, // Simplified feerate
-            long_term_feerate: Some(0.4),
+            target_feerate: FeeRate::from_sat_per_wu(0.4), // Simplified feerate
+            long_term_feerate: Some(FeeRate::from_sat_per_wu(0.4)),
             min_absolute_fee: 0,
             base_weight: 10,
             change_weight: 50,
@@ -137,9 +194,17 @@ mod test {
             avg_output_weight: 10,
             min_change_value: 500,
             excess_strategy: ExcessStrategy::ToChange,
+            max_inputs: None,
+            bnb_tries: None,
+            include_uneconomical: false,
+            max_excess: None,
+            max_tx_weight: None,
+            knapsack_iterations: None,
+            knapsack_stagnation: None,
         }
     }
 
+    #[cfg(feature = "std")]
     fn test_successful_selection() {
         let mut inputs = setup_basic_output_groups();
         let mut options = setup_options(2500);
@@ -147,6 +212,7 @@ mod test {
         assert!(result.is_ok());
         let mut selection_output = result.unwrap();
         assert!(!selection_output.selected_inputs.is_empty());
+        assert!(selection_output.selected_value >= options.target_value);
 
         inputs = setup_output_groups_withsequence();
         options = setup_options(500);
@@ -154,8 +220,10 @@ mod test {
         assert!(result.is_ok());
         selection_output = result.unwrap();
         assert!(!selection_output.selected_inputs.is_empty());
+        assert!(selection_output.selected_value >= options.target_value);
     }
 
+    #[cfg(feature = "std")]
     fn test_insufficient_funds() {
         let inputs = setup_basic_output_groups();
         let options = setup_options(7000); // Set a target value higher than the sum of all inputs
@@ -163,9 +231,147 @@ mod test {
         assert!(matches!(result, Err(SelectionError::InsufficientFunds)));
     }
 
+    #[cfg(feature = "std")]
     #[test]
     fn test_fifo() {
         test_successful_selection();
         test_insufficient_funds();
     }
+
+    #[test]
+    fn test_fifo_skips_uneconomical_inputs() {
+        let mut inputs = setup_output_groups_withsequence();
+        // The oldest input costs 40 sats to spend
+        inputs.push(OutputGroup {
+            value: 30,
+            weight: 100,
+            input_count: 1,
+            creation_sequence: Some(0),
+            ancestor_fee: 0,
+            ancestor_weight: 0,
+            block_height: None,
+        });
+        let mut options = setup_options(500);
+        let result = select_coin_fifo(&inputs, &options).unwrap();
+        assert_eq!(result.selected_inputs, vec![0, 2]);
+
+        options.include_uneconomical = true;
+        let result = select_coin_fifo(&inputs, &options).unwrap();
+        assert_eq!(result.selected_inputs, vec![0, 2, 4]);
+    }
+
+    #[test]
+    fn test_fifo_max_inputs() {
+        let inputs = setup_output_groups_withsequence();
+        // The two oldest inputs cover the target
+        let mut options = setup_options(3000);
+        options.max_inputs = Some(2);
+        let result = select_coin_fifo(&inputs, &options).unwrap();
+        assert_eq!(result.selected_inputs, vec![0, 2]);
+
+        options.max_inputs = Some(1);
+        let result = select_coin_fifo(&inputs, &options);
+        assert!(matches!(result, Err(SelectionError::NoSolutionFound)));
+    }
+
+    #[test]
+    fn test_fifo_fee_includes_last_input() {
+        let inputs = setup_basic_output_groups();
+        let options = setup_options(2355);
+        // The fee for the base weight and both inputs is 125 sats and the change output 21 sats, 1 sat more than what
+        // is left for them
+        let result = select_coin_fifo(&inputs[..2], &options);
+        assert!(matches!(result, Err(SelectionError::InsufficientFunds)));
+
+        let options = setup_options(2354);
+        let result = select_coin_fifo(&inputs[..2], &options).unwrap();
+        assert_eq!(result.selected_inputs, vec![0, 1]);
+        assert_eq!(result.estimated_fee, 125);
+        assert!(
+            result.selected_value
+                >= options.target_value + result.estimated_fee + 21 + options.min_change_value
+        );
+    }
+
+    #[test]
+    fn test_fifo_ties_keep_slice_order() {
+        let inputs: Vec<OutputGroup> = [None, Some(3), Some(1), None, Some(3), Some(1), None]
+            .iter()
+            .map(|&creation_sequence| OutputGroup {
+                value: 1000,
+                weight: 100,
+                input_count: 1,
+                creation_sequence,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            })
+            .collect();
+        let result = select_coin_fifo(&inputs, &setup_options(2500)).unwrap();
+        assert_eq!(result.selected_inputs, vec![1, 2, 4, 5]);
+
+        // The ones without a sequence come last in their original order
+        let result = select_coin_fifo(&inputs, &setup_options(3500)).unwrap();
+        assert_eq!(result.selected_inputs, vec![0, 1, 2, 4, 5]);
+    }
+
+    #[test]
+    fn test_fifo_changeless_exact_match() {
+        // The target plus the fee for the base weight and the input, with nothing left for a change output
+        let inputs = vec![OutputGroup {
+            value: 1045,
+            weight: 100,
+            input_count: 1,
+            creation_sequence: None,
+            ancestor_fee: 0,
+            ancestor_weight: 0,
+            block_height: None,
+        }];
+        let result = select_coin_fifo(&inputs, &setup_options(1000)).unwrap();
+        assert_eq!(result.selected_inputs, vec![0]);
+        assert_eq!(result.change_value, None);
+        assert_eq!(result.waste.0, 0);
+    }
+
+    #[test]
+    fn test_fifo_orders_by_block_height() {
+        let inputs: Vec<OutputGroup> = [
+            (None, None),
+            (None, Some(800_010)),
+            // The sequence takes precedence over the height
+            (Some(5), Some(1)),
+            (None, Some(800_000)),
+            (Some(2), None),
+            (None, None),
+            (None, Some(800_010)),
+        ]
+        .iter()
+        .map(|&(creation_sequence, block_height)| OutputGroup {
+            value: 1000,
+            weight: 100,
+            input_count: 1,
+            creation_sequence,
+            ancestor_fee: 0,
+            ancestor_weight: 0,
+            block_height,
+        })
+        .collect();
+        let result = select_coin_fifo(&inputs, &setup_options(2500)).unwrap();
+        assert_eq!(result.selected_inputs, vec![1, 2, 3, 4]);
+
+        // Sequenced inputs first, then the confirmed ones from the oldest
+        let result = select_coin_fifo(&inputs, &setup_options(3500)).unwrap();
+        assert_eq!(result.selected_inputs, vec![1, 2, 3, 4, 6]);
+
+        // Heights alone are enough
+        let inputs: Vec<OutputGroup> = inputs
+            .into_iter()
+            .map(|input| OutputGroup {
+                creation_sequence: None,
+                ..input
+            })
+            .collect();
+        let result = select_coin_fifo(&inputs, &setup_options(2500)).unwrap();
+        assert_eq!(result.selected_inputs, vec![1, 2, 3, 6]);
+    }
 }
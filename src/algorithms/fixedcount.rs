@@ -0,0 +1,313 @@
+use crate::{
+    types::{CoinSelectionOpt, SelectionError, SelectionOutput, WasteMetric, WeightedUtxo},
+    utils::{
+        calculate_change, calculate_fee, calculate_selection_fee, calculate_waste,
+        calculate_waste_detailed, effective_value, exceeds_max_inputs, exceeds_max_tx_weight,
+        validate_feerate, validate_target,
+    },
+};
+use alloc::{vec, vec::Vec};
+use core::cmp::Reverse;
+
+/// Upper bound on the number of nodes the search visits.
+const FIXED_COUNT_TOTAL_TRIES: u32 = 100_000;
+
+/// Performs coin selection spending exactly `input_count` UTXOs.
+///
+/// The number of inputs is the sum of the `input_count` of the selected groups, not the number of groups,
+/// so a group is either spent as a whole or not at all. The groups are explored in descending order of effective
+/// value, and among the selections with exactly `input_count` inputs covering the target plus the minimum change,
+/// the one with the lowest waste wins. As in Branch and Bound, the target includes the fee for the `base_weight`,
+/// raised to `min_absolute_fee`. Selections heavier than `max_tx_weight` allows are pruned.
+///
+/// Returns `NoSolutionFound` if no such selection exists, none is found within the search budget, or `input_count`
+/// exceeds `max_inputs`, and `ArithmeticOverflow` if the values of the inputs or the target do not sum within a
+/// `u64`, see [`validate_target`].
+pub fn select_coin_fixed_input_count<T: WeightedUtxo>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+    input_count: usize,
+) -> Result<SelectionOutput, SelectionError> {
+    validate_feerate(*options.target_feerate)?;
+    validate_target(inputs, options)?;
+    let target = options
+        .target_value
+        .checked_add(options.min_change_value)
+        .and_then(|target| {
+            target.checked_add(
+                calculate_fee(options.base_weight, *options.target_feerate)
+                    .max(options.min_absolute_fee),
+            )
+        })
+        .ok_or(SelectionError::ArithmeticOverflow)?;
+
+    // (index, effective value, input count)
+    let mut sorted_inputs: Vec<(usize, u64, usize)> = inputs
+        .iter()
+        .enumerate()
+        .map(|(index, input)| {
+            (
+                index,
+                effective_value(input, *options.target_feerate),
+                input.input_count(),
+            )
+        })
+        .filter(|&(_, value, count)| value > 0 && count <= input_count)
+        .collect();
+    sorted_inputs.sort_by_key(|&(_, value, _)| Reverse(value));
+
+    // Lookahead tables: sum of the effective values and input counts from a position onwards.
+    let len = sorted_inputs.len();
+    let mut remaining_value = vec![0u64; len + 1];
+    let mut remaining_count = vec![0usize; len + 1];
+    for pos in (0..len).rev() {
+        remaining_value[pos] = remaining_value[pos + 1]
+            .checked_add(sorted_inputs[pos].1)
+            .ok_or(SelectionError::ArithmeticOverflow)?;
+        remaining_count[pos] = remaining_count[pos + 1] + sorted_inputs[pos].2;
+    }
+
+    if input_count == 0
+        || exceeds_max_inputs(options, input_count)
+        || remaining_value[0] < target
+        || remaining_count[0] < input_count
+    {
+        return Err(SelectionError::NoSolutionFound);
+    }
+
+    // The selection holds positions in `sorted_inputs`.
+    let mut selection: Vec<usize> = Vec::new();
+    let (mut acc_eff_value, mut acc_count) = (0u64, 0usize);
+    let (mut acc_value, mut acc_weight) = (0u64, 0u64);
+    let mut best: Option<(Vec<usize>, i64)> = None;
+    let mut next: usize = 0;
+    let mut tries: u32 = 0;
+
+    loop {
+        let mut backtrack = true;
+        if next < len && tries < FIXED_COUNT_TOTAL_TRIES {
+            tries += 1;
+            let (index, value, count) = sorted_inputs[next];
+            selection.push(next);
+            acc_eff_value = acc_eff_value
+                .checked_add(value)
+                .ok_or(SelectionError::ArithmeticOverflow)?;
+            acc_count += count;
+            acc_value = acc_value
+                .checked_add(inputs[index].value())
+                .ok_or(SelectionError::ArithmeticOverflow)?;
+            acc_weight = acc_weight
+                .checked_add(inputs[index].weight())
+                .ok_or(SelectionError::ArithmeticOverflow)?;
+            next += 1;
+
+            if acc_count > input_count || exceeds_max_tx_weight(options, acc_weight) {
+                // Too many inputs or too heavy already
+            } else if acc_count == input_count {
+                if acc_eff_value >= target {
+                    let estimated_fees = calculate_selection_fee(options, acc_weight);
+                    let waste = calculate_waste(options, acc_value, acc_weight, estimated_fees);
+                    if best
+                        .as_ref()
+                        .is_none_or(|(_, best_waste)| waste < *best_waste)
+                    {
+                        best = Some((selection.clone(), waste));
+                    }
+                }
+            } else if acc_eff_value + remaining_value[next] >= target
+                && acc_count + remaining_count[next] >= input_count
+            {
+                backtrack = false;
+            }
+        } else if tries >= FIXED_COUNT_TOTAL_TRIES {
+            break;
+        }
+
+        if backtrack {
+            match selection.pop() {
+                Some(last) => {
+                    let (index, value, count) = sorted_inputs[last];
+                    acc_eff_value -= value;
+                    acc_count -= count;
+                    acc_value -= inputs[index].value();
+                    acc_weight -= inputs[index].weight();
+                    next = last + 1;
+                }
+                None => break,
+            }
+        }
+    }
+
+    let (best_selection, _) = best.ok_or(SelectionError::NoSolutionFound)?;
+    let mut selected_inputs: Vec<usize> = best_selection
+        .iter()
+        .map(|&pos| sorted_inputs[pos].0)
+        .collect();
+    let accumulated_value = selected_inputs
+        .iter()
+        .try_fold(0u64, |total, &i| total.checked_add(inputs[i].value()))
+        .ok_or(SelectionError::ArithmeticOverflow)?;
+    let accumulated_weight = selected_inputs
+        .iter()
+        .try_fold(0u64, |total, &i| total.checked_add(inputs[i].weight()))
+        .ok_or(SelectionError::ArithmeticOverflow)?;
+    let estimated_fees = calculate_selection_fee(options, accumulated_weight);
+    let waste_breakdown = calculate_waste_detailed(
+        options,
+        accumulated_value,
+        accumulated_weight,
+        estimated_fees,
+    );
+    selected_inputs.sort_unstable();
+    Ok(SelectionOutput {
+        selected_inputs,
+        waste: WasteMetric(waste_breakdown.total()),
+        waste_breakdown,
+        change_value: calculate_change(options, accumulated_value, estimated_fees),
+        estimated_fee: estimated_fees,
+        selected_value: accumulated_value,
+        selected_weight: accumulated_weight,
+    })
+}
+
+#[cfg(test)]
+mod test {
+
+    use crate::{
+        algorithms::fixedcount::select_coin_fixed_input_count,
+        types::{CoinSelectionOpt, ExcessStrategy, FeeRate, OutputGroup, SelectionError},
+    };
+
+    fn setup_output_groups() -> Vec<OutputGroup> {
+        vec![
+            OutputGroup {
+                value: 6000,
+                weight: 100,
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            },
+            OutputGroup {
+                value: 3000,
+                weight: 100,
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            },
+            OutputGroup {
+                value: 2000,
+                weight: 200,
+                input_count: 2,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            },
+            OutputGroup {
+                value: 1000,
+                weight: 100,
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            },
+        ]
+    }
+
+    fn setup_options(target_value: u64) -> CoinSelectionOpt {
+        CoinSelectionOpt {
+            target_value,
+            target_feerate: FeeRate::from_sat_per_wu(1.0),
+            long_term_feerate: Some(FeeRate::from_sat_per_wu(0.5)),
+            min_absolute_fee: 0,
+            base_weight: 10,
+            change_weight: 50,
+            change_cost: 10,
+            avg_input_weight: 20,
+            avg_output_weight: 10,
+            min_change_value: 500,
+            excess_strategy: ExcessStrategy::ToChange,
+            max_inputs: None,
+            bnb_tries: None,
+            include_uneconomical: false,
+            max_excess: None,
+            max_tx_weight: None,
+            knapsack_iterations: None,
+            knapsack_stagnation: None,
+        }
+    }
+
+    fn sorted(selected_inputs: &[usize]) -> Vec<usize> {
+        let mut selected_inputs = selected_inputs.to_vec();
+        selected_inputs.sort();
+        selected_inputs
+    }
+
+    #[test]
+    fn test_fixed_input_count_single() {
+        let inputs = setup_output_groups();
+        let result = select_coin_fixed_input_count(&inputs, &setup_options(4000), 1).unwrap();
+        assert_eq!(result.selected_inputs, vec![0]);
+    }
+
+    #[test]
+    fn test_fixed_input_count_counts_utxos() {
+        let inputs = setup_output_groups();
+        // Three UTXOs, either the three single ones or a single one along with the group of two
+        let result = select_coin_fixed_input_count(&inputs, &setup_options(7000), 3).unwrap();
+        let selected_inputs = sorted(&result.selected_inputs);
+        assert!(selected_inputs == vec![0, 1, 3] || selected_inputs == vec![0, 2]);
+
+        // The whole pool holds five UTXOs in four groups
+        let result = select_coin_fixed_input_count(&inputs, &setup_options(10_000), 5).unwrap();
+        assert_eq!(sorted(&result.selected_inputs), vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_fixed_input_count_pays_base_weight() {
+        let inputs = setup_output_groups();
+        // The largest UTXO covers the target and the minimum change over its own fee, not over the fee for the
+        // base weight too
+        let options = CoinSelectionOpt {
+            base_weight: 1000,
+            ..setup_options(5000)
+        };
+        let result = select_coin_fixed_input_count(&inputs, &options, 1);
+        assert!(matches!(result, Err(SelectionError::NoSolutionFound)));
+
+        let result = select_coin_fixed_input_count(&inputs, &options, 2).unwrap();
+        assert_eq!(result.selected_inputs, vec![0, 1]);
+        assert!(
+            result.selected_value
+                >= options.target_value + options.min_change_value + result.estimated_fee
+        );
+
+        // The minimum absolute fee is paid too
+        let options = CoinSelectionOpt {
+            min_absolute_fee: 600,
+            ..setup_options(5000)
+        };
+        let result = select_coin_fixed_input_count(&inputs, &options, 1);
+        assert!(matches!(result, Err(SelectionError::NoSolutionFound)));
+    }
+
+    #[test]
+    fn test_fixed_input_count_infeasible() {
+        let inputs = setup_output_groups();
+        // A single UTXO can not cover the target
+        let result = select_coin_fixed_input_count(&inputs, &setup_options(7000), 1);
+        assert!(matches!(result, Err(SelectionError::NoSolutionFound)));
+
+        // More UTXOs than the pool holds
+        let result = select_coin_fixed_input_count(&inputs, &setup_options(1000), 6);
+        assert!(matches!(result, Err(SelectionError::NoSolutionFound)));
+
+        let result = select_coin_fixed_input_count(&inputs, &setup_options(1000), 0);
+        assert!(matches!(result, Err(SelectionError::NoSolutionFound)));
+    }
+}
@@ -1,70 +1,332 @@
+#[cfg(feature = "std")]
+use crate::algorithms::dp::min_overshoot_subset;
 use crate::{
     types::{
-        CoinSelectionOpt, EffectiveValue, OutputGroup, SelectionError, SelectionOutput,
-        WasteMetric, Weight,
+        CoinSelectionOpt, EffectiveValue, ExcessStrategy, SelectionError, SelectionOutput,
+        WasteMetric, Weight, WeightedUtxo,
+    },
+    utils::{
+        calculate_change, calculate_fee, calculate_selection_fee, calculate_waste_detailed,
+        economical_inputs, effective_value, exceeds_max_inputs, exceeds_max_tx_weight,
+        validate_feerate, validate_target,
     },
-    utils::{calculate_accumulated_weight, calculate_fee, calculate_waste, effective_value},
 };
-use rand::{thread_rng, Rng};
-use std::{cmp::Reverse, collections::HashSet};
+use alloc::{collections::BTreeSet, vec, vec::Vec};
+use core::cmp::Reverse;
+#[cfg(feature = "std")]
+use rand::thread_rng;
+use rand::{seq::SliceRandom, Rng};
+
+/// Default number of randomized passes of the knapsack, same as Bitcoin Core, see
+/// [`CoinSelectionOpt::knapsack_iterations`].
+pub const KNAPSACK_TOTAL_ITERATIONS: u32 = 1000;
+
+/// Default number of consecutive randomized passes without a better subset after which the knapsack stops, see
+/// [`CoinSelectionOpt::knapsack_stagnation`].
+///
+/// The `knapsack_stagnation` group of the knapsack bench compares it with making every pass.
+pub const KNAPSACK_STAGNATION_ITERATIONS: u32 = 100;
+
+/// Default upper bound on the adjusted target for which [`select_coin_knapsack_dp`] solves the knapsack exactly.
+pub const KNAPSACK_MAX_DP_TARGET: u64 = 500_000;
+
+/// Performs coin selection using the Knapsack algorithm of Bitcoin Core's legacy selector.
+///
+/// Like Bitcoin Core, a first pass looks for a single coin, or a greedy combination of the coins smaller than the
+/// adjusted target in descending order, whose effective value hits the adjusted target exactly. Coins of equal
+/// effective value are picked in random order. Only when there is no exact match, the randomized passes
+/// approximate the best subset. Subsets spending more than `max_inputs` inputs or heavier than `max_tx_weight`
+/// allows are never considered, and inputs costing more to spend than they are worth only when
+/// `include_uneconomical` is set.
+///
+/// As in Bitcoin Core, the coin with the lowest effective value above the adjusted target is returned instead when
+/// no subset reaches the target, or when it does not exceed the effective value of the best subset.
+///
+/// Returns `ArithmeticOverflow` if the effective values of a subset or the target do not sum within a `u64`, see
+/// [`validate_target`].
+#[cfg(feature = "std")]
+pub fn select_coin_knapsack<T: WeightedUtxo>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+) -> Result<SelectionOutput, SelectionError> {
+    select_coin_knapsack_with_rng(inputs, options, &mut thread_rng())
+}
+
+/// Performs coin selection using the Knapsack algorithm, drawing from `rng`, see [`select_coin_knapsack`].
+pub fn select_coin_knapsack_with_rng<T: WeightedUtxo, R: Rng>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+    rng: &mut R,
+) -> Result<SelectionOutput, SelectionError> {
+    validate_feerate(*options.target_feerate)?;
+    validate_target(inputs, options)?;
+    let (adjusted_target, mut smaller_coins) = knapsack_candidates(inputs, options);
+    // Shuffling before the stable sort breaks the ties between coins of equal effective value at random
+    smaller_coins.shuffle(rng);
+    smaller_coins.sort_by_key(|&(_, value, _)| Reverse(value));
+
+    let exact_coin = inputs.iter().enumerate().find_map(|(index, input)| {
+        (effective_value(input, *options.target_feerate) == adjusted_target
+            && !exceeds_max_inputs(options, input.input_count())
+            && !exceeds_max_tx_weight(options, input.weight()))
+        .then_some(index)
+    });
+    if let Some(index) = exact_coin {
+        return Ok(knapsack_output(inputs, options, vec![index]));
+    }
+
+    let mut selected_inputs: Vec<usize> = Vec::new();
+    let mut accumulated_value: u64 = 0;
+    let mut accumulated_weight: u64 = 0;
+    let mut input_count: usize = 0;
+    for &(index, value, weight) in &smaller_coins {
+        if accumulated_value
+            .checked_add(value)
+            .is_some_and(|total| total <= adjusted_target)
+            && !exceeds_max_inputs(options, input_count + inputs[index].input_count())
+            && !exceeds_max_tx_weight(options, accumulated_weight + weight)
+        {
+            selected_inputs.push(index);
+            accumulated_value += value;
+            accumulated_weight += weight;
+            input_count += inputs[index].input_count();
+        }
+        if accumulated_value == adjusted_target {
+            return Ok(knapsack_output(inputs, options, selected_inputs));
+        }
+    }
+
+    let best_subset = knap_sack(inputs, adjusted_target, &smaller_coins, options, rng);
+    or_lowest_larger(inputs, options, adjusted_target, best_subset)
+}
+
+/// Performs coin selection by solving the knapsack exactly via dynamic programming.
+///
+/// Uses [`KNAPSACK_MAX_DP_TARGET`], see [`select_coin_knapsack_dp_with_limit`].
+#[cfg(feature = "std")]
+pub fn select_coin_knapsack_dp<T: WeightedUtxo>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+) -> Result<SelectionOutput, SelectionError> {
+    select_coin_knapsack_dp_with_limit(inputs, options, KNAPSACK_MAX_DP_TARGET)
+}
+
+/// Performs coin selection by solving the knapsack exactly via dynamic programming.
+///
+/// Considers the same coins as [`select_coin_knapsack`], and deterministically returns the subset with the
+/// smallest effective value not below the adjusted target, so an exact match is found whenever one exists. The
+/// lowest larger coin is preferred as in [`select_coin_knapsack`].
+/// The table holds one entry per sat up to twice the adjusted target, so above `max_dp_target` the
+/// randomized knapsack is used instead. The table does not track the number of inputs, so the randomized
+/// knapsack is also used when the exact subset spends more than `max_inputs` inputs.
+#[cfg(feature = "std")]
+pub fn select_coin_knapsack_dp_with_limit<T: WeightedUtxo>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+    max_dp_target: u64,
+) -> Result<SelectionOutput, SelectionError> {
+    select_coin_knapsack_dp_with_rng(inputs, options, max_dp_target, &mut thread_rng())
+}
+
+/// Performs coin selection by solving the knapsack exactly via dynamic programming, falling back to the randomized
+/// knapsack drawing from `rng`, see [`select_coin_knapsack_dp_with_limit`].
+#[cfg(feature = "std")]
+pub fn select_coin_knapsack_dp_with_rng<T: WeightedUtxo, R: Rng>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+    max_dp_target: u64,
+    rng: &mut R,
+) -> Result<SelectionOutput, SelectionError> {
+    validate_feerate(*options.target_feerate)?;
+    validate_target(inputs, options)?;
+    let (adjusted_target, smaller_coins) = knapsack_candidates(inputs, options);
+    let best_subset = if adjusted_target > max_dp_target {
+        knap_sack(inputs, adjusted_target, &smaller_coins, options, rng)
+    } else {
+        let values: Vec<EffectiveValue> =
+            smaller_coins.iter().map(|&(_, value, _)| value).collect();
+        match min_overshoot_subset(&values, adjusted_target) {
+            None => Err(SelectionError::NoSolutionFound),
+            Some(best_set) => {
+                let best_set_count: usize = best_set
+                    .iter()
+                    .map(|&pos| inputs[smaller_coins[pos].0].input_count())
+                    .sum();
+                let best_set_weight: u64 = best_set.iter().map(|&pos| smaller_coins[pos].2).sum();
+                if exceeds_max_inputs(options, best_set_count)
+                    || exceeds_max_tx_weight(options, best_set_weight)
+                {
+                    knap_sack(inputs, adjusted_target, &smaller_coins, options, rng)
+                } else {
+                    Ok(knapsack_output(
+                        inputs,
+                        options,
+                        best_set.iter().map(|&pos| smaller_coins[pos].0).collect(),
+                    ))
+                }
+            }
+        }
+    };
+    or_lowest_larger(inputs, options, adjusted_target, best_subset)
+}
 
-pub fn select_coin_knapsack(
-    inputs: &[OutputGroup],
+/// Returns the coin with the lowest effective value covering the `adjusted_target` on its own, unless the
+/// `best_subset` of the smaller coins has a lower effective value.
+///
+/// Only the economical coins left out of the smaller coins, see [`knapsack_candidates`], and within `max_inputs` and
+/// `max_tx_weight` are considered. The error of `best_subset` is returned when there is no such coin.
+fn or_lowest_larger<T: WeightedUtxo>(
+    inputs: &[T],
     options: &CoinSelectionOpt,
+    adjusted_target: u64,
+    best_subset: Result<SelectionOutput, SelectionError>,
 ) -> Result<SelectionOutput, SelectionError> {
+    let lowest_larger = economical_inputs(inputs, options)
+        .into_iter()
+        .map(|index| {
+            (
+                index,
+                effective_value(&inputs[index], *options.target_feerate),
+            )
+        })
+        .filter(|&(index, value)| {
+            value >= adjusted_target
+                && !exceeds_max_inputs(options, inputs[index].input_count())
+                && !exceeds_max_tx_weight(options, inputs[index].weight())
+        })
+        .min_by_key(|&(_, value)| value);
+    let Some((index, value)) = lowest_larger else {
+        return best_subset;
+    };
+    match best_subset {
+        Ok(selection)
+            if selection
+                .selected_inputs
+                .iter()
+                .map(|&i| effective_value(&inputs[i], *options.target_feerate))
+                .sum::<u64>()
+                < value =>
+        {
+            Ok(selection)
+        }
+        _ => Ok(knapsack_output(inputs, options, vec![index])),
+    }
+}
+
+/// Builds the [`SelectionOutput`] of a knapsack selection.
+///
+/// The selection is made over effective values, while the waste and change are computed from the actual values. As
+/// in the adjusted target, the estimated fee covers the `base_weight` too.
+fn knapsack_output<T: WeightedUtxo>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+    mut selected_inputs: Vec<usize>,
+) -> SelectionOutput {
+    let accumulated_value: u64 = selected_inputs.iter().map(|&i| inputs[i].value()).sum();
+    let accumulated_weight: u64 = selected_inputs.iter().map(|&i| inputs[i].weight()).sum();
+    let estimated_fees = calculate_selection_fee(options, accumulated_weight);
+    let waste_breakdown = calculate_waste_detailed(
+        options,
+        accumulated_value,
+        accumulated_weight,
+        estimated_fees,
+    );
+    selected_inputs.sort_unstable();
+    SelectionOutput {
+        selected_inputs,
+        waste: WasteMetric(waste_breakdown.total()),
+        waste_breakdown,
+        change_value: calculate_change(options, accumulated_value, estimated_fees),
+        estimated_fee: estimated_fees,
+        selected_value: accumulated_value,
+        selected_weight: accumulated_weight,
+    }
+}
+
+/// Returns the adjusted target and the economical coins smaller than it, sorted by descending effective value.
+///
+/// As in Bitcoin Core, the coins are split by effective value, so that a coin worth more than the adjusted target
+/// but not once its fee is paid is still combined with the others.
+/// The fee for the base weight in the adjusted target is raised to `min_absolute_fee`, so every subset reaching
+/// it also covers the minimum fee. The `min_change_value` is only added when the excess goes to a change output,
+/// with [`ExcessStrategy::ToChange`] or [`ExcessStrategy::Split`].
+pub(crate) fn knapsack_candidates<T: WeightedUtxo>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+) -> (u64, Vec<(usize, EffectiveValue, Weight)>) {
+    let min_change_value = match options.excess_strategy {
+        ExcessStrategy::ToChange | ExcessStrategy::Split => options.min_change_value,
+        ExcessStrategy::ToFee | ExcessStrategy::ToRecipient => 0,
+    };
     let adjusted_target = options.target_value
-        + options.min_change_value
-        + calculate_fee(options.base_weight, options.target_feerate);
-    let mut smaller_coins = inputs
-        .iter()
-        .enumerate()
-        .filter(|&(_, output_group)| output_group.value < adjusted_target)
-        .map(|(index, output_group)| {
+        + min_change_value
+        + calculate_fee(options.base_weight, *options.target_feerate).max(options.min_absolute_fee);
+    let mut smaller_coins = economical_inputs(inputs, options)
+        .into_iter()
+        .map(|index| {
             (
                 index,
-                effective_value(output_group, options.target_feerate),
-                output_group.weight,
+                effective_value(&inputs[index], *options.target_feerate),
+                inputs[index].weight(),
             )
         })
+        .filter(|&(_, value, _)| value < adjusted_target)
         .collect::<Vec<_>>();
     smaller_coins.sort_by_key(|&(_, value, _)| Reverse(value));
 
-    knap_sack(adjusted_target, &smaller_coins, options)
+    (adjusted_target, smaller_coins)
 }
 
-fn knap_sack(
+/// Approximates the best subset of the `smaller_coins` with `knapsack_iterations` randomized passes, skipping any
+/// coin which would take the subset over `max_inputs` inputs or `max_tx_weight`.
+///
+/// Once a subset is found, the passes stop early after `knapsack_stagnation` of them in a row find no better one, so
+/// the subset found with a given `rng` depends on it.
+///
+/// Returns `ArithmeticOverflow` if the effective values of a subset do not sum within a `u64`.
+pub(crate) fn knap_sack<T: WeightedUtxo, R: Rng>(
+    inputs: &[T],
     adjusted_target: u64,
     smaller_coins: &[(usize, EffectiveValue, Weight)],
     options: &CoinSelectionOpt,
+    rng: &mut R,
 ) -> Result<SelectionOutput, SelectionError> {
-    let mut selected_inputs: HashSet<usize> = HashSet::new();
+    if !reachable_within_weight(adjusted_target, smaller_coins, options) {
+        return Err(SelectionError::NoSolutionFound);
+    }
+    let mut selected_inputs: BTreeSet<usize> = BTreeSet::new();
     let mut accumulated_value: u64 = 0;
-    let mut best_set: HashSet<usize> = HashSet::new();
+    let mut accumulated_weight: u64 = 0;
+    let mut input_count: usize = 0;
+    let mut best_set: BTreeSet<usize> = BTreeSet::new();
     let mut best_set_value: u64 = u64::MAX;
-    let mut rng = thread_rng();
-    for _ in 1..=1000 {
+    let max_stagnation = options
+        .knapsack_stagnation
+        .unwrap_or(KNAPSACK_STAGNATION_ITERATIONS);
+    let mut stagnation: u32 = 0;
+    for _ in 0..options
+        .knapsack_iterations
+        .unwrap_or(KNAPSACK_TOTAL_ITERATIONS)
+    {
+        let previous_best_set_value = best_set_value;
         for pass in 1..=2 {
-            for &(index, value, _) in smaller_coins {
+            for &(index, value, weight) in smaller_coins {
                 let toss_result: bool = rng.gen_bool(0.5);
                 if (pass == 2 && !selected_inputs.contains(&index)) || (pass == 1 && toss_result) {
+                    if exceeds_max_inputs(options, input_count + inputs[index].input_count())
+                        || exceeds_max_tx_weight(options, accumulated_weight + weight)
+                    {
+                        continue;
+                    }
                     selected_inputs.insert(index);
-                    accumulated_value += value;
+                    accumulated_value = accumulated_value
+                        .checked_add(value)
+                        .ok_or(SelectionError::ArithmeticOverflow)?;
+                    accumulated_weight += weight;
+                    input_count += inputs[index].input_count();
                     if accumulated_value == adjusted_target {
-                        let accumulated_weight =
-                            calculate_accumulated_weight(smaller_coins, &selected_inputs);
-                        let estimated_fees =
-                            calculate_fee(accumulated_weight, options.target_feerate);
                         let index_vector: Vec<usize> = selected_inputs.into_iter().collect();
-                        let waste: u64 = calculate_waste(
-                            options,
-                            accumulated_value,
-                            accumulated_weight,
-                            estimated_fees,
-                        );
-                        return Ok(SelectionOutput {
-                            selected_inputs: index_vector,
-                            waste: WasteMetric(waste),
-                        });
+                        return Ok(knapsack_output(inputs, options, index_vector));
                     } else if accumulated_value >= adjusted_target {
                         if accumulated_value < best_set_value {
                             best_set_value = accumulated_value;
@@ -72,39 +334,95 @@ fn knap_sack(
                         }
                         selected_inputs.remove(&index);
                         accumulated_value -= value;
+                        accumulated_weight -= weight;
+                        input_count -= inputs[index].input_count();
                     }
                 }
             }
         }
         accumulated_value = 0;
+        accumulated_weight = 0;
+        input_count = 0;
         selected_inputs.clear();
+
+        if best_set_value < previous_best_set_value {
+            stagnation = 0;
+        } else if best_set_value != u64::MAX {
+            stagnation += 1;
+        }
+        if best_set_value != u64::MAX && stagnation >= max_stagnation {
+            break;
+        }
     }
     if best_set_value == u64::MAX {
         Err(SelectionError::NoSolutionFound)
     } else {
-        let best_set_weight = calculate_accumulated_weight(smaller_coins, &best_set);
-        let estimated_fees = calculate_fee(best_set_weight, options.target_feerate);
         let index_vector: Vec<usize> = best_set.into_iter().collect();
-        let waste: u64 = calculate_waste(options, best_set_value, best_set_weight, estimated_fees);
-        Ok(SelectionOutput {
-            selected_inputs: index_vector,
-            waste: WasteMetric(waste),
-        })
+        Ok(knapsack_output(inputs, options, index_vector))
+    }
+}
+
+/// Returns whether a subset of the `smaller_coins` light enough for `max_tx_weight` may reach the `adjusted_target`.
+///
+/// The effective value of such a subset is bounded by the fractional knapsack, filling the weight budget with the
+/// coins of highest effective value per weight unit first, and taking a fraction of the first coin that does not
+/// fit. When even that bound falls short, the randomized passes are skipped altogether.
+fn reachable_within_weight(
+    adjusted_target: u64,
+    smaller_coins: &[(usize, EffectiveValue, Weight)],
+    options: &CoinSelectionOpt,
+) -> bool {
+    let Some(max_tx_weight) = options.max_tx_weight else {
+        return true;
+    };
+    let mut weight_budget =
+        max_tx_weight.saturating_sub(options.base_weight.saturating_add(options.change_weight));
+    let mut by_density = smaller_coins.to_vec();
+    by_density.sort_by(|&(_, a_value, a_weight), &(_, b_value, b_weight)| {
+        (b_value as u128 * a_weight as u128).cmp(&(a_value as u128 * b_weight as u128))
+    });
+
+    let mut reachable_value: u64 = 0;
+    for (_, value, weight) in by_density {
+        if weight <= weight_budget {
+            weight_budget -= weight;
+            reachable_value = reachable_value.saturating_add(value);
+        } else {
+            reachable_value = reachable_value
+                .saturating_add((value as u128 * weight_budget as u128 / weight as u128) as u64);
+            break;
+        }
+        if reachable_value >= adjusted_target {
+            return true;
+        }
     }
+    reachable_value >= adjusted_target
 }
 
 #[cfg(test)]
 mod test {
 
+    #[cfg(feature = "std")]
     use crate::{
-        algorithms::knapsack::select_coin_knapsack,
-        types::{CoinSelectionOpt, ExcessStrategy, OutputGroup, SelectionError},
-        utils::calculate_fee,
+        algorithms::knapsack::{
+            select_coin_knapsack, select_coin_knapsack_dp, select_coin_knapsack_dp_with_limit,
+        },
+        types::SelectionError,
     };
+    use crate::{
+        algorithms::knapsack::{select_coin_knapsack_with_rng, KNAPSACK_STAGNATION_ITERATIONS},
+        types::{CoinSelectionOpt, ExcessStrategy, FeeRate, OutputGroup},
+        utils::{calculate_fee, effective_value},
+    };
+    use rand::{rngs::StdRng, Rng, SeedableRng};
 
+    #[cfg(feature = "std")]
     const CENT: f64 = 1000000.0;
+    #[cfg(feature = "std")]
     const COIN: f64 = 100000000.0;
+    #[cfg(feature = "std")]
     const RUN_TESTS: u32 = 100;
+    #[cfg(feature = "std")]
     const RUN_TESTS_SLIM: u32 = 10;
 
     fn knapsack_setup_options(adjusted_target: u64, target_feerate: f32) -> CoinSelectionOpt {
@@ -114,8 +432,8 @@ mod test {
             adjusted_target - min_change_value - calculate_fee(base_weight, target_feerate);
         CoinSelectionOpt {
             target_value,
-            target_feerate, // Simplified feerate
-            long_term_feerate: Some(0.4),
+            target_feerate: FeeRate::from_sat_per_wu(target_feerate), // Simplified feerate
+            long_term_feerate: Some(FeeRate::from_sat_per_wu(0.4)),
             min_absolute_fee: 0,
             base_weight,
             change_weight: 50,
@@ -124,6 +442,13 @@ mod test {
             avg_output_weight: 10,
             min_change_value,
             excess_strategy: ExcessStrategy::ToChange,
+            max_inputs: None,
+            bnb_tries: None,
+            include_uneconomical: false,
+            max_excess: None,
+            max_tx_weight: None,
+            knapsack_iterations: None,
+            knapsack_stagnation: None,
         }
     }
 
@@ -133,7 +458,7 @@ mod test {
         target_feerate: f32,
     ) -> Vec<OutputGroup> {
         let mut inputs: Vec<OutputGroup> = Vec::new();
-        for (i, j) in value.into_iter().zip(weights.into_iter()) {
+        for (i, j) in value.into_iter().zip(weights) {
             // input value = effective value + fees
             // Example If we want our input to be equal to 1 CENT while being considered by knapsack(effective value), we have to increase the input by the fees to beginwith
             let k = i.saturating_add(calculate_fee(j, target_feerate));
@@ -142,18 +467,22 @@ mod test {
                 weight: j,
                 input_count: 1,
                 creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
             })
         }
         inputs
     }
 
+    #[cfg(feature = "std")]
     fn knapsack_add_to_output_group(
         inputs: &mut Vec<OutputGroup>,
         value: Vec<u64>,
         weights: Vec<u64>,
         target_feerate: f32,
     ) {
-        for (i, j) in value.into_iter().zip(weights.into_iter()) {
+        for (i, j) in value.into_iter().zip(weights) {
             // input value = effective value + fees
             // Example If we want our input to be equal to 1 CENT while being considered by knapsack(effective value), we have to increase the input by the fees to beginwith
             let k = i.saturating_add(calculate_fee(j, target_feerate));
@@ -162,10 +491,14 @@ mod test {
                 weight: j,
                 input_count: 1,
                 creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
             })
         }
     }
 
+    #[cfg(feature = "std")]
     fn knapsack_test_vectors() {
         let mut inputs_verify: Vec<usize> = Vec::new();
         for _ in 0..RUN_TESTS {
@@ -268,8 +601,8 @@ mod test {
             if let Ok(result) = select_coin_knapsack(&inputs, &options) {
                 // Chekcing if knapsack selects exactly 1 inputs
                 assert_eq!(result.selected_inputs.len(), 1);
-                // Checking if the selected inputs are 10 CENTS
-                inputs_verify = vec![10];
+                // Checking if the selected input is the 10 CENTS one, found by the exact match pre-pass
+                inputs_verify = vec![3];
                 assert!(inputs_verify
                     .iter()
                     .all(|&item| result.selected_inputs.contains(&item)));
@@ -294,13 +627,13 @@ mod test {
             options = knapsack_setup_options((72.0 * CENT).round() as u64, 0.77);
             result = select_coin_knapsack(&inputs, &options);
             assert!(matches!(result, Err(SelectionError::NoSolutionFound)));
-            // Testing if knapsack can select 3 input (6,7,8) CENTS to make 16 CENTS
+            // Testing if knapsack selects the 20 CENTS input to make 16 CENTS, as 6+7+8 CENTS overshoot it
             options = knapsack_setup_options((16.0 * CENT).round() as u64, 0.77);
             if let Ok(result) = select_coin_knapsack(&inputs, &options) {
-                // Chekcing if knapsack selects exactly 3 inputs
-                assert_eq!(result.selected_inputs.len(), 3);
-                // Checking if the selected inputs are 6,7,8 CENTS
-                inputs_verify = vec![0, 1, 2];
+                // Chekcing if knapsack selects exactly 1 input
+                assert_eq!(result.selected_inputs.len(), 1);
+                // Checking if the selected input is the 20 CENTS one
+                inputs_verify = vec![3];
                 assert!(inputs_verify
                     .iter()
                     .all(|&item| result.selected_inputs.contains(&item)));
@@ -443,8 +776,8 @@ mod test {
             // Testing if knapsack can select 2 input (100,1) CENTS to make 100.01 CENTs, therby avoiding creating small change if 100 & 0.05 is chosen
             options = CoinSelectionOpt {
                 target_value: (100.01 * CENT).round() as u64,
-                target_feerate: 0.56, // Simplified feerate
-                long_term_feerate: Some(0.4),
+                target_feerate: FeeRate::from_sat_per_wu(0.56), // Simplified feerate
+                long_term_feerate: Some(FeeRate::from_sat_per_wu(0.4)),
                 min_absolute_fee: 0,
                 base_weight: 10,
                 change_weight: 50,
@@ -453,6 +786,13 @@ mod test {
                 avg_output_weight: 10,
                 min_change_value: (0.05 * CENT).round() as u64, // Setting minimum change value = 0.05 CENT. This will make the algorithm to avoid creating small change.
                 excess_strategy: ExcessStrategy::ToChange,
+                max_inputs: None,
+                bnb_tries: None,
+                include_uneconomical: false,
+                max_excess: None,
+                max_tx_weight: None,
+                knapsack_iterations: None,
+                knapsack_stagnation: None,
             };
             if let Ok(result) = select_coin_knapsack(&inputs, &options) {
                 // Chekcing if knapsack selects exactly 2 inputs
@@ -549,6 +889,355 @@ mod test {
         );
     }
 
+    fn selected_effective_value(
+        inputs: &[OutputGroup],
+        selected_inputs: &[usize],
+        target_feerate: f32,
+    ) -> u64 {
+        selected_inputs
+            .iter()
+            .map(|&i| effective_value(&inputs[i], target_feerate))
+            .sum()
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_knapsack_dp_finds_exact_match() {
+        // 40 coins of odd sizes, only a few specific combinations match the adjusted target exactly
+        let mut rng = StdRng::seed_from_u64(3);
+        let values: Vec<u64> = (0..40).map(|_| rng.gen_range(10_000..100_000)).collect();
+        let weights: Vec<u64> = vec![100; 40];
+        let adjusted_target = values[3] + values[11] + values[17] + values[29] + values[36];
+        let inputs = knapsack_setup_output_groups(values, weights, 0.5);
+        let options = knapsack_setup_options(adjusted_target, 0.5);
+
+        let result = select_coin_knapsack_dp(&inputs, &options).unwrap();
+        assert_eq!(
+            selected_effective_value(&inputs, &result.selected_inputs, 0.5),
+            adjusted_target
+        );
+
+        let mut randomized_misses = 0;
+        for _ in 0..RUN_TESTS_SLIM {
+            let result = select_coin_knapsack(&inputs, &options).unwrap();
+            let value = selected_effective_value(&inputs, &result.selected_inputs, 0.5);
+            assert!(value >= adjusted_target);
+            if value > adjusted_target {
+                randomized_misses += 1;
+            }
+        }
+        assert!(randomized_misses > 0);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_knapsack_exact_match_pre_pass() {
+        // A single coin worth exactly the adjusted target, larger than all the others combined
+        let inputs = knapsack_setup_output_groups(
+            vec![3000, 16_000, 2000, 5000],
+            vec![100, 100, 100, 100],
+            0.5,
+        );
+        let options = knapsack_setup_options(16_000, 0.5);
+        for _ in 0..RUN_TESTS_SLIM {
+            let result = select_coin_knapsack(&inputs, &options).unwrap();
+            assert_eq!(result.selected_inputs, vec![1]);
+        }
+
+        // Greedily picking 7000 and 3000 hits the adjusted target exactly
+        let inputs = knapsack_setup_output_groups(
+            vec![2500, 7000, 3000, 5000],
+            vec![100, 100, 100, 100],
+            0.5,
+        );
+        let options = knapsack_setup_options(10_000, 0.5);
+        for _ in 0..RUN_TESTS_SLIM {
+            let result = select_coin_knapsack(&inputs, &options).unwrap();
+            assert_eq!(result.selected_inputs, vec![1, 2]);
+            assert_eq!(
+                selected_effective_value(&inputs, &result.selected_inputs, 0.5),
+                10_000
+            );
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_knapsack_iterations() {
+        // No subset hits 10000 exactly, only the randomized passes find the one covering it
+        let inputs = knapsack_setup_output_groups(vec![3000, 4000, 5000], vec![100, 100, 100], 0.5);
+        let options = CoinSelectionOpt {
+            knapsack_iterations: Some(0),
+            ..knapsack_setup_options(10_000, 0.5)
+        };
+        let result = select_coin_knapsack(&inputs, &options);
+        assert!(matches!(result, Err(SelectionError::NoSolutionFound)));
+
+        let options = CoinSelectionOpt {
+            knapsack_iterations: Some(10),
+            ..options
+        };
+        let result = select_coin_knapsack(&inputs, &options).unwrap();
+        assert_eq!(result.selected_inputs, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_knapsack_iterations_budget() {
+        let mut pool_rng = StdRng::seed_from_u64(5);
+        let values: Vec<u64> = (0..30).map(|_| pool_rng.gen_range(1000..20_000)).collect();
+        let inputs = knapsack_setup_output_groups(values, vec![100; 30], 1.0);
+        // Dropping the excess to fees at the long term feerate, the waste is the excess over the target. Without a
+        // change output, the 500 sats of minimum change are not part of the adjusted target
+        let options = CoinSelectionOpt {
+            long_term_feerate: Some(FeeRate::from_sat_per_wu(1.0)),
+            excess_strategy: ExcessStrategy::ToFee,
+            ..knapsack_setup_options(100_501, 1.0)
+        };
+
+        let mut wastes = Vec::new();
+        for knapsack_iterations in [1, 2, 5, 10, 100, 1000] {
+            let options = CoinSelectionOpt {
+                knapsack_iterations: Some(knapsack_iterations),
+                ..options.clone()
+            };
+            // The same seed replays the passes of the smaller budgets before making more
+            let result =
+                select_coin_knapsack_with_rng(&inputs, &options, &mut StdRng::seed_from_u64(9))
+                    .unwrap();
+            assert!(selected_effective_value(&inputs, &result.selected_inputs, 1.0) >= 100_001);
+            wastes.push(result.waste.0);
+        }
+        assert!(wastes.windows(2).all(|pair| pair[1] <= pair[0]));
+        assert!(wastes[wastes.len() - 1] < wastes[0]);
+    }
+
+    #[test]
+    fn test_knapsack_stagnation() {
+        let mut pool_rng = StdRng::seed_from_u64(5);
+        let values: Vec<u64> = (0..30).map(|_| pool_rng.gen_range(1000..20_000)).collect();
+        let inputs = knapsack_setup_output_groups(values, vec![100; 30], 1.0);
+        let options = knapsack_setup_options(100_001, 1.0);
+        let select = |knapsack_iterations, knapsack_stagnation, seed| {
+            let options = CoinSelectionOpt {
+                knapsack_iterations,
+                knapsack_stagnation,
+                ..options.clone()
+            };
+            select_coin_knapsack_with_rng(&inputs, &options, &mut StdRng::seed_from_u64(seed))
+                .unwrap()
+        };
+
+        // Stopping early only makes the first of the passes, with the same seed, so every pass can only do better
+        for seed in 0..10 {
+            let stagnating = select(None, None, seed).selected_inputs;
+            assert_eq!(
+                stagnating,
+                select(None, Some(KNAPSACK_STAGNATION_ITERATIONS), seed).selected_inputs
+            );
+            let exhaustive = select(None, Some(u32::MAX), seed).selected_inputs;
+            let exhaustive_value = selected_effective_value(&inputs, &exhaustive, 1.0);
+            assert!(exhaustive_value >= 100_001);
+            assert!(selected_effective_value(&inputs, &stagnating, 1.0) >= exhaustive_value);
+        }
+
+        // Without any tolerance, the search stops after the first pass
+        assert_eq!(select(None, Some(0), 9), select(Some(1), Some(u32::MAX), 9));
+    }
+
+    #[test]
+    fn test_knapsack_excess_strategy() {
+        let inputs = knapsack_setup_output_groups(vec![3000, 5000, 7000, 9500], vec![100; 4], 1.0);
+        let options = CoinSelectionOpt {
+            long_term_feerate: Some(FeeRate::from_sat_per_wu(1.0)),
+            ..knapsack_setup_options(10_500, 1.0)
+        };
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut selected_values = Vec::new();
+        for excess_strategy in [
+            ExcessStrategy::ToChange,
+            ExcessStrategy::ToFee,
+            ExcessStrategy::ToRecipient,
+        ] {
+            let options = CoinSelectionOpt {
+                excess_strategy,
+                ..options.clone()
+            };
+            let result = select_coin_knapsack_with_rng(&inputs, &options, &mut rng).unwrap();
+            let effective_value = selected_effective_value(&inputs, &result.selected_inputs, 1.0);
+            if options.excess_strategy == ExcessStrategy::ToChange {
+                // 5000 + 7000 covers the minimum change on top
+                assert_eq!(result.selected_inputs, vec![1, 2]);
+                assert_eq!(result.waste.0, options.change_cost as i64);
+            } else {
+                // Without a change output, 3000 + 7000 hit the target and the base fee exactly
+                assert_eq!(result.selected_inputs, vec![0, 2]);
+                assert_eq!(effective_value, 10_000);
+                assert_eq!(result.change_value, None);
+                assert_eq!(result.waste.0, 0);
+            }
+            selected_values.push(result.selected_value);
+        }
+        assert!(selected_values[1] < selected_values[0]);
+        assert_eq!(selected_values[1], selected_values[2]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_knapsack_dp_is_deterministic() {
+        let inputs = knapsack_setup_output_groups(
+            vec![6000, 7000, 8000, 20_000, 30_000],
+            vec![100, 200, 100, 10, 5],
+            0.77,
+        );
+        // 6000 + 8000 overshoots the least, and less than the 20_000 input
+        let options = knapsack_setup_options(13_500, 0.77);
+        let result = select_coin_knapsack_dp(&inputs, &options).unwrap();
+        let mut selected_inputs = result.selected_inputs.clone();
+        selected_inputs.sort();
+        assert_eq!(selected_inputs, vec![0, 2]);
+        for _ in 0..RUN_TESTS_SLIM {
+            let repeated = select_coin_knapsack_dp(&inputs, &options).unwrap();
+            assert_eq!(repeated.selected_inputs, result.selected_inputs);
+        }
+
+        // Above the limit the randomized knapsack still finds a selection
+        let result = select_coin_knapsack_dp_with_limit(&inputs, &options, 1000).unwrap();
+        assert!(selected_effective_value(&inputs, &result.selected_inputs, 0.77) >= 13_500);
+
+        let options = knapsack_setup_options(72_000, 0.77);
+        let result = select_coin_knapsack_dp(&inputs, &options);
+        assert!(matches!(result, Err(SelectionError::NoSolutionFound)));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_knapsack_subset_without_larger_coin() {
+        // Without the 20 and 30 CENTS coins, no single coin covers 16 CENTS and the subset search combines 6+7+8 CENTS
+        let inputs = knapsack_setup_output_groups(
+            vec![
+                (6.0 * CENT).round() as u64,
+                (7.0 * CENT).round() as u64,
+                (8.0 * CENT).round() as u64,
+            ],
+            vec![100, 200, 100],
+            0.77,
+        );
+        let options = knapsack_setup_options((16.0 * CENT).round() as u64, 0.77);
+        for seed in 0..RUN_TESTS_SLIM as u64 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let result = select_coin_knapsack_with_rng(&inputs, &options, &mut rng).unwrap();
+            assert_eq!(result.selected_inputs, vec![0, 1, 2]);
+        }
+        let result = select_coin_knapsack_dp(&inputs, &options).unwrap();
+        assert_eq!(result.selected_inputs, vec![0, 1, 2]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_knapsack_lowest_larger_fallback() {
+        // Every coin is larger than the target, there is no subset of smaller coins at all
+        let inputs = knapsack_setup_output_groups(
+            vec![50_000, 80_000, 30_000, 120_000],
+            vec![100, 100, 100, 100],
+            1.0,
+        );
+        let options = knapsack_setup_options(10_000, 1.0);
+        let mut rng = StdRng::seed_from_u64(1);
+        let result = select_coin_knapsack_with_rng(&inputs, &options, &mut rng).unwrap();
+        assert_eq!(result.selected_inputs, vec![2]);
+        let result = select_coin_knapsack_dp(&inputs, &options).unwrap();
+        assert_eq!(result.selected_inputs, vec![2]);
+
+        // 6000 + 7000 + 8000 overshoots 16_000 by more than the 20_000 coin
+        let inputs = knapsack_setup_output_groups(
+            vec![6000, 7000, 8000, 20_000, 30_000],
+            vec![100, 100, 100, 100, 100],
+            1.0,
+        );
+        let options = knapsack_setup_options(16_000, 1.0);
+        for _ in 0..RUN_TESTS_SLIM {
+            let result = select_coin_knapsack_with_rng(&inputs, &options, &mut rng).unwrap();
+            assert_eq!(result.selected_inputs, vec![3]);
+        }
+        let result = select_coin_knapsack_dp(&inputs, &options).unwrap();
+        assert_eq!(result.selected_inputs, vec![3]);
+
+        // Unless the larger coin is too heavy
+        let options = CoinSelectionOpt {
+            max_tx_weight: Some(1000),
+            ..knapsack_setup_options(16_000, 1.0)
+        };
+        let mut inputs = inputs;
+        inputs[3].weight = 2000;
+        inputs[4].weight = 2000;
+        let result = select_coin_knapsack_with_rng(&inputs, &options, &mut rng).unwrap();
+        assert_eq!(result.selected_inputs, vec![0, 1, 2]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_knapsack_larger_value_smaller_effective_value() {
+        // The first coin is worth more than the adjusted target, but not once its fee is paid, both coins match it
+        // exactly
+        let inputs = knapsack_setup_output_groups(vec![9700, 300], vec![400, 100], 1.0);
+        assert_eq!(inputs[0].value, 10_100);
+        let options = knapsack_setup_options(10_000, 1.0);
+        let mut rng = StdRng::seed_from_u64(0);
+        let result = select_coin_knapsack_with_rng(&inputs, &options, &mut rng).unwrap();
+        assert_eq!(result.selected_inputs, vec![0, 1]);
+        let result = select_coin_knapsack_dp(&inputs, &options).unwrap();
+        assert_eq!(result.selected_inputs, vec![0, 1]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_knapsack_max_inputs() {
+        let inputs = knapsack_setup_output_groups(
+            vec![1000, 2000, 3000, 4000, 6000],
+            vec![100, 100, 100, 100, 100],
+            0.5,
+        );
+        let mut options = knapsack_setup_options(10_000, 0.5);
+        options.max_inputs = Some(2);
+        for _ in 0..RUN_TESTS_SLIM {
+            let result = select_coin_knapsack(&inputs, &options).unwrap();
+            assert!(result.selected_inputs.len() <= 2);
+            assert!(selected_effective_value(&inputs, &result.selected_inputs, 0.5) >= 10_000);
+        }
+        let result = select_coin_knapsack_dp(&inputs, &options).unwrap();
+        assert!(result.selected_inputs.len() <= 2);
+
+        // Every coin is smaller than the adjusted target
+        options.max_inputs = Some(1);
+        let result = select_coin_knapsack(&inputs, &options);
+        assert!(matches!(result, Err(SelectionError::NoSolutionFound)));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_knapsack_min_absolute_fee() {
+        let inputs = knapsack_setup_output_groups(vec![1000, 2000, 3000], vec![100, 100, 100], 0.5);
+        let mut options = knapsack_setup_options(3000, 0.5);
+        let result = select_coin_knapsack(&inputs, &options).unwrap();
+        assert_eq!(result.selected_inputs, vec![2]);
+
+        // The 5 sats fee for the base weight is raised to 1005 sats
+        options.min_absolute_fee = 1005;
+        for _ in 0..RUN_TESTS_SLIM {
+            let mut result = select_coin_knapsack(&inputs, &options).unwrap();
+            result.selected_inputs.sort();
+            assert_eq!(result.selected_inputs, vec![0, 2]);
+            assert!(
+                result.selected_value - options.target_value - options.min_change_value
+                    >= options.min_absolute_fee
+            );
+        }
+        let mut result = select_coin_knapsack_dp(&inputs, &options).unwrap();
+        result.selected_inputs.sort();
+        assert_eq!(result.selected_inputs, vec![0, 2]);
+    }
+
+    #[cfg(feature = "std")]
     #[test]
     fn test_knapsack() {
         knapsack_test_vectors();
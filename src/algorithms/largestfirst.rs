@@ -0,0 +1,252 @@
+use crate::{
+    types::{CoinSelectionOpt, SelectionError, SelectionOutput, WasteMetric, WeightedUtxo},
+    utils::{
+        calculate_bump_fee, calculate_change, calculate_selection_fee, calculate_waste_detailed,
+        economical_inputs, effective_value, exceeds_max_inputs, exceeds_max_tx_weight,
+        funds_change, validate_feerate, validate_target, within_changeless_window,
+    },
+};
+use alloc::vec::Vec;
+use core::cmp::Reverse;
+
+/// Performs coin selection using the Largest First algorithm.
+///
+/// Inputs are spent in descending order of effective value, which minimizes the number of inputs.
+///
+/// Inputs costing more to spend than they are worth are skipped, unless `include_uneconomical` is set. The selection
+/// stops once it covers the target, the fee and a change output, see [`funds_change`], or as soon as it pays them
+/// without one, see [`within_changeless_window`].
+///
+/// Returns `InsufficientFunds` if the inputs can not cover the target, `NoSolutionFound` if covering it takes more
+/// than `max_inputs` inputs or goes over `max_tx_weight`, and `ArithmeticOverflow` if the values of the selected
+/// inputs or the target do not sum within a `u64`, see [`validate_target`].
+pub fn select_coin_largestfirst<T: WeightedUtxo>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+) -> Result<SelectionOutput, SelectionError> {
+    select_in_sorted_order(inputs, options, ValueOrder::Descending)
+}
+
+/// The order of effective value in which [`select_in_sorted_order`] spends the inputs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ValueOrder {
+    Ascending,
+    Descending,
+}
+
+/// Accumulates the inputs sorted by effective value in the given `order`, ties kept in the order of `inputs`.
+///
+/// Inputs costing more to spend than they are worth are skipped, unless `include_uneconomical` is set. The estimated
+/// fee covers the `base_weight` and the weight of every selected input, plus the fee lifting their unconfirmed
+/// ancestors to the `target_feerate`, see [`calculate_bump_fee`]. The selection stops once it covers the target, the
+/// fee and a change output, see [`funds_change`], or as soon as it pays them without a change output, see
+/// [`within_changeless_window`].
+pub(crate) fn select_in_sorted_order<T: WeightedUtxo>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+    order: ValueOrder,
+) -> Result<SelectionOutput, SelectionError> {
+    validate_feerate(*options.target_feerate)?;
+    validate_target(inputs, options)?;
+    let mut accumulated_value: u64 = 0;
+    let mut accumulated_weight: u64 = 0;
+    let mut selected_inputs: Vec<usize> = Vec::new();
+    let mut estimated_fees: u64 = 0;
+    let mut bump_fees: u64 = 0;
+    let mut input_count: usize = 0;
+
+    let mut sorted_inputs: Vec<(usize, &T)> = economical_inputs(inputs, options)
+        .into_iter()
+        .map(|index| (index, &inputs[index]))
+        .collect();
+    match order {
+        ValueOrder::Ascending => {
+            sorted_inputs.sort_by_key(|(_, input)| effective_value(*input, *options.target_feerate))
+        }
+        ValueOrder::Descending => sorted_inputs
+            .sort_by_key(|(_, input)| Reverse(effective_value(*input, *options.target_feerate))),
+    }
+
+    for (index, input) in sorted_inputs {
+        input_count += input.input_count();
+        if exceeds_max_inputs(options, input_count)
+            || exceeds_max_tx_weight(options, accumulated_weight + input.weight())
+        {
+            return Err(SelectionError::NoSolutionFound);
+        }
+        accumulated_value = accumulated_value
+            .checked_add(input.value())
+            .ok_or(SelectionError::ArithmeticOverflow)?;
+        accumulated_weight += input.weight();
+        bump_fees += calculate_bump_fee(input, *options.target_feerate);
+        estimated_fees = calculate_selection_fee(options, accumulated_weight) + bump_fees;
+        selected_inputs.push(index);
+
+        if funds_change(options, accumulated_value, estimated_fees)
+            || within_changeless_window(options, accumulated_value, estimated_fees)
+        {
+            break;
+        }
+    }
+
+    if !funds_change(options, accumulated_value, estimated_fees)
+        && !within_changeless_window(options, accumulated_value, estimated_fees)
+    {
+        Err(SelectionError::InsufficientFunds)
+    } else {
+        let mut waste_breakdown = calculate_waste_detailed(
+            options,
+            accumulated_value,
+            accumulated_weight,
+            estimated_fees,
+        );
+        // Lifting the unconfirmed ancestors to the target feerate is paid only because the inputs are spent now
+        waste_breakdown.feerate_diff += bump_fees as i64;
+        selected_inputs.sort_unstable();
+        Ok(SelectionOutput {
+            selected_inputs,
+            waste: WasteMetric(waste_breakdown.total()),
+            waste_breakdown,
+            change_value: calculate_change(options, accumulated_value, estimated_fees),
+            estimated_fee: estimated_fees,
+            selected_value: accumulated_value,
+            selected_weight: accumulated_weight,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use crate::{
+        algorithms::largestfirst::select_coin_largestfirst,
+        types::{CoinSelectionOpt, ExcessStrategy, FeeRate, OutputGroup, SelectionError},
+    };
+
+    fn setup_largestfirst_output_groups() -> Vec<OutputGroup> {
+        vec![
+            OutputGroup {
+                value: 1000,
+                weight: 100,
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            },
+            OutputGroup {
+                value: 3000,
+                weight: 300,
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            },
+            OutputGroup {
+                value: 2000,
+                weight: 200,
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            },
+            OutputGroup {
+                value: 500,
+                weight: 50,
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            },
+        ]
+    }
+
+    fn setup_options(target_value: u64) -> CoinSelectionOpt {
+        CoinSelectionOpt {
+            target_value,
+            target_feerate: FeeRate::from_sat_per_wu(0.4), // Simplified feerate
+            long_term_feerate: Some(FeeRate::from_sat_per_wu(0.4)),
+            min_absolute_fee: 0,
+            base_weight: 10,
+            change_weight: 50,
+            change_cost: 10,
+            avg_input_weight: 20,
+            avg_output_weight: 10,
+            min_change_value: 500,
+            excess_strategy: ExcessStrategy::ToChange,
+            max_inputs: None,
+            bnb_tries: None,
+            include_uneconomical: false,
+            max_excess: None,
+            max_tx_weight: None,
+            knapsack_iterations: None,
+            knapsack_stagnation: None,
+        }
+    }
+
+    #[test]
+    fn test_largestfirst_successful() {
+        let inputs = setup_largestfirst_output_groups();
+        let options = setup_options(3500);
+        let result = select_coin_largestfirst(&inputs, &options).unwrap();
+        assert_eq!(result.selected_inputs, vec![1, 2]);
+        // 5000 sats less the target, 205 sats of fees for the inputs and the base weight, and 21 sats for the change
+        // output
+        assert_eq!(result.change_value, Some(1274));
+        assert_eq!(result.estimated_fee, 205);
+        assert_eq!(result.selected_value, 5000);
+
+        // The excess is not turned into change
+        let options = CoinSelectionOpt {
+            excess_strategy: ExcessStrategy::ToFee,
+            ..setup_options(3500)
+        };
+        let result = select_coin_largestfirst(&inputs, &options).unwrap();
+        assert_eq!(result.change_value, None);
+    }
+
+    #[test]
+    fn test_largestfirst_single_coin_covers_target() {
+        let inputs = setup_largestfirst_output_groups();
+        // 3000 covers 2354 + 500 min change + 125 fee + 21 for the change output exactly
+        let options = setup_options(2354);
+        let result = select_coin_largestfirst(&inputs, &options).unwrap();
+        assert_eq!(result.selected_inputs, vec![1]);
+
+        // One sat more and the change output would fall short of the minimum change once paid for, the next largest
+        // coin is needed
+        let options = setup_options(2355);
+        let result = select_coin_largestfirst(&inputs, &options).unwrap();
+        assert_eq!(result.selected_inputs, vec![1, 2]);
+
+        // Within the 10 sats cost of change of paying 2866 sats and the fee, no change output is needed
+        let options = setup_options(2866);
+        let result = select_coin_largestfirst(&inputs, &options).unwrap();
+        assert_eq!(result.selected_inputs, vec![1]);
+        assert_eq!(result.change_value, None);
+    }
+
+    #[test]
+    fn test_largestfirst_max_inputs() {
+        let inputs = setup_largestfirst_output_groups();
+        let mut options = setup_options(3500);
+        options.max_inputs = Some(2);
+        let result = select_coin_largestfirst(&inputs, &options).unwrap();
+        assert_eq!(result.selected_inputs, vec![1, 2]);
+
+        options.max_inputs = Some(1);
+        let result = select_coin_largestfirst(&inputs, &options);
+        assert!(matches!(result, Err(SelectionError::NoSolutionFound)));
+    }
+
+    #[test]
+    fn test_largestfirst_insufficient() {
+        let inputs = setup_largestfirst_output_groups();
+        let options = setup_options(6000);
+        let result = select_coin_largestfirst(&inputs, &options);
+        assert!(matches!(result, Err(SelectionError::InsufficientFunds)));
+    }
+}
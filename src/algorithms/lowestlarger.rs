@@ -1,74 +1,220 @@
 use crate::{
-    types::{CoinSelectionOpt, OutputGroup, SelectionError, SelectionOutput, WasteMetric},
-    utils::{calculate_fee, calculate_waste, effective_value},
+    algorithms::knapsack::{knap_sack, knapsack_candidates},
+    types::{
+        CoinSelectionOpt, ExcessStrategy, SelectionError, SelectionOutput, WasteMetric,
+        WeightedUtxo,
+    },
+    utils::{
+        calculate_change, calculate_selection_fee, calculate_waste_detailed, economical_inputs,
+        effective_value, exceeds_max_inputs, exceeds_max_tx_weight, funds_change, validate_feerate,
+        validate_target, within_changeless_window,
+    },
 };
+use alloc::{vec, vec::Vec};
+#[cfg(feature = "std")]
+use rand::thread_rng;
+use rand::Rng;
 
 /// Performs coin selection using the Lowest Larger algorithm.
 ///
-/// Returns `NoSolutionFound` if no solution exists.
-pub fn select_coin_lowestlarger(
-    inputs: &[OutputGroup],
+/// Inputs costing more to spend than they are worth are skipped, unless `include_uneconomical` is set. The estimated
+/// fee covers the `base_weight` and the selected inputs, and the selection has to fund a change output on top, see
+/// [`funds_change`]. A selection paying the target and the fee without a change output is accepted, see
+/// [`within_changeless_window`].
+///
+/// Returns `NoSolutionFound` if no solution exists, or if covering the target takes more than `max_inputs` inputs
+/// or goes over `max_tx_weight`, and `ArithmeticOverflow` if the values of the selected inputs or the target do not
+/// sum within a `u64`, see [`validate_target`].
+pub fn select_coin_lowestlarger<T: WeightedUtxo>(
+    inputs: &[T],
     options: &CoinSelectionOpt,
 ) -> Result<SelectionOutput, SelectionError> {
+    validate_feerate(*options.target_feerate)?;
+    validate_target(inputs, options)?;
     let mut accumulated_value: u64 = 0;
     let mut accumulated_weight: u64 = 0;
     let mut selected_inputs: Vec<usize> = Vec::new();
     let mut estimated_fees: u64 = 0;
+    let mut input_count: usize = 0;
     let target = options.target_value + options.min_change_value;
 
-    let mut sorted_inputs: Vec<_> = inputs.iter().enumerate().collect();
-    sorted_inputs.sort_by_key(|(_, input)| effective_value(input, options.target_feerate));
-
-    let index = sorted_inputs.partition_point(|(_, input)| {
-        input.value <= (target + calculate_fee(input.weight, options.target_feerate))
-    });
+    let (sorted_inputs, index) = partition_inputs(inputs, options, target);
+    // Covering the minimum change too, or paying the target without a change output
+    let covered = |accumulated_value: u64, estimated_fees: u64| {
+        funds_change(options, accumulated_value, estimated_fees)
+            || within_changeless_window(options, accumulated_value, estimated_fees)
+    };
 
     for (idx, input) in sorted_inputs.iter().take(index).rev() {
-        accumulated_value += input.value;
-        accumulated_weight += input.weight;
-        estimated_fees = calculate_fee(accumulated_weight, options.target_feerate);
+        input_count += input.input_count();
+        if exceeds_max_inputs(options, input_count)
+            || exceeds_max_tx_weight(options, accumulated_weight + input.weight())
+        {
+            return Err(SelectionError::NoSolutionFound);
+        }
+        accumulated_value = accumulated_value
+            .checked_add(input.value())
+            .ok_or(SelectionError::ArithmeticOverflow)?;
+        accumulated_weight += input.weight();
+        estimated_fees = calculate_selection_fee(options, accumulated_weight);
         selected_inputs.push(*idx);
 
-        if accumulated_value >= (target + estimated_fees.max(options.min_absolute_fee)) {
+        if covered(accumulated_value, estimated_fees) {
             break;
         }
     }
 
-    if accumulated_value < (target + estimated_fees.max(options.min_absolute_fee)) {
+    if !covered(accumulated_value, estimated_fees) {
         for (idx, input) in sorted_inputs.iter().skip(index) {
-            accumulated_value += input.value;
-            accumulated_weight += input.weight;
-            estimated_fees = calculate_fee(accumulated_weight, options.target_feerate);
+            input_count += input.input_count();
+            if exceeds_max_inputs(options, input_count)
+                || exceeds_max_tx_weight(options, accumulated_weight + input.weight())
+            {
+                return Err(SelectionError::NoSolutionFound);
+            }
+            accumulated_value = accumulated_value
+                .checked_add(input.value())
+                .ok_or(SelectionError::ArithmeticOverflow)?;
+            accumulated_weight += input.weight();
+            estimated_fees = calculate_selection_fee(options, accumulated_weight);
             selected_inputs.push(*idx);
 
-            if accumulated_value >= (target + estimated_fees.max(options.min_absolute_fee)) {
+            if covered(accumulated_value, estimated_fees) {
                 break;
             }
         }
     }
 
-    if accumulated_value < (target + estimated_fees.max(options.min_absolute_fee)) {
+    if !covered(accumulated_value, estimated_fees) {
         Err(SelectionError::InsufficientFunds)
     } else {
-        let waste: u64 = calculate_waste(
+        let waste_breakdown = calculate_waste_detailed(
             options,
             accumulated_value,
             accumulated_weight,
             estimated_fees,
         );
+        selected_inputs.sort_unstable();
         Ok(SelectionOutput {
             selected_inputs,
-            waste: WasteMetric(waste),
+            waste: WasteMetric(waste_breakdown.total()),
+            waste_breakdown,
+            change_value: calculate_change(options, accumulated_value, estimated_fees),
+            estimated_fee: estimated_fees,
+            selected_value: accumulated_value,
+            selected_weight: accumulated_weight,
         })
     }
 }
 
+/// Performs coin selection picking the better of the lowest larger coin and the best subset of smaller coins.
+///
+/// Like Bitcoin Core's legacy selector, two candidates are considered: the single coin with the lowest value
+/// larger than the target, found by the same partition as [`select_coin_lowestlarger`], and the subset of the
+/// coins smaller than the target approximated by the randomized knapsack. The candidate with the lower waste,
+/// computed from the actual values of both, is returned.
+///
+/// Returns `InsufficientFunds` if neither candidate exists.
+#[cfg(feature = "std")]
+pub fn select_coin_lowestlarger_knapsack<T: WeightedUtxo>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+) -> Result<SelectionOutput, SelectionError> {
+    select_coin_lowestlarger_knapsack_with_rng(inputs, options, &mut thread_rng())
+}
+
+/// Performs coin selection picking the better of the lowest larger coin and the best subset of smaller coins,
+/// drawing from `rng`, see [`select_coin_lowestlarger_knapsack`].
+pub fn select_coin_lowestlarger_knapsack_with_rng<T: WeightedUtxo, R: Rng>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+    rng: &mut R,
+) -> Result<SelectionOutput, SelectionError> {
+    validate_feerate(*options.target_feerate)?;
+    validate_target(inputs, options)?;
+    let target = options.target_value + options.min_change_value;
+    let (sorted_inputs, index) = partition_inputs(inputs, options, target);
+    let lowest_larger = sorted_inputs[index..]
+        .iter()
+        .find(|(_, input)| {
+            let estimated_fees = calculate_selection_fee(options, input.weight());
+            input.value() >= target + estimated_fees.max(options.min_absolute_fee)
+                && !exceeds_max_inputs(options, input.input_count())
+                && !exceeds_max_tx_weight(options, input.weight())
+        })
+        .map(|&(idx, _)| vec![idx]);
+
+    let (adjusted_target, smaller_coins) = knapsack_candidates(inputs, options);
+    // Only the subset is used, its waste is recomputed below from the actual values
+    let subset_options = CoinSelectionOpt {
+        excess_strategy: ExcessStrategy::ToChange,
+        ..options.clone()
+    };
+    let best_subset = knap_sack(
+        inputs,
+        adjusted_target,
+        &smaller_coins,
+        &subset_options,
+        rng,
+    )
+    .ok()
+    .map(|selection| selection.selected_inputs);
+
+    [lowest_larger, best_subset]
+        .into_iter()
+        .flatten()
+        .map(|mut selected_inputs| {
+            let accumulated_value: u64 = selected_inputs.iter().map(|&i| inputs[i].value()).sum();
+            let accumulated_weight: u64 = selected_inputs.iter().map(|&i| inputs[i].weight()).sum();
+            let estimated_fees = calculate_selection_fee(options, accumulated_weight);
+            let waste_breakdown = calculate_waste_detailed(
+                options,
+                accumulated_value,
+                accumulated_weight,
+                estimated_fees,
+            );
+            selected_inputs.sort_unstable();
+            SelectionOutput {
+                selected_inputs,
+                waste: WasteMetric(waste_breakdown.total()),
+                waste_breakdown,
+                change_value: calculate_change(options, accumulated_value, estimated_fees),
+                estimated_fee: estimated_fees,
+                selected_value: accumulated_value,
+                selected_weight: accumulated_weight,
+            }
+        })
+        .min_by_key(|selection| selection.waste.0)
+        .ok_or(SelectionError::InsufficientFunds)
+}
+
+/// Sorts the inputs by ascending effective value, and returns them along with the position of the first input
+/// whose value covers `target` plus its own fee.
+fn partition_inputs<'a, T: WeightedUtxo>(
+    inputs: &'a [T],
+    options: &CoinSelectionOpt,
+    target: u64,
+) -> (Vec<(usize, &'a T)>, usize) {
+    let mut sorted_inputs: Vec<_> = economical_inputs(inputs, options)
+        .into_iter()
+        .map(|index| (index, &inputs[index]))
+        .collect();
+    sorted_inputs.sort_by_key(|(_, input)| effective_value(input, *options.target_feerate));
+
+    let index = sorted_inputs.partition_point(|(_, input)| {
+        input.value() <= (target + calculate_selection_fee(options, input.weight()))
+    });
+    (sorted_inputs, index)
+}
+
 #[cfg(test)]
 mod test {
 
+    #[cfg(feature = "std")]
+    use crate::algorithms::lowestlarger::select_coin_lowestlarger_knapsack;
     use crate::{
         algorithms::lowestlarger::select_coin_lowestlarger,
-        types::{CoinSelectionOpt, ExcessStrategy, OutputGroup, SelectionError},
+        types::{CoinSelectionOpt, ExcessStrategy, FeeRate, OutputGroup, SelectionError},
     };
 
     fn setup_lowestlarger_output_groups() -> Vec<OutputGroup> {
@@ -78,72 +224,108 @@ mod test {
                 weight: 100,
                 input_count: 1,
                 creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
             },
             OutputGroup {
                 value: 1500,
                 weight: 200,
                 input_count: 1,
                 creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
             },
             OutputGroup {
                 value: 3400,
                 weight: 300,
                 input_count: 1,
                 creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
             },
             OutputGroup {
                 value: 2200,
                 weight: 150,
                 input_count: 1,
                 creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
             },
             OutputGroup {
                 value: 1190,
                 weight: 200,
                 input_count: 1,
                 creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
             },
             OutputGroup {
                 value: 3300,
                 weight: 100,
                 input_count: 1,
                 creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
             },
             OutputGroup {
                 value: 1000,
                 weight: 190,
                 input_count: 1,
                 creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
             },
             OutputGroup {
                 value: 2000,
                 weight: 210,
                 input_count: 1,
                 creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
             },
             OutputGroup {
                 value: 3000,
                 weight: 300,
                 input_count: 1,
                 creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
             },
             OutputGroup {
                 value: 2250,
                 weight: 250,
                 input_count: 1,
                 creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
             },
             OutputGroup {
                 value: 190,
                 weight: 220,
                 input_count: 1,
                 creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
             },
             OutputGroup {
                 value: 1750,
                 weight: 170,
                 input_count: 1,
                 creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
             },
         ]
     }
@@ -151,8 +333,8 @@ mod test {
     fn setup_options(target_value: u64) -> CoinSelectionOpt {
         CoinSelectionOpt {
             target_value,
-            target_feerate: 0.4, // Simplified feerate
-            long_term_feerate: Some(0.4),
+            target_feerate: FeeRate::from_sat_per_wu(0.4), // Simplified feerate
+            long_term_feerate: Some(FeeRate::from_sat_per_wu(0.4)),
             min_absolute_fee: 0,
             base_weight: 10,
             change_weight: 50,
@@ -161,6 +343,13 @@ mod test {
             avg_output_weight: 10,
             min_change_value: 500,
             excess_strategy: ExcessStrategy::ToChange,
+            max_inputs: None,
+            bnb_tries: None,
+            include_uneconomical: false,
+            max_excess: None,
+            max_tx_weight: None,
+            knapsack_iterations: None,
+            knapsack_stagnation: None,
         }
     }
 
@@ -172,6 +361,7 @@ mod test {
         assert!(result.is_ok());
         let selection_output = result.unwrap();
         assert!(!selection_output.selected_inputs.is_empty());
+        assert!(selection_output.selected_value >= options.target_value);
     }
 
     #[test]
@@ -181,4 +371,110 @@ mod test {
         let result = select_coin_lowestlarger(&inputs, &options);
         assert!(matches!(result, Err(SelectionError::InsufficientFunds)));
     }
+
+    #[test]
+    fn test_lowestlarger_max_inputs() {
+        let inputs = setup_lowestlarger_output_groups();
+        // No single input covers the target, the two largest ones do
+        let mut options = setup_options(4000);
+        options.max_inputs = Some(2);
+        let result = select_coin_lowestlarger(&inputs, &options).unwrap();
+        assert_eq!(result.selected_inputs.len(), 2);
+
+        options.max_inputs = Some(1);
+        let result = select_coin_lowestlarger(&inputs, &options);
+        assert!(matches!(result, Err(SelectionError::NoSolutionFound)));
+    }
+
+    #[cfg(feature = "std")]
+    fn setup_hybrid_options(target_value: u64) -> CoinSelectionOpt {
+        CoinSelectionOpt {
+            target_value,
+            target_feerate: FeeRate::from_sat_per_wu(1.0),
+            long_term_feerate: Some(FeeRate::from_sat_per_wu(1.0)),
+            min_absolute_fee: 0,
+            base_weight: 10,
+            change_weight: 50,
+            change_cost: 10,
+            avg_input_weight: 20,
+            avg_output_weight: 10,
+            min_change_value: 0,
+            excess_strategy: ExcessStrategy::ToFee,
+            max_inputs: None,
+            bnb_tries: None,
+            include_uneconomical: false,
+            max_excess: None,
+            max_tx_weight: None,
+            knapsack_iterations: None,
+            knapsack_stagnation: None,
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn setup_hybrid_output_groups(values: &[u64]) -> Vec<OutputGroup> {
+        values
+            .iter()
+            .map(|&value| OutputGroup {
+                value,
+                weight: 100,
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            })
+            .collect()
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_lowestlarger_knapsack_single_coin_wins() {
+        // The smaller coins overshoot by about 2000, the larger coin by 90
+        let inputs = setup_hybrid_output_groups(&[6000, 6000, 10_200, 50_000]);
+        let options = setup_hybrid_options(10_000);
+        for _ in 0..10 {
+            let result = select_coin_lowestlarger_knapsack(&inputs, &options).unwrap();
+            assert_eq!(result.selected_inputs, vec![2]);
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_lowestlarger_knapsack_subset_wins() {
+        // The smaller coins match the target exactly, the larger coin overshoots by 5000
+        let inputs = setup_hybrid_output_groups(&[4100, 6110, 15_100, 50_000]);
+        let options = setup_hybrid_options(10_000);
+        for _ in 0..10 {
+            let result = select_coin_lowestlarger_knapsack(&inputs, &options).unwrap();
+            let mut selected_inputs = result.selected_inputs.clone();
+            selected_inputs.sort();
+            assert_eq!(selected_inputs, vec![0, 1]);
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_lowestlarger_knapsack_insufficient_funds() {
+        let inputs = setup_hybrid_output_groups(&[4100, 6110]);
+        let result = select_coin_lowestlarger_knapsack(&inputs, &setup_hybrid_options(20_000));
+        assert!(matches!(result, Err(SelectionError::InsufficientFunds)));
+    }
+
+    #[test]
+    fn test_lowestlarger_changeless_exact_match() {
+        // The target plus the fee for the base weight and the input, with nothing left for a change output
+        let inputs = vec![OutputGroup {
+            value: 1045,
+            weight: 100,
+            input_count: 1,
+            creation_sequence: None,
+            ancestor_fee: 0,
+            ancestor_weight: 0,
+            block_height: None,
+        }];
+        let result = select_coin_lowestlarger(&inputs, &setup_options(1000)).unwrap();
+        assert_eq!(result.selected_inputs, vec![0]);
+        assert_eq!(result.change_value, None);
+        assert_eq!(result.waste.0, 0);
+    }
 }
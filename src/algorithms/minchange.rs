@@ -0,0 +1,262 @@
+use crate::{
+    types::{CoinSelectionOpt, SelectionError, SelectionOutput, WasteMetric, WeightedUtxo},
+    utils::{
+        calculate_change, calculate_fee, calculate_selection_fee, calculate_waste_detailed,
+        effective_value, exceeds_max_inputs, exceeds_max_tx_weight, validate_feerate,
+        validate_target,
+    },
+};
+use alloc::{vec, vec::Vec};
+use core::cmp::Reverse;
+
+/// Upper bound on the number of nodes the search visits.
+const MIN_CHANGE_TOTAL_TRIES: u32 = 100_000;
+
+/// Performs coin selection minimizing the change amount.
+///
+/// The change of a selection is its value minus the target, the estimated fee and the fee for the change output,
+/// where the estimated fee is the fee for the base weight and the selected inputs, raised to `min_absolute_fee`. The search looks for the selection
+/// with the smallest change that is still at least `min_change_value`, to avoid creating large change outputs.
+/// The inputs are explored in descending order of effective value, and selections spending more than `max_inputs`
+/// inputs or heavier than `max_tx_weight` allows are pruned.
+///
+/// The change amount is returned in the `change_value` of the selection, when a change output is created.
+///
+/// Returns `InsufficientFunds` if the inputs can not cover the target plus the minimum change, `NoSolutionFound`
+/// if no selection is found within the search budget, and `ArithmeticOverflow` if the values of the inputs or the
+/// target do not sum within a `u64`, see [`validate_target`].
+pub fn select_coin_min_change<T: WeightedUtxo>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+) -> Result<SelectionOutput, SelectionError> {
+    validate_feerate(*options.target_feerate)?;
+    validate_target(inputs, options)?;
+    let target = options
+        .target_value
+        .checked_add(options.min_change_value)
+        .ok_or(SelectionError::ArithmeticOverflow)?;
+    let change_fee = calculate_fee(options.change_weight, *options.target_feerate);
+
+    let mut sorted_inputs: Vec<(usize, u64)> = inputs
+        .iter()
+        .enumerate()
+        .map(|(index, input)| (index, effective_value(input, *options.target_feerate)))
+        .filter(|&(_, value)| value > 0)
+        .collect();
+    sorted_inputs.sort_by_key(|&(_, value)| Reverse(value));
+
+    let len = sorted_inputs.len();
+    let mut remaining_value = vec![0u64; len + 1];
+    for pos in (0..len).rev() {
+        remaining_value[pos] = remaining_value[pos + 1]
+            .checked_add(sorted_inputs[pos].1)
+            .ok_or(SelectionError::ArithmeticOverflow)?;
+    }
+
+    if remaining_value[0] < target {
+        return Err(SelectionError::InsufficientFunds);
+    }
+
+    // The selection holds positions in `sorted_inputs`.
+    let mut selection: Vec<usize> = Vec::new();
+    let (mut acc_eff_value, mut acc_value, mut acc_weight) = (0u64, 0u64, 0u64);
+    let mut acc_count: usize = 0;
+    let mut best: Option<(Vec<usize>, u64)> = None;
+    let mut next: usize = 0;
+    let mut tries: u32 = 0;
+
+    loop {
+        let mut backtrack = true;
+        if next < len && tries < MIN_CHANGE_TOTAL_TRIES {
+            tries += 1;
+            let (index, value) = sorted_inputs[next];
+            selection.push(next);
+            acc_eff_value = acc_eff_value
+                .checked_add(value)
+                .ok_or(SelectionError::ArithmeticOverflow)?;
+            acc_value = acc_value
+                .checked_add(inputs[index].value())
+                .ok_or(SelectionError::ArithmeticOverflow)?;
+            acc_weight = acc_weight
+                .checked_add(inputs[index].weight())
+                .ok_or(SelectionError::ArithmeticOverflow)?;
+            acc_count += inputs[index].input_count();
+            next += 1;
+
+            let estimated_fees =
+                calculate_selection_fee(options, acc_weight).max(options.min_absolute_fee);
+            let change = acc_value.checked_sub(options.target_value + estimated_fees + change_fee);
+            if exceeds_max_inputs(options, acc_count) || exceeds_max_tx_weight(options, acc_weight)
+            {
+                // Too many inputs or too heavy already
+            } else if let Some(change) = change.filter(|&change| change >= options.min_change_value)
+            {
+                // Adding more inputs only increases the change, record the candidate and backtrack.
+                if best
+                    .as_ref()
+                    .is_none_or(|(_, best_change)| change < *best_change)
+                {
+                    best = Some((selection.clone(), change));
+                }
+            } else if acc_eff_value + remaining_value[next] >= target {
+                backtrack = false;
+            }
+        } else if tries >= MIN_CHANGE_TOTAL_TRIES {
+            break;
+        }
+
+        if backtrack {
+            match selection.pop() {
+                Some(last) => {
+                    let (index, value) = sorted_inputs[last];
+                    acc_eff_value -= value;
+                    acc_value -= inputs[index].value();
+                    acc_weight -= inputs[index].weight();
+                    acc_count -= inputs[index].input_count();
+                    next = last + 1;
+                }
+                None => break,
+            }
+        }
+    }
+
+    let (best_selection, _) = best.ok_or(SelectionError::NoSolutionFound)?;
+    let mut selected_inputs: Vec<usize> = best_selection
+        .iter()
+        .map(|&pos| sorted_inputs[pos].0)
+        .collect();
+    let accumulated_value = selected_inputs
+        .iter()
+        .try_fold(0u64, |total, &i| total.checked_add(inputs[i].value()))
+        .ok_or(SelectionError::ArithmeticOverflow)?;
+    let accumulated_weight = selected_inputs
+        .iter()
+        .try_fold(0u64, |total, &i| total.checked_add(inputs[i].weight()))
+        .ok_or(SelectionError::ArithmeticOverflow)?;
+    let estimated_fees = calculate_selection_fee(options, accumulated_weight);
+    let waste_breakdown = calculate_waste_detailed(
+        options,
+        accumulated_value,
+        accumulated_weight,
+        estimated_fees,
+    );
+    selected_inputs.sort_unstable();
+    Ok(SelectionOutput {
+        selected_inputs,
+        waste: WasteMetric(waste_breakdown.total()),
+        waste_breakdown,
+        change_value: calculate_change(options, accumulated_value, estimated_fees),
+        estimated_fee: estimated_fees,
+        selected_value: accumulated_value,
+        selected_weight: accumulated_weight,
+    })
+}
+
+#[cfg(test)]
+mod test {
+
+    use crate::{
+        algorithms::minchange::select_coin_min_change,
+        types::{CoinSelectionOpt, ExcessStrategy, FeeRate, OutputGroup, SelectionError},
+    };
+    #[cfg(feature = "std")]
+    use crate::{
+        algorithms::{fifo::select_coin_fifo, srd::select_coin_srd},
+        utils::{calculate_fee, calculate_selection_fee},
+    };
+
+    fn setup_output_groups() -> Vec<OutputGroup> {
+        [9000, 7000, 6100, 5000, 4300, 3000, 2600, 1900, 1500, 1200]
+            .iter()
+            .enumerate()
+            .map(|(sequence, &value)| OutputGroup {
+                value,
+                weight: 100,
+                input_count: 1,
+                creation_sequence: Some(sequence as u32),
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            })
+            .collect()
+    }
+
+    fn setup_options(target_value: u64) -> CoinSelectionOpt {
+        CoinSelectionOpt {
+            target_value,
+            target_feerate: FeeRate::from_sat_per_wu(1.0),
+            long_term_feerate: Some(FeeRate::from_sat_per_wu(0.5)),
+            min_absolute_fee: 0,
+            base_weight: 10,
+            change_weight: 50,
+            change_cost: 10,
+            avg_input_weight: 20,
+            avg_output_weight: 10,
+            min_change_value: 300,
+            excess_strategy: ExcessStrategy::ToChange,
+            max_inputs: None,
+            bnb_tries: None,
+            include_uneconomical: false,
+            max_excess: None,
+            max_tx_weight: None,
+            knapsack_iterations: None,
+            knapsack_stagnation: None,
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn change(
+        inputs: &[OutputGroup],
+        selected_inputs: &[usize],
+        options: &CoinSelectionOpt,
+    ) -> u64 {
+        let value: u64 = selected_inputs.iter().map(|&i| inputs[i].value).sum();
+        let weight: u64 = selected_inputs.iter().map(|&i| inputs[i].weight).sum();
+        value
+            - options.target_value
+            - calculate_selection_fee(options, weight)
+            - calculate_fee(options.change_weight, *options.target_feerate)
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_min_change_beats_fifo_and_srd() {
+        let inputs = setup_output_groups();
+        let options = setup_options(9940);
+        let result = select_coin_min_change(&inputs, &options).unwrap();
+        let min_change = result.change_value.unwrap();
+        assert_eq!(
+            change(&inputs, &result.selected_inputs, &options),
+            min_change
+        );
+        assert!(min_change >= options.min_change_value);
+        // 9000 + 1500, less 210 of fees for the inputs and the base weight and 50 for the change output, leaves
+        // exactly the minimum change
+        assert_eq!(min_change, 300);
+
+        let fifo = select_coin_fifo(&inputs, &options).unwrap();
+        assert!(min_change < change(&inputs, &fifo.selected_inputs, &options));
+
+        let mut srd_changes = Vec::new();
+        for _ in 0..20 {
+            let srd = select_coin_srd(&inputs, &options).unwrap();
+            // Draws paying the target without a change output are not compared
+            if srd.change_value.is_some() {
+                srd_changes.push(change(&inputs, &srd.selected_inputs, &options));
+            }
+        }
+        assert!(srd_changes
+            .iter()
+            .all(|&srd_change| min_change <= srd_change));
+        assert!(srd_changes
+            .iter()
+            .any(|&srd_change| min_change < srd_change));
+    }
+
+    #[test]
+    fn test_min_change_insufficient_funds() {
+        let inputs = setup_output_groups();
+        let result = select_coin_min_change(&inputs, &setup_options(42_000));
+        assert!(matches!(result, Err(SelectionError::InsufficientFunds)));
+    }
+}
@@ -0,0 +1,279 @@
+use crate::{
+    types::{CoinSelectionOpt, SelectionError, SelectionOutput, WasteMetric, WeightedUtxo},
+    utils::{
+        calculate_bump_fee, calculate_change, calculate_selection_fee, calculate_waste_detailed,
+        effective_value, exceeds_max_inputs, exceeds_max_tx_weight, funds_change, validate_feerate,
+        validate_target, within_changeless_window,
+    },
+};
+use alloc::{vec, vec::Vec};
+
+/// Upper bound on the number of nodes the search visits.
+const MIN_INPUTS_TOTAL_TRIES: u32 = 100_000;
+
+/// Performs coin selection minimizing the number of spent UTXOs.
+///
+/// The number of inputs is the sum of the `input_count` of the selected groups, not the number of groups.
+/// The groups are explored in descending order of effective value, so the first candidates found are the greedy
+/// largest-first selections, which are then improved upon. Among selections with the same number of inputs,
+/// the lighter one wins.
+///
+/// A selection is complete once it pays the target, the fee and a change output, see [`funds_change`], or the target
+/// and the fee without a change output, see [`within_changeless_window`]. The fee covers the `base_weight` and the
+/// selected inputs, plus the fee lifting their unconfirmed ancestors to the `target_feerate`.
+///
+/// Returns `NoSolutionFound` if no selection is complete, or if the smallest one found still spends more than
+/// `max_inputs` inputs. Selections going over `max_tx_weight` are pruned. Returns `ArithmeticOverflow` if the values
+/// of the inputs or the target do not sum within a `u64`, see [`validate_target`].
+pub fn select_coin_min_inputs<T: WeightedUtxo>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+) -> Result<SelectionOutput, SelectionError> {
+    validate_feerate(*options.target_feerate)?;
+    validate_target(inputs, options)?;
+
+    // (index, effective value, input count, weight)
+    let mut sorted_inputs: Vec<(usize, u64, usize, u64)> = inputs
+        .iter()
+        .enumerate()
+        .map(|(index, input)| {
+            (
+                index,
+                effective_value(input, *options.target_feerate),
+                input.input_count(),
+                input.weight(),
+            )
+        })
+        .filter(|&(_, value, _, _)| value > 0)
+        .collect();
+    sorted_inputs.sort_by(|a, b| b.1.cmp(&a.1).then(a.2.cmp(&b.2)));
+
+    // Lookahead table: sum of the values from a position onwards.
+    let len = sorted_inputs.len();
+    let mut remaining_value = vec![0u64; len + 1];
+    for pos in (0..len).rev() {
+        remaining_value[pos] = remaining_value[pos + 1]
+            .checked_add(inputs[sorted_inputs[pos].0].value())
+            .ok_or(SelectionError::ArithmeticOverflow)?;
+    }
+
+    let min_target = options
+        .target_value
+        .checked_add(calculate_selection_fee(options, 0).max(options.min_absolute_fee))
+        .ok_or(SelectionError::ArithmeticOverflow)?;
+    if remaining_value[0] < min_target {
+        return Err(SelectionError::NoSolutionFound);
+    }
+
+    // The selection holds positions in `sorted_inputs`.
+    let mut selection: Vec<usize> = Vec::new();
+    let (mut acc_value, mut acc_count, mut acc_weight) = (0u64, 0usize, 0u64);
+    let mut acc_bump_fee: u64 = 0;
+    let mut best_selection: Vec<usize> = Vec::new();
+    let (mut best_count, mut best_weight) = (usize::MAX, u64::MAX);
+    let mut next: usize = 0;
+    let mut tries: u32 = 0;
+
+    loop {
+        let mut backtrack = true;
+        if next < len && tries < MIN_INPUTS_TOTAL_TRIES {
+            tries += 1;
+            let (index, _, count, weight) = sorted_inputs[next];
+            selection.push(next);
+            acc_value = acc_value
+                .checked_add(inputs[index].value())
+                .ok_or(SelectionError::ArithmeticOverflow)?;
+            acc_count += count;
+            acc_weight = acc_weight
+                .checked_add(weight)
+                .ok_or(SelectionError::ArithmeticOverflow)?;
+            acc_bump_fee += calculate_bump_fee(&inputs[index], *options.target_feerate);
+            next += 1;
+            let estimated_fees = calculate_selection_fee(options, acc_weight) + acc_bump_fee;
+
+            if acc_count > best_count
+                || (acc_count == best_count && acc_weight >= best_weight)
+                || exceeds_max_tx_weight(options, acc_weight)
+            {
+                // Can not beat the best candidate anymore, or too heavy already
+            } else if funds_change(options, acc_value, estimated_fees)
+                || within_changeless_window(options, acc_value, estimated_fees)
+            {
+                best_selection.clone_from(&selection);
+                best_count = acc_count;
+                best_weight = acc_weight;
+            } else if acc_value + remaining_value[next]
+                >= options.target_value + estimated_fees.max(options.min_absolute_fee)
+            {
+                // The fee only grows with the inputs added, the value left may still pay for it
+                backtrack = false;
+            }
+        } else if tries >= MIN_INPUTS_TOTAL_TRIES {
+            break;
+        }
+
+        if backtrack {
+            match selection.pop() {
+                Some(last) => {
+                    let (index, _, count, weight) = sorted_inputs[last];
+                    acc_value -= inputs[index].value();
+                    acc_count -= count;
+                    acc_weight -= weight;
+                    acc_bump_fee -= calculate_bump_fee(&inputs[index], *options.target_feerate);
+                    next = last + 1;
+                }
+                None => break,
+            }
+        }
+    }
+
+    if best_selection.is_empty() || exceeds_max_inputs(options, best_count) {
+        return Err(SelectionError::NoSolutionFound);
+    }
+
+    let mut selected_inputs: Vec<usize> = best_selection
+        .iter()
+        .map(|&pos| sorted_inputs[pos].0)
+        .collect();
+    let accumulated_value = selected_inputs
+        .iter()
+        .try_fold(0u64, |total, &i| total.checked_add(inputs[i].value()))
+        .ok_or(SelectionError::ArithmeticOverflow)?;
+    let bump_fees: u64 = selected_inputs
+        .iter()
+        .map(|&i| calculate_bump_fee(&inputs[i], *options.target_feerate))
+        .sum();
+    let estimated_fees = calculate_selection_fee(options, best_weight) + bump_fees;
+    let mut waste_breakdown =
+        calculate_waste_detailed(options, accumulated_value, best_weight, estimated_fees);
+    // Lifting the unconfirmed ancestors to the target feerate is paid only because the inputs are spent now
+    waste_breakdown.feerate_diff += bump_fees as i64;
+    selected_inputs.sort_unstable();
+    Ok(SelectionOutput {
+        selected_inputs,
+        waste: WasteMetric(waste_breakdown.total()),
+        waste_breakdown,
+        change_value: calculate_change(options, accumulated_value, estimated_fees),
+        estimated_fee: estimated_fees,
+        selected_value: accumulated_value,
+        selected_weight: best_weight,
+    })
+}
+
+#[cfg(test)]
+mod test {
+
+    use crate::{
+        algorithms::{largestfirst::select_coin_largestfirst, mininputs::select_coin_min_inputs},
+        types::{CoinSelectionOpt, ExcessStrategy, FeeRate, OutputGroup, SelectionError},
+    };
+
+    fn setup_mininputs_output_groups() -> Vec<OutputGroup> {
+        vec![
+            // A group of four UTXOs belonging to the same address
+            OutputGroup {
+                value: 8000,
+                weight: 400,
+                input_count: 4,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            },
+            OutputGroup {
+                value: 4000,
+                weight: 100,
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            },
+            OutputGroup {
+                value: 3500,
+                weight: 100,
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            },
+            OutputGroup {
+                value: 1000,
+                weight: 100,
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            },
+        ]
+    }
+
+    fn setup_options(target_value: u64) -> CoinSelectionOpt {
+        CoinSelectionOpt {
+            target_value,
+            target_feerate: FeeRate::from_sat_per_wu(0.4), // Simplified feerate
+            long_term_feerate: Some(FeeRate::from_sat_per_wu(0.4)),
+            min_absolute_fee: 0,
+            base_weight: 10,
+            change_weight: 50,
+            change_cost: 10,
+            avg_input_weight: 20,
+            avg_output_weight: 10,
+            min_change_value: 500,
+            excess_strategy: ExcessStrategy::ToChange,
+            max_inputs: None,
+            bnb_tries: None,
+            include_uneconomical: false,
+            max_excess: None,
+            max_tx_weight: None,
+            knapsack_iterations: None,
+            knapsack_stagnation: None,
+        }
+    }
+
+    fn total_input_count(inputs: &[OutputGroup], selected_inputs: &[usize]) -> usize {
+        selected_inputs.iter().map(|&i| inputs[i].input_count).sum()
+    }
+
+    #[test]
+    fn test_min_inputs_counts_utxos_in_groups() {
+        let inputs = setup_mininputs_output_groups();
+        let options = setup_options(6000);
+        let result = select_coin_min_inputs(&inputs, &options).unwrap();
+        let mut selected_inputs = result.selected_inputs.clone();
+        selected_inputs.sort();
+        // The two single UTXOs beat the group of four, even though the group is the largest
+        assert_eq!(selected_inputs, vec![1, 2]);
+        assert_eq!(total_input_count(&inputs, &result.selected_inputs), 2);
+
+        let largestfirst = select_coin_largestfirst(&inputs, &options).unwrap();
+        assert!(total_input_count(&inputs, &largestfirst.selected_inputs) > 2);
+    }
+
+    #[test]
+    fn test_min_inputs_pays_base_weight() {
+        let inputs = setup_mininputs_output_groups();
+        // The three single UTXOs cover the target and the minimum change over their own fee, not over the fee for
+        // the base weight too
+        let options = CoinSelectionOpt {
+            base_weight: 2000,
+            ..setup_options(7400)
+        };
+        let result = select_coin_min_inputs(&inputs, &options).unwrap();
+        assert_eq!(result.selected_inputs, vec![0, 1]);
+        assert!(
+            result.selected_value
+                >= options.target_value + options.min_change_value + result.estimated_fee
+        );
+    }
+
+    #[test]
+    fn test_min_inputs_no_solution() {
+        let inputs = setup_mininputs_output_groups();
+        let options = setup_options(20000);
+        let result = select_coin_min_inputs(&inputs, &options);
+        assert!(matches!(result, Err(SelectionError::NoSolutionFound)));
+    }
+}
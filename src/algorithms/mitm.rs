@@ -0,0 +1,228 @@
+use crate::{
+    algorithms::bnb::match_window,
+    types::{CoinSelectionOpt, SelectionError, SelectionOutput, WasteMetric, WeightedUtxo},
+    utils::{
+        calculate_change, calculate_selection_fee, calculate_waste_detailed, effective_value,
+        exceeds_max_inputs, exceeds_max_tx_weight, validate_feerate, validate_target,
+    },
+};
+use alloc::{vec, vec::Vec};
+
+/// Largest number of inputs with a positive effective value the meet-in-the-middle search accepts.
+///
+/// Each half of the pool enumerates up to `2^(MITM_MAX_INPUTS / 2)` subset sums.
+pub const MITM_MAX_INPUTS: usize = 40;
+
+/// Performs coin selection by meet-in-the-middle search for a changeless match.
+///
+/// The inputs are split in two halves and the effective value of every subset of each half is enumerated.
+/// One side is sorted, and for every subset sum of the other side a binary search looks for a complement
+/// landing inside the same match window as [`select_coin_bnb`](crate::algorithms::bnb::select_coin_bnb).
+/// Unlike the capped BnB search, a changeless match is always found when one exists. Among all matches
+/// the one with the lowest effective value is returned.
+///
+/// Returns `NoSolutionFound` if there is no match, if the lowest match spends more than `max_inputs` inputs or goes
+/// over `max_tx_weight`, or if more than [`MITM_MAX_INPUTS`] inputs have a positive effective value. Returns
+/// `ArithmeticOverflow` if the values of the inputs or the target do not sum within a `u64`, see [`validate_target`].
+pub fn select_coin_mitm<T: WeightedUtxo>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+) -> Result<SelectionOutput, SelectionError> {
+    validate_feerate(*options.target_feerate)?;
+    validate_target(inputs, options)?;
+    let (target_for_match, match_range) = match_window(options)?;
+
+    // Inputs with zero effective value can never help reaching the target.
+    let candidates: Vec<(usize, u64)> = inputs
+        .iter()
+        .enumerate()
+        .map(|(index, input)| (index, effective_value(input, *options.target_feerate)))
+        .filter(|&(_, value)| value > 0)
+        .collect();
+    if candidates.len() > MITM_MAX_INPUTS {
+        return Err(SelectionError::NoSolutionFound);
+    }
+
+    let (left, right) = candidates.split_at(candidates.len() / 2);
+    let left_sums = subset_sums(left)?;
+    let mut right_sums = subset_sums(right)?;
+    right_sums.sort_unstable();
+
+    // Lowest matching effective value, with the subsets of both halves reaching it
+    let mut best: Option<(u64, u32, u32)> = None;
+    for &(left_sum, left_mask) in &left_sums {
+        if left_sum > target_for_match + match_range {
+            continue;
+        }
+        let lower = target_for_match.saturating_sub(left_sum);
+        let pos = right_sums.partition_point(|&(sum, _)| sum < lower);
+        if let Some(&(right_sum, right_mask)) = right_sums.get(pos) {
+            let total = left_sum
+                .checked_add(right_sum)
+                .ok_or(SelectionError::ArithmeticOverflow)?;
+            if total <= target_for_match + match_range
+                && best.is_none_or(|(best_total, _, _)| total < best_total)
+            {
+                best = Some((total, left_mask, right_mask));
+            }
+        }
+    }
+
+    let (_, left_mask, right_mask) = best.ok_or(SelectionError::NoSolutionFound)?;
+    let mut selected_inputs: Vec<usize> = masked_indices(left, left_mask)
+        .chain(masked_indices(right, right_mask))
+        .collect();
+    let input_count: usize = selected_inputs
+        .iter()
+        .map(|&i| inputs[i].input_count())
+        .sum();
+    let accumulated_weight = selected_inputs
+        .iter()
+        .try_fold(0u64, |total, &i| total.checked_add(inputs[i].weight()))
+        .ok_or(SelectionError::ArithmeticOverflow)?;
+    if exceeds_max_inputs(options, input_count)
+        || exceeds_max_tx_weight(options, accumulated_weight)
+    {
+        return Err(SelectionError::NoSolutionFound);
+    }
+    let accumulated_value = selected_inputs
+        .iter()
+        .try_fold(0u64, |total, &i| total.checked_add(inputs[i].value()))
+        .ok_or(SelectionError::ArithmeticOverflow)?;
+    let estimated_fee = calculate_selection_fee(options, accumulated_weight);
+    let waste_breakdown = calculate_waste_detailed(
+        options,
+        accumulated_value,
+        accumulated_weight,
+        estimated_fee,
+    );
+    selected_inputs.sort_unstable();
+    Ok(SelectionOutput {
+        selected_inputs,
+        waste: WasteMetric(waste_breakdown.total()),
+        waste_breakdown,
+        change_value: calculate_change(options, accumulated_value, estimated_fee),
+        estimated_fee,
+        selected_value: accumulated_value,
+        selected_weight: accumulated_weight,
+    })
+}
+
+/// Enumerates the effective value of every subset of `half`, along with the bitmask of the subset.
+///
+/// Returns `ArithmeticOverflow` if the effective values of a subset do not sum within a `u64`.
+fn subset_sums(half: &[(usize, u64)]) -> Result<Vec<(u64, u32)>, SelectionError> {
+    let mut sums = vec![(0u64, 0u32)];
+    for (bit, &(_, value)) in half.iter().enumerate() {
+        let extended = sums
+            .iter()
+            .map(|&(sum, mask)| Some((sum.checked_add(value)?, mask | (1 << bit))))
+            .collect::<Option<Vec<(u64, u32)>>>()
+            .ok_or(SelectionError::ArithmeticOverflow)?;
+        sums.extend(extended);
+    }
+    Ok(sums)
+}
+
+fn masked_indices(half: &[(usize, u64)], mask: u32) -> impl Iterator<Item = usize> + '_ {
+    half.iter()
+        .enumerate()
+        .filter(move |(bit, _)| mask & (1 << bit) != 0)
+        .map(|(_, &(index, _))| index)
+}
+
+#[cfg(test)]
+mod test {
+
+    use crate::{
+        algorithms::{bnb::select_coin_bnb, mitm::select_coin_mitm},
+        types::{CoinSelectionOpt, ExcessStrategy, FeeRate, OutputGroup, SelectionError},
+    };
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    fn setup_output_groups(values: &[u64]) -> Vec<OutputGroup> {
+        values
+            .iter()
+            .map(|&value| OutputGroup {
+                value,
+                weight: 0,
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            })
+            .collect()
+    }
+
+    fn setup_options(target_value: u64) -> CoinSelectionOpt {
+        CoinSelectionOpt {
+            target_value,
+            target_feerate: FeeRate::from_sat_per_wu(1.0),
+            long_term_feerate: Some(FeeRate::from_sat_per_wu(1.0)),
+            min_absolute_fee: 0,
+            base_weight: 0,
+            change_weight: 0,
+            change_cost: 0,
+            avg_input_weight: 0,
+            avg_output_weight: 0,
+            min_change_value: 0,
+            excess_strategy: ExcessStrategy::ToFee,
+            max_inputs: None,
+            bnb_tries: None,
+            include_uneconomical: false,
+            max_excess: None,
+            max_tx_weight: None,
+            knapsack_iterations: None,
+            knapsack_stagnation: None,
+        }
+    }
+
+    fn selected_value(inputs: &[OutputGroup], selected_inputs: &[usize]) -> u64 {
+        selected_inputs.iter().map(|&i| inputs[i].value).sum()
+    }
+
+    #[test]
+    fn test_mitm_finds_exact_match_missed_by_bnb() {
+        // 36 distinct coins, the target is the value of every other one and nothing may be overpaid
+        let mut rng = StdRng::seed_from_u64(42);
+        let values: Vec<u64> = (0..36)
+            .map(|_| rng.gen_range(1_000_000..2_000_000))
+            .collect();
+        let target: u64 = values.iter().step_by(2).sum();
+        let inputs = setup_output_groups(&values);
+        let options = setup_options(target);
+
+        assert!(matches!(
+            select_coin_bnb(&inputs, &options),
+            Err(SelectionError::NoSolutionFound)
+        ));
+        let result = select_coin_mitm(&inputs, &options).unwrap();
+        assert_eq!(selected_value(&inputs, &result.selected_inputs), target);
+    }
+
+    #[test]
+    fn test_mitm_prefers_lowest_match() {
+        let inputs = setup_output_groups(&[3000, 5000, 2000, 4000, 1200]);
+        let mut options = setup_options(6000);
+        options.change_cost = 300;
+        // Window is [6000, 6300], 6200 = 5000 + 1200 and 6000 = 4000 + 2000 both match
+        let result = select_coin_mitm(&inputs, &options).unwrap();
+        let mut selected_inputs = result.selected_inputs.clone();
+        selected_inputs.sort();
+        assert_eq!(selected_inputs, vec![2, 3]);
+    }
+
+    #[test]
+    fn test_mitm_no_solution() {
+        let inputs = setup_output_groups(&[3000, 5000, 2000]);
+        // 4000 is unreachable without overpaying
+        let result = select_coin_mitm(&inputs, &setup_options(4000));
+        assert!(matches!(result, Err(SelectionError::NoSolutionFound)));
+
+        // Past the size limit the search is not attempted
+        let inputs = setup_output_groups(&[1000; 41]);
+        let result = select_coin_mitm(&inputs, &setup_options(1000));
+        assert!(matches!(result, Err(SelectionError::NoSolutionFound)));
+    }
+}
@@ -1,5 +1,20 @@
+pub mod annealing;
+pub mod bestfit;
 pub mod bnb;
+pub mod coingrinder;
+pub mod consolidate;
+pub mod dp;
+pub mod exhaustive;
 pub mod fifo;
+pub mod fixedcount;
+pub mod greedywaste;
 pub mod knapsack;
+pub mod largestfirst;
 pub mod lowestlarger;
+pub mod minchange;
+pub mod mininputs;
+pub mod mitm;
+pub mod randomimprove;
+pub mod smallestfirst;
 pub mod srd;
+pub mod weighted_random;
@@ -0,0 +1,281 @@
+use crate::{
+    types::{CoinSelectionOpt, SelectionError, SelectionOutput, WasteMetric, WeightedUtxo},
+    utils::{
+        calculate_change, calculate_selection_fee, calculate_waste_detailed, exceeds_max_inputs,
+        exceeds_max_tx_weight, validate_feerate, validate_target,
+    },
+};
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use rand::thread_rng;
+use rand::{seq::SliceRandom, Rng};
+
+/// Performs coin selection using the Random-Improve algorithm from Cardano's CIP-2.
+///
+/// Inputs are first drawn at random until the target, the fee and `min_change_value` are covered.
+/// The remaining inputs are then visited in random order and added whenever they move the change closer
+/// to the ideal change, which equals the target value, without exceeding three times the target.
+/// Change outputs of a similar size as the payment make it harder to tell them apart. The improvement never takes
+/// the selection over `max_inputs` inputs or `max_tx_weight`.
+///
+/// Returns `InsufficientFunds` if the inputs can not cover the target, `NoSolutionFound` if the random draw
+/// takes more than `max_inputs` inputs, or more than `max_tx_weight` allows, to cover it, and `ArithmeticOverflow`
+/// if the values of the drawn inputs or the target do not sum within a `u64`, see [`validate_target`].
+#[cfg(feature = "std")]
+pub fn select_coin_randomimprove<T: WeightedUtxo>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+) -> Result<SelectionOutput, SelectionError> {
+    select_coin_randomimprove_with_rng(inputs, options, &mut thread_rng())
+}
+
+/// Running state of the Random-Improve selection.
+struct Selection {
+    selected_inputs: Vec<usize>,
+    accumulated_value: u64,
+    accumulated_weight: u64,
+    input_count: usize,
+}
+
+impl Selection {
+    fn fee(&self, options: &CoinSelectionOpt) -> u64 {
+        calculate_selection_fee(options, self.accumulated_weight).max(options.min_absolute_fee)
+    }
+
+    /// Distance between the change of the selection and the ideal change.
+    fn change_distance(&self, options: &CoinSelectionOpt) -> u64 {
+        let change = self
+            .accumulated_value
+            .saturating_sub(options.target_value + self.fee(options));
+        change.abs_diff(options.target_value)
+    }
+}
+
+/// Performs coin selection using the Random-Improve algorithm, drawing from `rng`, see
+/// [`select_coin_randomimprove`].
+pub fn select_coin_randomimprove_with_rng<T: WeightedUtxo, R: Rng>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+    rng: &mut R,
+) -> Result<SelectionOutput, SelectionError> {
+    validate_feerate(*options.target_feerate)?;
+    validate_target(inputs, options)?;
+    let mut randomized_inputs: Vec<usize> = (0..inputs.len()).collect();
+    randomized_inputs.shuffle(rng);
+
+    let mut selection = random_select(inputs, options, &mut randomized_inputs)?;
+    randomized_inputs.shuffle(rng);
+    improve(inputs, options, &mut selection, &randomized_inputs);
+
+    let estimated_fees = calculate_selection_fee(options, selection.accumulated_weight);
+    let waste_breakdown = calculate_waste_detailed(
+        options,
+        selection.accumulated_value,
+        selection.accumulated_weight,
+        estimated_fees,
+    );
+    selection.selected_inputs.sort_unstable();
+    Ok(SelectionOutput {
+        selected_inputs: selection.selected_inputs,
+        waste: WasteMetric(waste_breakdown.total()),
+        waste_breakdown,
+        change_value: calculate_change(options, selection.accumulated_value, estimated_fees),
+        estimated_fee: estimated_fees,
+        selected_value: selection.accumulated_value,
+        selected_weight: selection.accumulated_weight,
+    })
+}
+
+/// Draws the inputs in the given order until the target is covered, leaving the undrawn ones in `randomized_inputs`.
+fn random_select<T: WeightedUtxo>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+    randomized_inputs: &mut Vec<usize>,
+) -> Result<Selection, SelectionError> {
+    let mut selection = Selection {
+        selected_inputs: Vec::new(),
+        accumulated_value: 0,
+        accumulated_weight: 0,
+        input_count: 0,
+    };
+    for (drawn, &index) in randomized_inputs.iter().enumerate() {
+        selection.input_count += inputs[index].input_count();
+        if exceeds_max_inputs(options, selection.input_count)
+            || exceeds_max_tx_weight(
+                options,
+                selection.accumulated_weight + inputs[index].weight(),
+            )
+        {
+            return Err(SelectionError::NoSolutionFound);
+        }
+        selection.selected_inputs.push(index);
+        selection.accumulated_value = selection
+            .accumulated_value
+            .checked_add(inputs[index].value())
+            .ok_or(SelectionError::ArithmeticOverflow)?;
+        selection.accumulated_weight = selection
+            .accumulated_weight
+            .checked_add(inputs[index].weight())
+            .ok_or(SelectionError::ArithmeticOverflow)?;
+        let required_value = options
+            .target_value
+            .checked_add(selection.fee(options))
+            .and_then(|value| value.checked_add(options.min_change_value))
+            .ok_or(SelectionError::ArithmeticOverflow)?;
+        if selection.accumulated_value >= required_value {
+            randomized_inputs.drain(..=drawn);
+            return Ok(selection);
+        }
+    }
+    Err(SelectionError::InsufficientFunds)
+}
+
+/// Adds the remaining inputs which bring the change closer to the ideal change.
+fn improve<T: WeightedUtxo>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+    selection: &mut Selection,
+    remaining_inputs: &[usize],
+) {
+    let maximum_value = options.target_value.saturating_mul(3);
+    for &index in remaining_inputs {
+        // A value beyond a `u64` is beyond the maximum value as well
+        let Some(accumulated_value) = selection
+            .accumulated_value
+            .checked_add(inputs[index].value())
+        else {
+            continue;
+        };
+        let candidate = Selection {
+            selected_inputs: Vec::new(),
+            accumulated_value,
+            accumulated_weight: selection.accumulated_weight + inputs[index].weight(),
+            input_count: selection.input_count + inputs[index].input_count(),
+        };
+        if candidate.accumulated_value <= maximum_value
+            && !exceeds_max_inputs(options, candidate.input_count)
+            && !exceeds_max_tx_weight(options, candidate.accumulated_weight)
+            && candidate.change_distance(options) < selection.change_distance(options)
+        {
+            selection.selected_inputs.push(index);
+            selection.accumulated_value = candidate.accumulated_value;
+            selection.accumulated_weight = candidate.accumulated_weight;
+            selection.input_count = candidate.input_count;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::{improve, random_select};
+    use crate::types::{CoinSelectionOpt, ExcessStrategy, FeeRate, OutputGroup};
+    #[cfg(feature = "std")]
+    use crate::{algorithms::randomimprove::select_coin_randomimprove, types::SelectionError};
+    use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+    fn setup_randomimprove_output_groups() -> Vec<OutputGroup> {
+        (0..30)
+            .map(|_| OutputGroup {
+                value: 1000,
+                weight: 100,
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            })
+            .collect()
+    }
+
+    fn setup_options(target_value: u64) -> CoinSelectionOpt {
+        CoinSelectionOpt {
+            target_value,
+            target_feerate: FeeRate::from_sat_per_wu(0.4), // Simplified feerate
+            long_term_feerate: Some(FeeRate::from_sat_per_wu(0.4)),
+            min_absolute_fee: 0,
+            base_weight: 10,
+            change_weight: 50,
+            change_cost: 10,
+            avg_input_weight: 20,
+            avg_output_weight: 10,
+            min_change_value: 500,
+            excess_strategy: ExcessStrategy::ToChange,
+            max_inputs: None,
+            bnb_tries: None,
+            include_uneconomical: false,
+            max_excess: None,
+            max_tx_weight: None,
+            knapsack_iterations: None,
+            knapsack_stagnation: None,
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_randomimprove_successful() {
+        let inputs = setup_randomimprove_output_groups();
+        let options = setup_options(5000);
+        let result = select_coin_randomimprove(&inputs, &options).unwrap();
+        let value: u64 = result
+            .selected_inputs
+            .iter()
+            .map(|&i| inputs[i].value)
+            .sum();
+        assert!(value >= 5000 + options.min_change_value);
+        assert!(value <= 3 * 5000);
+    }
+
+    #[test]
+    fn test_randomimprove_moves_change_towards_target() {
+        let inputs = setup_randomimprove_output_groups();
+        let options = setup_options(5000);
+        for seed in 0..10 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut randomized_inputs: Vec<usize> = (0..inputs.len()).collect();
+            randomized_inputs.shuffle(&mut rng);
+            let mut selection = random_select(&inputs, &options, &mut randomized_inputs).unwrap();
+            let distance_before = selection.change_distance(&options);
+            randomized_inputs.shuffle(&mut rng);
+            improve(&inputs, &options, &mut selection, &randomized_inputs);
+            assert!(selection.change_distance(&options) < distance_before);
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_randomimprove_max_inputs() {
+        // The random draw takes six inputs, the improvement would add several more
+        let inputs = setup_randomimprove_output_groups();
+        let mut options = setup_options(5000);
+        options.max_inputs = Some(8);
+        for _ in 0..10 {
+            let result = select_coin_randomimprove(&inputs, &options).unwrap();
+            assert!((6..=8).contains(&result.selected_inputs.len()));
+        }
+
+        options.max_inputs = Some(5);
+        let result = select_coin_randomimprove(&inputs, &options);
+        assert!(matches!(result, Err(SelectionError::NoSolutionFound)));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_randomimprove_insufficient() {
+        let inputs = setup_randomimprove_output_groups();
+        let options = setup_options(40000);
+        let result = select_coin_randomimprove(&inputs, &options);
+        assert!(matches!(result, Err(SelectionError::InsufficientFunds)));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_randomimprove_weight_overflow() {
+        let mut inputs = setup_randomimprove_output_groups();
+        inputs[0].weight = u64::MAX / 2;
+        inputs[1].weight = u64::MAX / 2;
+        let options = setup_options(5000);
+        let result = select_coin_randomimprove(&inputs, &options);
+        assert!(matches!(result, Err(SelectionError::ArithmeticOverflow)));
+    }
+}
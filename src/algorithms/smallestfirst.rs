@@ -0,0 +1,222 @@
+use crate::{
+    algorithms::largestfirst::{select_in_sorted_order, ValueOrder},
+    types::{CoinSelectionOpt, SelectionError, SelectionOutput, WeightedUtxo},
+};
+
+/// Performs coin selection using the Smallest First algorithm.
+///
+/// Inputs are spent in ascending order of effective value, sweeping the small coins of the wallet first so the
+/// UTXO count shrinks over time. Unlike FIFO, the `creation_sequence` is ignored.
+///
+/// Inputs costing more to spend than they are worth are skipped, unless `include_uneconomical` is set. The selection
+/// stops once it covers the target, the fee and a change output, see [`funds_change`](crate::utils::funds_change), or
+/// as soon as it pays them without one, see [`within_changeless_window`](crate::utils::within_changeless_window).
+///
+/// The waste is computed against the `long_term_feerate`, so [`select_coin`](crate::selectcoin::select_coin)
+/// only prefers this consolidating selection when the current feerate makes it cheap.
+///
+/// Returns `InsufficientFunds` if the inputs can not cover the target, `NoSolutionFound` if covering it takes more
+/// than `max_inputs` inputs or goes over `max_tx_weight`, and `ArithmeticOverflow` if the values of the selected
+/// inputs or the target do not sum within a `u64`, see [`validate_target`](crate::utils::validate_target).
+pub fn select_coin_smallestfirst<T: WeightedUtxo>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+) -> Result<SelectionOutput, SelectionError> {
+    select_in_sorted_order(inputs, options, ValueOrder::Ascending)
+}
+
+#[cfg(test)]
+mod test {
+
+    use crate::{
+        algorithms::{
+            largestfirst::select_coin_largestfirst, smallestfirst::select_coin_smallestfirst,
+        },
+        types::{CoinSelectionOpt, ExcessStrategy, FeeRate, OutputGroup, SelectionError},
+    };
+
+    fn setup_smallestfirst_output_groups() -> Vec<OutputGroup> {
+        vec![
+            OutputGroup {
+                value: 3000,
+                weight: 300,
+                input_count: 1,
+                creation_sequence: Some(0),
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            },
+            OutputGroup {
+                value: 700,
+                weight: 100,
+                input_count: 1,
+                creation_sequence: Some(3),
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            },
+            OutputGroup {
+                value: 2000,
+                weight: 200,
+                input_count: 1,
+                creation_sequence: Some(1),
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            },
+            OutputGroup {
+                value: 900,
+                weight: 100,
+                input_count: 1,
+                creation_sequence: Some(2),
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            },
+            OutputGroup {
+                value: 30,
+                weight: 100,
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            },
+        ]
+    }
+
+    fn setup_options(target_value: u64) -> CoinSelectionOpt {
+        CoinSelectionOpt {
+            target_value,
+            target_feerate: FeeRate::from_sat_per_wu(0.4), // Simplified feerate
+            long_term_feerate: Some(FeeRate::from_sat_per_wu(0.4)),
+            min_absolute_fee: 0,
+            base_weight: 10,
+            change_weight: 50,
+            change_cost: 10,
+            avg_input_weight: 20,
+            avg_output_weight: 10,
+            min_change_value: 500,
+            excess_strategy: ExcessStrategy::ToChange,
+            max_inputs: None,
+            bnb_tries: None,
+            include_uneconomical: false,
+            max_excess: None,
+            max_tx_weight: None,
+            knapsack_iterations: None,
+            knapsack_stagnation: None,
+        }
+    }
+
+    #[test]
+    fn test_smallestfirst_successful() {
+        let inputs = setup_smallestfirst_output_groups();
+        let options = setup_options(2000);
+        let result = select_coin_smallestfirst(&inputs, &options).unwrap();
+        // The 30 sats input costs 40 sats to spend and is skipped
+        assert_eq!(result.selected_inputs, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_smallestfirst_funds_change_output() {
+        let inputs = setup_smallestfirst_output_groups();
+        // 3600 sats cover 2914 + 500 min change + 165 fee + 21 for the change output exactly
+        let options = setup_options(2914);
+        let result = select_coin_smallestfirst(&inputs, &options).unwrap();
+        assert_eq!(result.selected_inputs, vec![1, 2, 3]);
+        assert_eq!(result.estimated_fee, 165);
+
+        // One sat more and the change would fall short of the minimum once the change output is paid for
+        let options = setup_options(2915);
+        let result = select_coin_smallestfirst(&inputs, &options).unwrap();
+        assert_eq!(result.selected_inputs, vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_smallestfirst_selects_more_inputs_than_largestfirst() {
+        let inputs = setup_smallestfirst_output_groups();
+        let options = setup_options(2000);
+        let smallestfirst = select_coin_smallestfirst(&inputs, &options).unwrap();
+        let largestfirst = select_coin_largestfirst(&inputs, &options).unwrap();
+        assert!(smallestfirst.selected_inputs.len() > largestfirst.selected_inputs.len());
+    }
+
+    fn setup_dust_pool() -> Vec<OutputGroup> {
+        // Hundreds of P2WPKH outputs worth less than 1000 sats, a few of them uneconomical, and a large coin.
+        let mut inputs: Vec<OutputGroup> = (0..300)
+            .map(|i| OutputGroup {
+                value: if i % 50 == 0 { 200 } else { 600 + i },
+                weight: 272,
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            })
+            .collect();
+        inputs.push(OutputGroup {
+            value: 500_000,
+            weight: 272,
+            input_count: 1,
+            creation_sequence: None,
+            ancestor_fee: 0,
+            ancestor_weight: 0,
+            block_height: None,
+        });
+        inputs
+    }
+
+    #[test]
+    fn test_smallestfirst_consolidates_dust() {
+        let inputs = setup_dust_pool();
+        let mut options = setup_options(20_000);
+        options.target_feerate = FeeRate::from_sat_per_wu(1.0);
+        options.long_term_feerate = Some(FeeRate::from_sat_per_wu(10.0));
+        let result = select_coin_smallestfirst(&inputs, &options).unwrap();
+        assert!(result.selected_inputs.len() > 30);
+        assert!(!result.selected_inputs.contains(&300));
+        // Uneconomical outputs are never swept
+        assert!(result
+            .selected_inputs
+            .iter()
+            .all(|&i| inputs[i].value > 272));
+
+        let largestfirst = select_coin_largestfirst(&inputs, &options).unwrap();
+        assert_eq!(largestfirst.selected_inputs, vec![300]);
+        assert!(result.waste.0 <= largestfirst.waste.0);
+
+        // At a high feerate the consolidation is expensive and wastes more
+        options.target_feerate = FeeRate::from_sat_per_wu(2.0);
+        options.long_term_feerate = Some(FeeRate::from_sat_per_wu(1.0));
+        let result = select_coin_smallestfirst(&inputs, &options).unwrap();
+        let largestfirst = select_coin_largestfirst(&inputs, &options).unwrap();
+        assert!(result.waste.0 > largestfirst.waste.0);
+    }
+
+    #[test]
+    fn test_smallestfirst_dust_insufficient() {
+        let mut inputs = setup_dust_pool();
+        inputs.pop();
+        // Counted at face value the pool holds more than 200_000 sats, but spending it costs 272 sats per input
+        let mut options = setup_options(150_000);
+        options.target_feerate = FeeRate::from_sat_per_wu(1.0);
+        let result = select_coin_smallestfirst(&inputs, &options);
+        assert!(matches!(result, Err(SelectionError::InsufficientFunds)));
+    }
+
+    #[test]
+    fn test_smallestfirst_insufficient() {
+        let inputs = setup_smallestfirst_output_groups();
+        let options = setup_options(6000);
+        let result = select_coin_smallestfirst(&inputs, &options);
+        assert!(matches!(result, Err(SelectionError::InsufficientFunds)));
+    }
+
+    #[test]
+    fn test_smallestfirst_target_overflow() {
+        let inputs = setup_smallestfirst_output_groups();
+        let options = setup_options(u64::MAX);
+        let result = select_coin_smallestfirst(&inputs, &options);
+        assert!(matches!(result, Err(SelectionError::ArithmeticOverflow)));
+    }
+}
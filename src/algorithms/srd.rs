@@ -1,55 +1,303 @@
 use crate::{
-    types::{CoinSelectionOpt, OutputGroup, SelectionError, SelectionOutput, WasteMetric},
-    utils::{calculate_fee, calculate_waste},
+    types::{CoinSelectionOpt, SelectionError, SelectionOutput, WasteMetric, WeightedUtxo},
+    utils::{
+        calculate_change, calculate_selection_fee, calculate_waste_detailed, economical_inputs,
+        effective_value, exceeds_max_inputs, exceeds_max_tx_weight, funds_change, validate_feerate,
+        validate_target, within_changeless_window,
+    },
+};
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use rand::thread_rng;
+use rand::{
+    distributions::{Distribution, WeightedIndex},
+    seq::SliceRandom,
+    Rng,
 };
-use rand::{seq::SliceRandom, thread_rng};
 
 /// Performs coin selection using a single random draw.
 ///
-/// Returns `NoSolutionFound` if no solution is found.
-pub fn select_coin_srd(
-    inputs: &[OutputGroup],
+/// Like Bitcoin Core, the effective values of the drawn inputs are accumulated until they cover the target and
+/// `min_change_value`, so every input pays for its own spending fee, and their values until they also pay for the
+/// `base_weight` and the change output, see [`funds_change`]. Inputs with no effective value are skipped,
+/// unless `include_uneconomical` is set.
+/// Once the target is covered, the inputs drawn first are dropped as long as the others still cover it, so the
+/// last draw does not leave the selection with a needlessly large change.
+///
+/// Inputs that would take the transaction over `max_tx_weight` are skipped, so that lighter inputs drawn later can
+/// still complete the selection.
+///
+/// Returns `InsufficientFunds` if the inputs can not cover the target, `MaxWeightExceeded` if they could but not
+/// within `max_tx_weight`, `NoSolutionFound` if the draw takes more than `max_inputs` inputs to cover it, and
+/// `ArithmeticOverflow` if the values of the drawn inputs or the target do not sum within a `u64`, see
+/// [`validate_target`].
+#[cfg(feature = "std")]
+pub fn select_coin_srd<T: WeightedUtxo>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+) -> Result<SelectionOutput, SelectionError> {
+    select_coin_srd_with_rng(inputs, options, &mut thread_rng())
+}
+
+/// Performs coin selection using a single random draw from `rng`, see [`select_coin_srd`].
+pub fn select_coin_srd_with_rng<T: WeightedUtxo, R: Rng>(
+    inputs: &[T],
     options: &CoinSelectionOpt,
+    rng: &mut R,
 ) -> Result<SelectionOutput, SelectionError> {
+    validate_feerate(*options.target_feerate)?;
     // In out put we need to specify the indexes of the inputs in the given order
     // So keep track of the indexes when randomiz ing the vec
-    let mut randomized_inputs: Vec<_> = inputs.iter().enumerate().collect();
+    let mut randomized_inputs: Vec<usize> = economical_inputs(inputs, options);
 
     // Randomize the inputs order to simulate the random draw
-    let mut rng = thread_rng();
-    randomized_inputs.shuffle(&mut rng);
+    randomized_inputs.shuffle(rng);
+
+    let selection = select_in_order(inputs, options, randomized_inputs)?;
+    Ok(trim_selection(inputs, options, selection))
+}
+
+/// Performs coin selection keeping the best of `draws` independent random draws, see [`select_coin_srd`].
+///
+/// A single draw often lands on a wasteful selection. Every draw shuffles the inputs anew, and the feasible selection
+/// with the lowest waste is returned, the earliest one on ties. At least one draw is made.
+///
+/// Returns the error of the last draw if none of them finds a selection.
+#[cfg(feature = "std")]
+pub fn select_coin_srd_best_of<T: WeightedUtxo>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+    draws: u32,
+) -> Result<SelectionOutput, SelectionError> {
+    select_coin_srd_best_of_with_rng(inputs, options, draws, &mut thread_rng())
+}
+
+/// Performs coin selection keeping the best of `draws` random draws from `rng`, see [`select_coin_srd_best_of`].
+pub fn select_coin_srd_best_of_with_rng<T: WeightedUtxo, R: Rng>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+    draws: u32,
+    rng: &mut R,
+) -> Result<SelectionOutput, SelectionError> {
+    let mut best_result = select_coin_srd_with_rng(inputs, options, rng);
+    for _ in 1..draws {
+        let result = select_coin_srd_with_rng(inputs, options, rng);
+        best_result = match (best_result, result) {
+            (Ok(best), Ok(selection)) if selection.waste < best.waste => Ok(selection),
+            (Ok(best), _) => Ok(best),
+            (Err(_), result) => result,
+        };
+    }
+    best_result
+}
+
+/// Drops the inputs drawn first that the rest of the selection no longer needs.
+///
+/// The last input drawn can cover much more than what was missing, leaving the inputs drawn before it
+/// unnecessary. Walking the selection in draw order, each input is dropped if the remaining ones still cover the
+/// target, `min_change_value` and the fee for their own, lower, weight, see [`funds_change`].
+fn trim_selection<T: WeightedUtxo>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+    selection: SelectionOutput,
+) -> SelectionOutput {
+    let target = options.target_value + options.min_change_value;
+    let mut accumulated_eff_value: u64 = selection
+        .selected_inputs
+        .iter()
+        .map(|&i| effective_value(&inputs[i], *options.target_feerate))
+        .sum();
+    let mut accumulated_value = selection.selected_value;
+    let mut accumulated_weight = selection.selected_weight;
+    let mut selected_inputs = Vec::with_capacity(selection.selected_inputs.len());
+
+    for (position, &index) in selection.selected_inputs.iter().enumerate() {
+        let input = &inputs[index];
+        let remaining_eff_value =
+            accumulated_eff_value - effective_value(input, *options.target_feerate);
+        let remaining_value = accumulated_value - input.value();
+        let remaining_weight = accumulated_weight - input.weight();
+        let remaining_fee = calculate_selection_fee(options, remaining_weight);
+        // The last input always stays, dropping it would leave nothing to spend
+        if position + 1 < selection.selected_inputs.len()
+            && remaining_eff_value >= target
+            && funds_change(options, remaining_value, remaining_fee)
+        {
+            accumulated_eff_value = remaining_eff_value;
+            accumulated_value = remaining_value;
+            accumulated_weight = remaining_weight;
+        } else {
+            selected_inputs.push(index);
+        }
+    }
+
+    let estimated_fee = calculate_selection_fee(options, accumulated_weight);
+    let waste_breakdown = calculate_waste_detailed(
+        options,
+        accumulated_value,
+        accumulated_weight,
+        estimated_fee,
+    );
+    selected_inputs.sort_unstable();
+    SelectionOutput {
+        selected_inputs,
+        waste: WasteMetric(waste_breakdown.total()),
+        waste_breakdown,
+        change_value: calculate_change(options, accumulated_value, estimated_fee),
+        estimated_fee,
+        selected_value: accumulated_value,
+        selected_weight: accumulated_weight,
+    }
+}
+
+/// Performs coin selection using a single random draw biased towards older inputs.
+///
+/// The inputs are drawn without replacement, with a probability proportional to their age: among `n` sequenced
+/// inputs, the one with the lowest `creation_sequence` is `n` times as likely to be drawn as the one with the highest.
+/// Inputs without a `creation_sequence` are as likely to be drawn as the youngest sequenced input, so a pool without
+/// any sequence is drawn uniformly.
+///
+/// Returns `InsufficientFunds` if the inputs can not cover the target.
+#[cfg(feature = "std")]
+pub fn select_coin_srd_weighted<T: WeightedUtxo>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+) -> Result<SelectionOutput, SelectionError> {
+    select_coin_srd_weighted_with_rng(inputs, options, &mut thread_rng())
+}
+
+/// Performs coin selection using a single random draw from `rng` biased towards older inputs, see
+/// [`select_coin_srd_weighted`].
+pub fn select_coin_srd_weighted_with_rng<T: WeightedUtxo, R: Rng>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+    rng: &mut R,
+) -> Result<SelectionOutput, SelectionError> {
+    validate_feerate(*options.target_feerate)?;
+    // Ranking the distinct sequences, the oldest one gets the highest weight
+    let mut sequences: Vec<u32> = inputs
+        .iter()
+        .filter_map(|input| input.creation_sequence())
+        .collect();
+    sequences.sort_unstable();
+    sequences.dedup();
+    let weights: Vec<u64> = inputs
+        .iter()
+        .map(|input| match input.creation_sequence() {
+            Some(sequence) => {
+                (sequences.len() - sequences.partition_point(|&s| s < sequence)) as u64
+            }
+            None => 1,
+        })
+        .collect();
+
+    select_weighted(inputs, options, &weights, rng)
+}
+
+/// Draws the inputs without replacement, with a probability proportional to their weight, and accumulates them
+/// until their effective value covers the target. Inputs with a zero weight are never drawn.
+///
+/// Returns `ArithmeticOverflow` if the weights do not sum within a `u64`.
+pub(crate) fn select_weighted<T: WeightedUtxo, R: Rng>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+    weights: &[u64],
+    rng: &mut R,
+) -> Result<SelectionOutput, SelectionError> {
+    weights
+        .iter()
+        .try_fold(0u64, |total, &weight| total.checked_add(weight))
+        .ok_or(SelectionError::ArithmeticOverflow)?;
+    let mut distribution = match WeightedIndex::new(weights) {
+        Ok(distribution) => distribution,
+        Err(_) => return Err(SelectionError::InsufficientFunds),
+    };
+    let mut remaining = weights.iter().filter(|&&weight| weight > 0).count();
+    let weighted_draws = core::iter::from_fn(|| {
+        if remaining == 0 {
+            return None;
+        }
+        let index = distribution.sample(rng);
+        remaining -= 1;
+        // Drawing without replacement. Once the last input is drawn all weights are zero and the update fails.
+        if remaining > 0 {
+            distribution.update_weights(&[(index, &0)]).ok()?;
+        }
+        Some(index)
+    });
+
+    let mut selection = select_in_order(inputs, options, weighted_draws)?;
+    selection.selected_inputs.sort_unstable();
+    Ok(selection)
+}
 
-    let mut accumulated_value = 0;
+/// Accumulates the inputs in the given order until their effective value covers the target and their value funds a
+/// change output, see [`funds_change`], or until their value pays the target and the fee without a change output,
+/// see [`within_changeless_window`].
+///
+/// Inputs going over `max_tx_weight` are skipped, returning `MaxWeightExceeded` if the others do not cover the target.
+/// Returns `NoSolutionFound` as soon as the drawn inputs exceed `max_inputs`.
+pub(crate) fn select_in_order<T: WeightedUtxo>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+    order: impl IntoIterator<Item = usize>,
+) -> Result<SelectionOutput, SelectionError> {
+    validate_target(inputs, options)?;
+    let target = options.target_value + options.min_change_value;
+    let mut accumulated_eff_value = 0;
+    let mut accumulated_value: u64 = 0;
     let mut selected_inputs = Vec::new();
     let mut accumulated_weight = 0;
     let mut estimated_fee = 0;
-    let mut _input_counts = 0;
+    let mut input_counts = 0;
+    // The effective value of the inputs skipped for their weight
+    let mut skipped_eff_value = 0;
 
-    for (index, input) in randomized_inputs {
+    for index in order {
+        let input = &inputs[index];
+        let input_eff_value = effective_value(input, *options.target_feerate);
+        if input_eff_value == 0 && !options.include_uneconomical {
+            continue;
+        }
+        if exceeds_max_tx_weight(options, accumulated_weight + input.weight()) {
+            // A lighter input further in the order may still complete the selection
+            skipped_eff_value += input_eff_value;
+            continue;
+        }
+        input_counts += input.input_count();
+        if exceeds_max_inputs(options, input_counts) {
+            return Err(SelectionError::NoSolutionFound);
+        }
+        // The effective values sum to no more than the values
+        accumulated_value = accumulated_value
+            .checked_add(input.value())
+            .ok_or(SelectionError::ArithmeticOverflow)?;
+        accumulated_eff_value += input_eff_value;
+        accumulated_weight += input.weight();
         selected_inputs.push(index);
-        accumulated_value += input.value;
-        accumulated_weight += input.weight;
-        _input_counts += input.input_count;
 
-        estimated_fee = calculate_fee(accumulated_weight, options.target_feerate);
+        estimated_fee = calculate_selection_fee(options, accumulated_weight);
 
-        if accumulated_value
-            >= options.target_value
-                + options.min_change_value
-                + estimated_fee.max(options.min_absolute_fee)
+        if (accumulated_eff_value >= target
+            && funds_change(options, accumulated_value, estimated_fee))
+            || within_changeless_window(options, accumulated_value, estimated_fee)
         {
             break;
         }
     }
 
-    if accumulated_value
-        < options.target_value
-            + options.min_change_value
-            + estimated_fee.max(options.min_absolute_fee)
+    if (accumulated_eff_value < target || !funds_change(options, accumulated_value, estimated_fee))
+        && !within_changeless_window(options, accumulated_value, estimated_fee)
     {
-        return Err(SelectionError::InsufficientFunds);
+        return Err(
+            if skipped_eff_value > 0 && accumulated_eff_value + skipped_eff_value >= target {
+                SelectionError::MaxWeightExceeded
+            } else {
+                SelectionError::InsufficientFunds
+            },
+        );
     }
-    let waste = calculate_waste(
+    let waste_breakdown = calculate_waste_detailed(
         options,
         accumulated_value,
         accumulated_weight,
@@ -58,7 +306,12 @@ pub fn select_coin_srd(
 
     Ok(SelectionOutput {
         selected_inputs,
-        waste: WasteMetric(waste),
+        waste: WasteMetric(waste_breakdown.total()),
+        waste_breakdown,
+        change_value: calculate_change(options, accumulated_value, estimated_fee),
+        estimated_fee,
+        selected_value: accumulated_value,
+        selected_weight: accumulated_weight,
     })
 }
 
@@ -66,10 +319,26 @@ pub fn select_coin_srd(
 mod test {
 
     use crate::{
-        algorithms::{fifo::select_coin_fifo, srd::select_coin_srd},
-        types::{CoinSelectionOpt, ExcessStrategy, OutputGroup, SelectionError},
+        algorithms::srd::{
+            select_coin_srd_weighted_with_rng, select_coin_srd_with_rng, select_in_order,
+        },
+        types::{CoinSelectionOpt, ExcessStrategy, FeeRate, OutputGroup},
     };
+    #[cfg(feature = "std")]
+    use crate::{
+        algorithms::{
+            fifo::select_coin_fifo,
+            srd::{
+                select_coin_srd, select_coin_srd_best_of, select_coin_srd_best_of_with_rng,
+                select_coin_srd_weighted,
+            },
+        },
+        types::SelectionError,
+        utils::calculate_fee,
+    };
+    use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
 
+    #[cfg(feature = "std")]
     fn setup_basic_output_groups() -> Vec<OutputGroup> {
         vec![
             OutputGroup {
@@ -77,22 +346,32 @@ mod test {
                 weight: 100,
                 input_count: 1,
                 creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
             },
             OutputGroup {
                 value: 2000,
                 weight: 200,
                 input_count: 1,
                 creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
             },
             OutputGroup {
                 value: 3000,
                 weight: 300,
                 input_count: 1,
                 creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
             },
         ]
     }
 
+    #[cfg(feature = "std")]
     fn setup_output_groups_withsequence() -> Vec<OutputGroup> {
         vec![
             OutputGroup {
@@ -100,24 +379,36 @@ mod test {
                 weight: 100,
                 input_count: 1,
                 creation_sequence: Some(1),
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
             },
             OutputGroup {
                 value: 2000,
                 weight: 200,
                 input_count: 1,
                 creation_sequence: Some(5000),
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
             },
             OutputGroup {
                 value: 3000,
                 weight: 300,
                 input_count: 1,
                 creation_sequence: Some(1001),
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
             },
             OutputGroup {
                 value: 1500,
                 weight: 150,
                 input_count: 1,
                 creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
             },
         ]
     }
@@ -125,8 +416,8 @@ mod test {
     fn setup_options(target_value: u64) -> CoinSelectionOpt {
         CoinSelectionOpt {
             target_value,
-            target_feerate: 0.4, // Simplified feerate
-            long_term_feerate: Some(0.4),
+            target_feerate: FeeRate::from_sat_per_wu(0.4), // Simplified feerate
+            long_term_feerate: Some(FeeRate::from_sat_per_wu(0.4)),
             min_absolute_fee: 0,
             base_weight: 10,
             change_weight: 50,
@@ -135,9 +426,17 @@ mod test {
             avg_output_weight: 10,
             min_change_value: 500,
             excess_strategy: ExcessStrategy::ToChange,
+            max_inputs: None,
+            bnb_tries: None,
+            include_uneconomical: false,
+            max_excess: None,
+            max_tx_weight: None,
+            knapsack_iterations: None,
+            knapsack_stagnation: None,
         }
     }
 
+    #[cfg(feature = "std")]
     fn test_successful_selection() {
         let mut inputs = setup_basic_output_groups();
         let mut options = setup_options(2500);
@@ -145,6 +444,7 @@ mod test {
         assert!(result.is_ok());
         let mut selection_output = result.unwrap();
         assert!(!selection_output.selected_inputs.is_empty());
+        assert!(selection_output.selected_value >= options.target_value);
 
         inputs = setup_output_groups_withsequence();
         options = setup_options(500);
@@ -152,8 +452,10 @@ mod test {
         assert!(result.is_ok());
         selection_output = result.unwrap();
         assert!(!selection_output.selected_inputs.is_empty());
+        assert!(selection_output.selected_value >= options.target_value);
     }
 
+    #[cfg(feature = "std")]
     fn test_insufficient_funds() {
         let inputs = setup_basic_output_groups();
         let options = setup_options(7000); // Set a target value higher than the sum of all inputs
@@ -161,9 +463,366 @@ mod test {
         assert!(matches!(result, Err(SelectionError::InsufficientFunds)));
     }
 
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_srd_accumulates_effective_values() {
+        // At 2 sats/wu the heavy inputs are worth a fraction of their nominal value, the last one nothing at all
+        let inputs = vec![
+            OutputGroup {
+                value: 3000,
+                weight: 1200,
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            },
+            OutputGroup {
+                value: 2500,
+                weight: 1000,
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            },
+            OutputGroup {
+                value: 1800,
+                weight: 100,
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            },
+            OutputGroup {
+                value: 1500,
+                weight: 1000,
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            },
+        ];
+        let mut options = setup_options(1000);
+        options.target_feerate = FeeRate::from_sat_per_wu(2.0);
+        for _ in 0..20 {
+            let result = select_coin_srd(&inputs, &options).unwrap();
+            let accumulated_value: u64 = result
+                .selected_inputs
+                .iter()
+                .map(|&i| inputs[i].value)
+                .sum();
+            let accumulated_weight: u64 = result
+                .selected_inputs
+                .iter()
+                .map(|&i| inputs[i].weight)
+                .sum();
+            assert!(!result.selected_inputs.contains(&3));
+            assert!(
+                accumulated_value
+                    >= options.target_value
+                        + options.min_change_value
+                        + calculate_fee(accumulated_weight, *options.target_feerate)
+            );
+        }
+
+        // Counted at face value the inputs would cover the target
+        let options = CoinSelectionOpt {
+            target_feerate: FeeRate::from_sat_per_wu(2.0),
+            ..setup_options(5000)
+        };
+        let result = select_coin_srd(&inputs, &options);
+        assert!(matches!(result, Err(SelectionError::InsufficientFunds)));
+    }
+
+    #[test]
+    fn test_srd_trims_overshoot() {
+        // Many small inputs and a few large ones, a large one drawn last covers the target on its own
+        let mut inputs: Vec<OutputGroup> = (0..20)
+            .map(|_| OutputGroup {
+                value: 500,
+                weight: 100,
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            })
+            .collect();
+        inputs.extend((0..3).map(|_| OutputGroup {
+            value: 10_000,
+            weight: 100,
+            input_count: 1,
+            creation_sequence: None,
+            ancestor_fee: 0,
+            ancestor_weight: 0,
+            block_height: None,
+        }));
+        let options = setup_options(2000);
+        let mut trimmed_runs = 0;
+        for seed in 0..50 {
+            let result =
+                select_coin_srd_with_rng(&inputs, &options, &mut StdRng::seed_from_u64(seed))
+                    .unwrap();
+            // The same draw, without trimming
+            let mut order: Vec<usize> = (0..inputs.len()).collect();
+            order.shuffle(&mut StdRng::seed_from_u64(seed));
+            let untrimmed = select_in_order(&inputs, &options, order).unwrap();
+
+            assert!(result.selected_value <= untrimmed.selected_value);
+            assert!(result.waste.0 <= untrimmed.waste.0);
+            assert!(
+                result.selected_value
+                    >= options.target_value + options.min_change_value + result.estimated_fee
+            );
+            assert_eq!(
+                result.selected_weight,
+                result
+                    .selected_inputs
+                    .iter()
+                    .map(|&i| inputs[i].weight)
+                    .sum::<u64>()
+            );
+            if result.selected_value < untrimmed.selected_value {
+                trimmed_runs += 1;
+            }
+        }
+        assert!(trimmed_runs > 0);
+    }
+
+    #[test]
+    fn test_srd_same_seed_same_selection() {
+        let inputs: Vec<OutputGroup> = (1..=30)
+            .map(|i| OutputGroup {
+                value: i * 100,
+                weight: 100,
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            })
+            .collect();
+        let options = setup_options(5000);
+        let selections: Vec<Vec<usize>> = (0..2)
+            .map(|_| {
+                select_coin_srd_with_rng(&inputs, &options, &mut StdRng::seed_from_u64(42))
+                    .unwrap()
+                    .selected_inputs
+            })
+            .collect();
+        assert_eq!(selections[0], selections[1]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_srd_best_of_draws() {
+        let inputs: Vec<OutputGroup> = (1..=30)
+            .map(|i| OutputGroup {
+                value: i * 100,
+                weight: 100,
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            })
+            .collect();
+        // Above the long term feerate, the draws spending fewer inputs waste less
+        let options = CoinSelectionOpt {
+            long_term_feerate: Some(FeeRate::from_sat_per_wu(0.1)),
+            ..setup_options(5000)
+        };
+        let mut improved_runs = 0;
+        for seed in 0..20 {
+            let single =
+                select_coin_srd_with_rng(&inputs, &options, &mut StdRng::seed_from_u64(seed))
+                    .unwrap();
+            // A single draw of the same seed is the first of the eight
+            let best_of_one = select_coin_srd_best_of_with_rng(
+                &inputs,
+                &options,
+                1,
+                &mut StdRng::seed_from_u64(seed),
+            )
+            .unwrap();
+            assert_eq!(best_of_one, single);
+            let best = select_coin_srd_best_of_with_rng(
+                &inputs,
+                &options,
+                8,
+                &mut StdRng::seed_from_u64(seed),
+            )
+            .unwrap();
+            assert!(best.waste <= single.waste);
+            if best.waste < single.waste {
+                improved_runs += 1;
+            }
+            let again = select_coin_srd_best_of_with_rng(
+                &inputs,
+                &options,
+                8,
+                &mut StdRng::seed_from_u64(seed),
+            )
+            .unwrap();
+            assert_eq!(again, best);
+        }
+        assert!(improved_runs > 0);
+
+        let result = select_coin_srd_best_of(&inputs, &setup_options(50_000), 8);
+        assert!(matches!(result, Err(SelectionError::InsufficientFunds)));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_srd_max_inputs() {
+        // Covering the target takes all three inputs
+        let inputs = setup_basic_output_groups();
+        let mut options = setup_options(4500);
+        options.max_inputs = Some(3);
+        let result = select_coin_srd(&inputs, &options).unwrap();
+        assert_eq!(result.selected_inputs.len(), 3);
+
+        options.max_inputs = Some(2);
+        for _ in 0..10 {
+            let result = select_coin_srd(&inputs, &options);
+            assert!(matches!(result, Err(SelectionError::NoSolutionFound)));
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn setup_weight_limited_output_groups() -> Vec<OutputGroup> {
+        // A heavy input worth more than the two light ones together
+        [(5000, 1000), (2000, 100), (2000, 100)]
+            .iter()
+            .map(|&(value, weight)| OutputGroup {
+                value,
+                weight,
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            })
+            .collect()
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_srd_skips_inputs_over_max_tx_weight() {
+        let inputs = setup_weight_limited_output_groups();
+        // Room for the base weight, the change output and both light inputs, never for the heavy one
+        let options = CoinSelectionOpt {
+            max_tx_weight: Some(10 + 50 + 300),
+            ..setup_options(3000)
+        };
+        // Drawn first, the heavy input is skipped rather than ending the draw
+        let result = select_in_order(&inputs, &options, [0, 1, 2]).unwrap();
+        assert_eq!(result.selected_inputs, vec![1, 2]);
+        for _ in 0..10 {
+            let result = select_coin_srd(&inputs, &options).unwrap();
+            let mut selected_inputs = result.selected_inputs;
+            selected_inputs.sort_unstable();
+            assert_eq!(selected_inputs, vec![1, 2]);
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_srd_max_weight_exceeded() {
+        let inputs = setup_weight_limited_output_groups();
+        // Only the heavy input makes up for what the light ones lack
+        let options = CoinSelectionOpt {
+            max_tx_weight: Some(10 + 50 + 300),
+            ..setup_options(4000)
+        };
+        for _ in 0..10 {
+            let result = select_coin_srd(&inputs, &options);
+            assert!(matches!(result, Err(SelectionError::MaxWeightExceeded)));
+        }
+        let options = CoinSelectionOpt {
+            max_tx_weight: None,
+            ..options
+        };
+        assert!(select_coin_srd(&inputs, &options).is_ok());
+
+        // Not even every input together covers the target
+        let options = CoinSelectionOpt {
+            max_tx_weight: Some(10 + 50 + 300),
+            ..setup_options(20_000)
+        };
+        let result = select_coin_srd(&inputs, &options);
+        assert!(matches!(result, Err(SelectionError::InsufficientFunds)));
+    }
+
+    #[test]
+    fn test_srd_weighted_prefers_older_coins() {
+        let inputs: Vec<OutputGroup> = (0..10)
+            .map(|sequence| OutputGroup {
+                value: 2000,
+                weight: 100,
+                input_count: 1,
+                creation_sequence: Some(sequence),
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            })
+            .collect();
+        // A single input covers the target
+        let options = setup_options(1000);
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut selection_counts = [0u32; 10];
+        for _ in 0..2000 {
+            let result = select_coin_srd_weighted_with_rng(&inputs, &options, &mut rng).unwrap();
+            assert_eq!(result.selected_inputs.len(), 1);
+            selection_counts[result.selected_inputs[0]] += 1;
+        }
+        // The oldest coin is ten times as likely to be drawn as the youngest
+        assert!(selection_counts[0] > 5 * selection_counts[9]);
+        assert!(
+            selection_counts[0] + selection_counts[1] > selection_counts[8] + selection_counts[9]
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_srd_weighted() {
+        let inputs = setup_output_groups_withsequence();
+        let result = select_coin_srd_weighted(&inputs, &setup_options(2500)).unwrap();
+        assert!(!result.selected_inputs.is_empty());
+
+        let result = select_coin_srd_weighted(&inputs, &setup_options(7000));
+        assert!(matches!(result, Err(SelectionError::InsufficientFunds)));
+
+        let result = select_coin_srd_weighted::<OutputGroup>(&[], &setup_options(7000));
+        assert!(matches!(result, Err(SelectionError::InsufficientFunds)));
+    }
+
+    #[cfg(feature = "std")]
     #[test]
     fn test_srd() {
         test_successful_selection();
         test_insufficient_funds();
     }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_srd_changeless_exact_match() {
+        // The target plus the fee for the base weight and the input, with nothing left for a change output
+        let inputs = vec![OutputGroup {
+            value: 1045,
+            weight: 100,
+            input_count: 1,
+            creation_sequence: None,
+            ancestor_fee: 0,
+            ancestor_weight: 0,
+            block_height: None,
+        }];
+        let result = select_coin_srd(&inputs, &setup_options(1000)).unwrap();
+        assert_eq!(result.selected_inputs, vec![0]);
+        assert_eq!(result.change_value, None);
+        assert_eq!(result.waste.0, 0);
+    }
 }
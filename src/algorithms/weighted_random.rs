@@ -0,0 +1,143 @@
+use crate::{
+    algorithms::srd::select_weighted,
+    types::{CoinSelectionOpt, SelectionError, SelectionOutput, WeightedUtxo},
+    utils::{effective_value, validate_feerate, validate_target},
+};
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use rand::thread_rng;
+use rand::Rng;
+
+/// Performs coin selection using a random draw weighted by effective value.
+///
+/// The inputs are drawn without replacement, with a probability proportional to their effective value, and
+/// accumulated until they cover the target and `min_change_value`. Unlike [`select_coin_srd`], where every input
+/// is equally likely to be drawn, large inputs are drawn first far more often, so small wallets do not drag in
+/// dust. Inputs with no effective value are never drawn.
+///
+/// Returns `InsufficientFunds` if the inputs can not cover the target, and `ArithmeticOverflow` if the values of the
+/// inputs or the target do not sum within a `u64`, see [`validate_target`].
+///
+/// [`select_coin_srd`]: crate::algorithms::srd::select_coin_srd
+#[cfg(feature = "std")]
+pub fn select_coin_weighted_random<T: WeightedUtxo>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+) -> Result<SelectionOutput, SelectionError> {
+    select_coin_weighted_random_with_rng(inputs, options, &mut thread_rng())
+}
+
+/// Performs coin selection using a random draw from `rng` weighted by effective value, see
+/// [`select_coin_weighted_random`].
+pub fn select_coin_weighted_random_with_rng<T: WeightedUtxo, R: Rng>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+    rng: &mut R,
+) -> Result<SelectionOutput, SelectionError> {
+    validate_feerate(*options.target_feerate)?;
+    validate_target(inputs, options)?;
+    let weights: Vec<u64> = inputs
+        .iter()
+        .map(|input| effective_value(input, *options.target_feerate))
+        .collect();
+    select_weighted(inputs, options, &weights, rng)
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+
+    use super::select_coin_weighted_random_with_rng;
+    use crate::{
+        algorithms::{srd::select_coin_srd, weighted_random::select_coin_weighted_random},
+        types::{CoinSelectionOpt, ExcessStrategy, FeeRate, OutputGroup, SelectionError},
+    };
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn setup_output_groups() -> Vec<OutputGroup> {
+        let mut inputs: Vec<OutputGroup> = (0..9)
+            .map(|_| OutputGroup {
+                value: 1000,
+                weight: 100,
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            })
+            .collect();
+        inputs.push(OutputGroup {
+            value: 100_000,
+            weight: 100,
+            input_count: 1,
+            creation_sequence: None,
+            ancestor_fee: 0,
+            ancestor_weight: 0,
+            block_height: None,
+        });
+        inputs
+    }
+
+    fn setup_options(target_value: u64) -> CoinSelectionOpt {
+        CoinSelectionOpt {
+            target_value,
+            target_feerate: FeeRate::from_sat_per_wu(0.4),
+            long_term_feerate: Some(FeeRate::from_sat_per_wu(0.4)),
+            min_absolute_fee: 0,
+            base_weight: 10,
+            change_weight: 50,
+            change_cost: 10,
+            avg_input_weight: 20,
+            avg_output_weight: 10,
+            min_change_value: 100,
+            excess_strategy: ExcessStrategy::ToChange,
+            max_inputs: None,
+            bnb_tries: None,
+            include_uneconomical: false,
+            max_excess: None,
+            max_tx_weight: None,
+            knapsack_iterations: None,
+            knapsack_stagnation: None,
+        }
+    }
+
+    #[test]
+    fn test_weighted_random_prefers_large_coins() {
+        let inputs = setup_output_groups();
+        let options = setup_options(500);
+        let mut rng = StdRng::seed_from_u64(11);
+        let (mut weighted_large_first, mut srd_large_first) = (0, 0);
+        for _ in 0..1000 {
+            let result = select_coin_weighted_random_with_rng(&inputs, &options, &mut rng).unwrap();
+            if result.selected_inputs[0] == 9 {
+                weighted_large_first += 1;
+            }
+            let result = select_coin_srd(&inputs, &options).unwrap();
+            if result.selected_inputs[0] == 9 {
+                srd_large_first += 1;
+            }
+        }
+        // The large coin holds over 90% of the effective value, but is only one coin out of ten
+        assert!(weighted_large_first > 850);
+        assert!(srd_large_first < 200);
+    }
+
+    #[test]
+    fn test_weighted_random_insufficient_funds() {
+        let inputs = setup_output_groups();
+        let result = select_coin_weighted_random(&inputs, &setup_options(200_000));
+        assert!(matches!(result, Err(SelectionError::InsufficientFunds)));
+
+        let result = select_coin_weighted_random::<OutputGroup>(&[], &setup_options(500));
+        assert!(matches!(result, Err(SelectionError::InsufficientFunds)));
+    }
+
+    #[test]
+    fn test_weighted_random_draws_every_input() {
+        // Covering the target takes every input
+        let inputs = setup_output_groups();
+        let result = select_coin_weighted_random(&inputs, &setup_options(108_000)).unwrap();
+        let mut selected_inputs = result.selected_inputs.clone();
+        selected_inputs.sort();
+        assert_eq!(selected_inputs, (0..10).collect::<Vec<usize>>());
+    }
+}
@@ -0,0 +1,261 @@
+//! Conversions from [rust-bitcoin](https://docs.rs/bitcoin) types into [`OutputGroup`] and [`FeeRate`].
+//!
+//! Wallets built on [BDK](https://docs.rs/bdk_wallet) describe their UTXOs with a satisfaction weight rather than a
+//! signed input, see [`OutputGroup::from_satisfaction_weight`]. With the `bdk` feature, a `bdk_wallet::WeightedUtxo`
+//! converts into an [`OutputGroup`] directly.
+
+use crate::types::{FeeRate, OutputGroup};
+use bitcoin::{Amount, TxIn, TxOut, Weight};
+
+/// Converts a rust-bitcoin feerate, kept in sats per 1000 weight units, into sats per weight unit.
+impl From<bitcoin::FeeRate> for FeeRate {
+    fn from(feerate: bitcoin::FeeRate) -> FeeRate {
+        FeeRate::from_sat_per_wu(feerate.to_sat_per_kwu() as f32 / 1000.0)
+    }
+}
+
+impl OutputGroup {
+    /// Creates a confirmed, single input [`OutputGroup`] of the given `value` and spending `weight`.
+    ///
+    /// Taking typed amounts rules out passing a size in vbytes where weight units are expected.
+    pub fn from_amount_and_weight(value: Amount, weight: Weight) -> OutputGroup {
+        OutputGroup {
+            value: value.to_sat(),
+            weight: weight.to_wu(),
+            input_count: 1,
+            creation_sequence: None,
+            ancestor_fee: 0,
+            ancestor_weight: 0,
+            block_height: None,
+        }
+    }
+
+    /// Returns the total value of the group as an [`Amount`].
+    pub fn amount(&self) -> Amount {
+        Amount::from_sat(self.value)
+    }
+
+    /// Returns the total weight of spending the group as a [`Weight`].
+    pub fn input_weight(&self) -> Weight {
+        Weight::from_wu(self.weight)
+    }
+
+    /// Creates a confirmed, single input [`OutputGroup`] spending `value` with the given `txin`.
+    ///
+    /// The weight is the one of the input in a segwit transaction: outpoint, sequence, `scriptSig` and witness,
+    /// so the `txin` should carry a final or dummy `scriptSig` and witness of the right size. Non segwit inputs are
+    /// counted with their one weight unit of empty witness.
+    pub fn from_txin(txin: &TxIn, value: Amount) -> OutputGroup {
+        OutputGroup::from_amount_and_weight(value, txin.segwit_weight())
+    }
+
+    /// Creates a confirmed, single input [`OutputGroup`] spending the previous output `prevout` with `txin`.
+    ///
+    /// The value is the one of `prevout`, the weight is computed from `txin` as in [`OutputGroup::from_txin`].
+    pub fn from_txout(prevout: &TxOut, txin: &TxIn) -> OutputGroup {
+        OutputGroup::from_txin(txin, prevout.value)
+    }
+
+    /// Creates a confirmed, single input [`OutputGroup`] spending `value` with an input of `satisfaction_weight`.
+    ///
+    /// The satisfaction weight is the weight of the `scriptSig` and witness that will spend the output, as
+    /// reported by BDK's `WeightedUtxo` and by miniscript descriptors. The weight of an empty segwit input is added
+    /// on top, as BDK does in its own coin selection.
+    ///
+    /// ```
+    /// use bitcoin::{Amount, Weight};
+    /// use rust_coinselect::types::OutputGroup;
+    ///
+    /// // The `bdk` feature converts a whole `WeightedUtxo` with `OutputGroup::from(&utxo)`
+    /// let group = OutputGroup::from_satisfaction_weight(Amount::from_sat(50_000), Weight::from_wu(108));
+    /// assert_eq!(group.weight, 165 + 108);
+    /// assert_eq!(group.input_count, 1);
+    /// ```
+    pub fn from_satisfaction_weight(value: Amount, satisfaction_weight: Weight) -> OutputGroup {
+        OutputGroup::from_amount_and_weight(
+            value,
+            TxIn::default().segwit_weight() + satisfaction_weight,
+        )
+    }
+}
+
+/// Converts a UTXO of a BDK wallet, weighted as in [`OutputGroup::from_satisfaction_weight`].
+///
+/// A local UTXO keeps the height of its confirming block, a foreign or unconfirmed one has none.
+#[cfg(feature = "bdk")]
+impl From<&bdk_wallet::WeightedUtxo> for OutputGroup {
+    fn from(weighted_utxo: &bdk_wallet::WeightedUtxo) -> OutputGroup {
+        let block_height = match &weighted_utxo.utxo {
+            bdk_wallet::Utxo::Local(local) => {
+                local.chain_position.confirmation_height_upper_bound()
+            }
+            bdk_wallet::Utxo::Foreign { .. } => None,
+        };
+        OutputGroup {
+            block_height,
+            ..OutputGroup::from_satisfaction_weight(
+                weighted_utxo.utxo.txout().value,
+                weighted_utxo.satisfaction_weight,
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use crate::types::{FeeRate, OutputGroup};
+    #[cfg(feature = "bdk")]
+    use bdk_wallet::{
+        chain::{BlockId, ChainPosition, ConfirmationBlockTime},
+        KeychainKind, LocalOutput, Utxo, WeightedUtxo,
+    };
+    #[cfg(feature = "bdk")]
+    use bitcoin::{hashes::Hash, psbt, BlockHash, OutPoint, Sequence};
+    use bitcoin::{Amount, ScriptBuf, TxIn, TxOut, Weight, Witness};
+
+    fn setup_txin(script_sig_len: usize, witness: &[&[u8]]) -> TxIn {
+        TxIn {
+            script_sig: ScriptBuf::from_bytes(vec![0; script_sig_len]),
+            witness: Witness::from_slice(witness),
+            ..TxIn::default()
+        }
+    }
+
+    #[test]
+    fn test_typed_round_trip() {
+        let weight = Weight::from_wu(272);
+        let group = OutputGroup::from_amount_and_weight(Amount::from_sat(50_000), weight);
+        assert_eq!(group.weight, 272);
+        assert_eq!(group.input_weight(), weight);
+        assert_eq!(group.value, 50_000);
+        assert_eq!(group.amount(), Amount::from_sat(50_000));
+        assert_eq!(group.input_count, 1);
+
+        // A size in vbytes has to be converted explicitly
+        let group = OutputGroup::from_amount_and_weight(
+            Amount::from_sat(50_000),
+            Weight::from_vb_unwrap(68),
+        );
+        assert_eq!(group.input_weight(), weight);
+    }
+
+    #[test]
+    fn test_feerate_from_bitcoin() {
+        let feerate = FeeRate::from(bitcoin::FeeRate::from_sat_per_vb_u32(10));
+        assert_eq!(feerate, FeeRate::from_sat_per_vb(10.0));
+        assert_eq!(feerate.as_sat_per_wu(), 2.5);
+    }
+
+    #[test]
+    fn test_from_txin_p2wpkh() {
+        // 72 bytes signature and 33 bytes public key
+        let txin = setup_txin(0, &[&[0; 72], &[0; 33]]);
+        let group = OutputGroup::from_txin(&txin, Amount::from_sat(50_000));
+        assert_eq!(group.value, 50_000);
+        // 41 bytes of non witness data and 108 bytes of witness
+        assert_eq!(group.weight, 41 * 4 + 108);
+        assert_eq!(group.input_count, 1);
+        assert_eq!(group.creation_sequence, None);
+        assert_eq!(group.ancestor_fee, 0);
+        assert_eq!(group.ancestor_weight, 0);
+    }
+
+    #[test]
+    fn test_from_txin_p2pkh() {
+        let txin = setup_txin(107, &[]);
+        let group = OutputGroup::from_txin(&txin, Amount::from_sat(50_000));
+        // 148 bytes of non witness data and the empty witness count
+        assert_eq!(group.weight, 148 * 4 + 1);
+    }
+
+    #[test]
+    fn test_from_txout() {
+        let txin = setup_txin(0, &[&[0; 72], &[0; 33]]);
+        let prevout = TxOut {
+            value: Amount::from_sat(120_000),
+            script_pubkey: ScriptBuf::new(),
+        };
+        let group = OutputGroup::from_txout(&prevout, &txin);
+        assert_eq!(group.value, 120_000);
+        assert_eq!(
+            group.weight,
+            OutputGroup::from_txin(&txin, prevout.value).weight
+        );
+    }
+
+    #[test]
+    fn test_from_satisfaction_weight() {
+        // A P2WPKH satisfaction: the witness element count, a 72 bytes signature and a 33 bytes public key
+        let satisfaction_weight = Weight::from_wu(1 + 1 + 72 + 1 + 33);
+        let group =
+            OutputGroup::from_satisfaction_weight(Amount::from_sat(50_000), satisfaction_weight);
+        let txin = setup_txin(0, &[&[0; 72], &[0; 33]]);
+        // As in BDK, the witness element count is counted both in the bare input and in the satisfaction
+        assert_eq!(
+            group.weight,
+            OutputGroup::from_txin(&txin, Amount::from_sat(50_000)).weight + 1
+        );
+        assert_eq!(group.value, 50_000);
+        assert_eq!(group.input_count, 1);
+    }
+
+    #[cfg(feature = "bdk")]
+    #[test]
+    fn test_from_bdk_weighted_utxo() {
+        let satisfaction_weight = Weight::from_wu(1 + 1 + 72 + 1 + 33);
+        let txout = TxOut {
+            value: Amount::from_sat(50_000),
+            script_pubkey: ScriptBuf::new(),
+        };
+        let local = |chain_position| WeightedUtxo {
+            satisfaction_weight,
+            utxo: Utxo::Local(LocalOutput {
+                outpoint: OutPoint::null(),
+                txout: txout.clone(),
+                keychain: KeychainKind::External,
+                is_spent: false,
+                derivation_index: 0,
+                chain_position,
+            }),
+        };
+        let expected = OutputGroup::from_satisfaction_weight(txout.value, satisfaction_weight);
+
+        let confirmed = OutputGroup::from(&local(ChainPosition::Confirmed {
+            anchor: ConfirmationBlockTime {
+                block_id: BlockId {
+                    height: 800_000,
+                    hash: BlockHash::all_zeros(),
+                },
+                confirmation_time: 0,
+            },
+            transitively: None,
+        }));
+        assert_eq!(confirmed.value, expected.value);
+        assert_eq!(confirmed.weight, expected.weight);
+        assert_eq!(confirmed.input_count, 1);
+        assert_eq!(confirmed.block_height, Some(800_000));
+
+        let unconfirmed = OutputGroup::from(&local(ChainPosition::Unconfirmed {
+            first_seen: None,
+            last_seen: None,
+        }));
+        assert_eq!(unconfirmed.weight, expected.weight);
+        assert_eq!(unconfirmed.block_height, None);
+
+        let foreign = OutputGroup::from(&WeightedUtxo {
+            satisfaction_weight,
+            utxo: Utxo::Foreign {
+                outpoint: OutPoint::null(),
+                sequence: Sequence::MAX,
+                psbt_input: Box::new(psbt::Input {
+                    witness_utxo: Some(txout.clone()),
+                    ..Default::default()
+                }),
+            },
+        });
+        assert_eq!(foreign.value, 50_000);
+        assert_eq!(foreign.weight, expected.weight);
+        assert_eq!(foreign.block_height, None);
+    }
+}
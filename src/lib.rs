@@ -1,4 +1,11 @@
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
 pub mod algorithms;
+#[cfg(feature = "bitcoin")]
+pub mod interop;
+#[cfg(feature = "std")]
 pub mod selectcoin;
 pub mod types;
 pub mod utils;
@@ -1,84 +1,672 @@
 use crate::{
     algorithms::{
-        bnb::select_coin_bnb, fifo::select_coin_fifo, knapsack::select_coin_knapsack,
-        lowestlarger::select_coin_lowestlarger, srd::select_coin_srd,
+        bestfit::select_coin_bestfit, bnb::select_coin_bnb, coingrinder::select_coin_coingrinder,
+        fifo::select_coin_fifo, greedywaste::select_coin_greedy_waste,
+        knapsack::select_coin_knapsack_with_rng, largestfirst::select_coin_largestfirst,
+        lowestlarger::select_coin_lowestlarger, randomimprove::select_coin_randomimprove_with_rng,
+        smallestfirst::select_coin_smallestfirst, srd::select_coin_srd_with_rng,
+        weighted_random::select_coin_weighted_random_with_rng,
+    },
+    types::{
+        Algorithm, CoinSelectionOpt, FeeRate, OutputGroup, SelectionError, SelectionOutput,
+        WasteMetric, WeightedUtxo,
+    },
+    utils::{
+        calculate_bump_fee, calculate_change, calculate_fee, calculate_selection_fee,
+        calculate_waste_detailed, economical_inputs, exceeds_max_inputs, splits_change,
+        validate_feerate, validate_inputs, validate_target,
     },
-    types::{CoinSelectionOpt, OutputGroup, SelectionError, SelectionOutput},
 };
+use rand::{rngs::StdRng, thread_rng, Rng, SeedableRng};
 use std::{
-    sync::{Arc, Mutex},
+    collections::HashSet,
+    panic::{self, AssertUnwindSafe},
     thread,
 };
 
+/// The signature shared by every `select_coin_*` algorithm, taking the inputs and the options by reference.
+pub type CoinSelectionFn<T> =
+    fn(&[T], &CoinSelectionOpt) -> Result<SelectionOutput, SelectionError>;
+
 /// The global coin selection API that applies all algorithms and produces the result with the lowest [WasteMetric].
 ///
-/// At least one selection solution should be found.
-type CoinSelectionFn =
-    fn(&[OutputGroup], &CoinSelectionOpt) -> Result<SelectionOutput, SelectionError>;
-
-#[derive(Debug)]
-struct SharedState {
-    result: Result<SelectionOutput, SelectionError>,
-    any_success: bool,
+/// At least one selection solution should be found. The fee lifting the unconfirmed ancestors of an input to
+/// `target_feerate` is deducted from its value before any algorithm sees it, and counted as waste, so unconfirmed
+/// inputs with large, cheap ancestors are only selected when nothing better covers the target. That fee is
+/// included in the returned `estimated_fee`. Inputs costing more to spend than they are worth are left out before
+/// any algorithm sees them, unless `include_uneconomical` is set.
+///
+/// Invalid options are reported as is, see [`CoinSelectionOpt::validate`], before any algorithm runs, and so are
+/// empty `inputs`, as `EmptyInputs`, and a target which does not fit in a `u64`, as `ArithmeticOverflow`, see
+/// [`validate_target`]. The algorithms run on a thread each, except on `wasm32` where they run in turn, see
+/// [`select_coin_sequential`].
+pub fn select_coin<T: WeightedUtxo + Sync>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+) -> Result<SelectionOutput, SelectionError> {
+    select_coin_with_rng(inputs, options, &mut thread_rng())
 }
 
-pub fn select_coin(
-    inputs: &[OutputGroup],
+/// Performs coin selection like [`select_coin`], seeding the randomized algorithms from `rng`.
+///
+/// One seed per algorithm is drawn from `rng` up front, so the same `rng` state always produces the same
+/// [`SelectionOutput`], whichever thread finishes first.
+pub fn select_coin_with_rng<T: WeightedUtxo + Sync, R: Rng>(
+    inputs: &[T],
     options: &CoinSelectionOpt,
+    rng: &mut R,
 ) -> Result<SelectionOutput, SelectionError> {
-    let algorithms: Vec<CoinSelectionFn> = vec![
-        select_coin_bnb,
-        select_coin_fifo,
-        select_coin_lowestlarger,
-        select_coin_srd,
-        select_coin_knapsack, // Future algorithms can be added here
-    ];
-    // Shared result for all threads
-    let best_result = Arc::new(Mutex::new(SharedState {
-        result: Err(SelectionError::NoSolutionFound),
-        any_success: false,
-    }));
-    for &algorithm in &algorithms {
-        let best_result_clone = Arc::clone(&best_result);
+    // Validated once up front, so the error is not lost among the results of the algorithms
+    options.validate()?;
+    validate_inputs(inputs)?;
+    validate_target(inputs, options)?;
+    best_selection(select_coin_all_with_rng(inputs, options, rng))
+}
+
+/// Picks the first successful selection of the ranked `results`.
+///
+/// Without any, `InsufficientFunds` is returned if an algorithm reported it, `AlgorithmPanicked` if every algorithm
+/// panicked, and `NoSolutionFound` otherwise.
+fn best_selection(
+    results: Vec<(Algorithm, Result<SelectionOutput, SelectionError>)>,
+) -> Result<SelectionOutput, SelectionError> {
+    let any_insufficient_funds = results
+        .iter()
+        .any(|(_, result)| matches!(result, Err(SelectionError::InsufficientFunds)));
+    let all_panicked = results
+        .iter()
+        .all(|(_, result)| matches!(result, Err(SelectionError::AlgorithmPanicked)));
+    results
+        .into_iter()
+        .find_map(|(_, result)| result.ok())
+        .ok_or(if any_insufficient_funds {
+            // Only reported as InsufficientFunds if no algorithm succeeded
+            SelectionError::InsufficientFunds
+        } else if all_panicked {
+            SelectionError::AlgorithmPanicked
+        } else {
+            SelectionError::NoSolutionFound
+        })
+}
+
+/// Runs every [`Algorithm`] like [`select_coin`], returning all the results ranked by ascending waste.
+///
+/// Successful selections come first, ties broken in favour of the algorithm listed first in [`Algorithm::ALL`], and
+/// the errors last, in the same order. Useful to compare the algorithms, or to see why one of them was chosen. An
+/// algorithm that panics is reported as `AlgorithmPanicked` instead of unwinding, the others are unaffected.
+pub fn select_coin_all<T: WeightedUtxo + Sync>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+) -> Vec<(Algorithm, Result<SelectionOutput, SelectionError>)> {
+    select_coin_all_with_rng(inputs, options, &mut thread_rng())
+}
+
+/// Runs every [`Algorithm`] like [`select_coin_all`], seeding the randomized algorithms from `rng` as
+/// [`select_coin_with_rng`] does.
+pub fn select_coin_all_with_rng<T: WeightedUtxo + Sync, R: Rng>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+    rng: &mut R,
+) -> Vec<(Algorithm, Result<SelectionOutput, SelectionError>)> {
+    // std::thread can not spawn on wasm32-unknown-unknown
+    let sequential = cfg!(target_arch = "wasm32");
+    run_all_algorithms(inputs, options, rng, sequential, run_algorithm)
+}
+
+/// Performs coin selection like [`select_coin`], running the algorithms in turn on the calling thread.
+///
+/// Meant for targets without threads, such as `wasm32-unknown-unknown` where [`select_coin`] delegates to it. The
+/// result is the same as the one of [`select_coin`] for the same inputs, only slower on native targets.
+pub fn select_coin_sequential<T: WeightedUtxo + Sync>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+) -> Result<SelectionOutput, SelectionError> {
+    select_coin_sequential_with_rng(inputs, options, &mut thread_rng())
+}
+
+/// Performs coin selection like [`select_coin_sequential`], seeding the randomized algorithms from `rng`.
+///
+/// The seeds are drawn as in [`select_coin_with_rng`], so both return the same [`SelectionOutput`] for the same `rng`
+/// state.
+pub fn select_coin_sequential_with_rng<T: WeightedUtxo + Sync, R: Rng>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+    rng: &mut R,
+) -> Result<SelectionOutput, SelectionError> {
+    options.validate()?;
+    validate_inputs(inputs)?;
+    validate_target(inputs, options)?;
+    best_selection(run_all_algorithms(
+        inputs,
+        options,
+        rng,
+        true,
+        run_algorithm,
+    ))
+}
+
+/// Runs every [`Algorithm`] with `run`, see [`select_coin_all_with_rng`], on a thread of its own or in turn on the
+/// calling thread if `sequential` is set.
+fn run_all_algorithms<'a, T, R, F>(
+    inputs: &'a [T],
+    options: &CoinSelectionOpt,
+    rng: &mut R,
+    sequential: bool,
+    run: F,
+) -> Vec<(Algorithm, Result<SelectionOutput, SelectionError>)>
+where
+    T: WeightedUtxo + Sync,
+    R: Rng,
+    F: Fn(
+            Algorithm,
+            &[AncestorAdjusted<'a, T>],
+            &CoinSelectionOpt,
+            &mut StdRng,
+        ) -> Result<SelectionOutput, SelectionError>
+        + Sync,
+{
+    let adjusted_inputs = adjust_for_ancestors(inputs, options);
+    let seeds: Vec<u64> = Algorithm::ALL.iter().map(|_| rng.gen()).collect();
+    // Unless sequential, all algorithms run in parallel, borrowing the inputs and options instead of cloning them
+    let adjusted_inputs = &adjusted_inputs;
+    let run = &run;
+    let results: Vec<Result<SelectionOutput, SelectionError>> = if sequential {
+        Algorithm::ALL
+            .iter()
+            .zip(seeds)
+            .map(|(&algorithm, seed)| {
+                let mut rng = StdRng::seed_from_u64(seed);
+                panic::catch_unwind(AssertUnwindSafe(|| {
+                    run(algorithm, adjusted_inputs, options, &mut rng)
+                }))
+                .unwrap_or(Err(SelectionError::AlgorithmPanicked))
+            })
+            .collect()
+    } else {
         thread::scope(|s| {
-            s.spawn(|| {
-                let result = algorithm(inputs, options);
-                let mut state = best_result_clone.lock().unwrap();
-                match result {
-                    Ok(selection_output) => {
-                        if match &state.result {
-                            Ok(current_best) => selection_output.waste.0 < current_best.waste.0,
-                            Err(_) => true,
-                        } {
-                            state.result = Ok(selection_output);
-                            state.any_success = true;
-                        }
-                    }
-                    Err(e) => {
-                        if e == SelectionError::InsufficientFunds && !state.any_success {
-                            // Only set to InsufficientFunds if no algorithm succeeded
-                            state.result = Err(SelectionError::InsufficientFunds);
-                        }
-                    }
-                }
-            });
-        });
+            let handles: Vec<_> = Algorithm::ALL
+                .iter()
+                .zip(seeds)
+                .map(|(&algorithm, seed)| {
+                    s.spawn(move || {
+                        let mut rng = StdRng::seed_from_u64(seed);
+                        run(algorithm, adjusted_inputs, options, &mut rng)
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                // A panicking algorithm only loses its own result
+                .map(|handle| {
+                    handle
+                        .join()
+                        .unwrap_or(Err(SelectionError::AlgorithmPanicked))
+                })
+                .collect()
+        })
+    };
+    let mut results: Vec<(Algorithm, Result<SelectionOutput, SelectionError>)> = results
+        .into_iter()
+        .map(|result| result.map(|selection| add_bump_fees(selection, adjusted_inputs)))
+        .zip(Algorithm::ALL)
+        .map(|(result, algorithm)| (algorithm, result))
+        .collect();
+
+    // The sort is stable, ties are broken in favour of the algorithm listed first, whichever thread finished first
+    results.sort_by_key(|(_, result)| match result {
+        Ok(selection) => (false, selection.waste),
+        Err(_) => (true, WasteMetric(0)),
+    });
+    results
+}
+
+/// Performs coin selection with a single [`Algorithm`], on the calling thread.
+///
+/// Unconfirmed ancestors are paid for as in [`select_coin`].
+pub fn select_coin_with<T: WeightedUtxo>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+    algorithm: Algorithm,
+) -> Result<SelectionOutput, SelectionError> {
+    options.validate()?;
+    validate_inputs(inputs)?;
+    validate_target(inputs, options)?;
+    let adjusted_inputs = adjust_for_ancestors(inputs, options);
+    let selection = run_algorithm(algorithm, &adjusted_inputs, options, &mut thread_rng())?;
+    Ok(add_bump_fees(selection, &adjusted_inputs))
+}
+
+/// Runs a single [`Algorithm`], drawing from `rng` if it is randomized.
+fn run_algorithm<T: WeightedUtxo, R: Rng>(
+    algorithm: Algorithm,
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+    rng: &mut R,
+) -> Result<SelectionOutput, SelectionError> {
+    match algorithm {
+        Algorithm::Bnb => select_coin_bnb(inputs, options),
+        Algorithm::Fifo => select_coin_fifo(inputs, options),
+        Algorithm::LowestLarger => select_coin_lowestlarger(inputs, options),
+        Algorithm::Srd => select_coin_srd_with_rng(inputs, options, rng),
+        Algorithm::Knapsack => select_coin_knapsack_with_rng(inputs, options, rng),
+        Algorithm::CoinGrinder => select_coin_coingrinder(inputs, options),
+        Algorithm::LargestFirst => select_coin_largestfirst(inputs, options),
+        Algorithm::SmallestFirst => select_coin_smallestfirst(inputs, options),
+        Algorithm::RandomImprove => select_coin_randomimprove_with_rng(inputs, options, rng),
+        Algorithm::BestFit => select_coin_bestfit(inputs, options),
+        Algorithm::WeightedRandom => select_coin_weighted_random_with_rng(inputs, options, rng),
+        Algorithm::GreedyWaste => select_coin_greedy_waste(inputs, options),
     }
-    // Extract the result from the shared state
-    Arc::try_unwrap(best_result)
-        .expect("Arc unwrap failed")
-        .into_inner()
-        .expect("Mutex lock failed")
-        .result
+}
+
+/// Performs coin selection via [`select_coin`], always spending the `required_inputs`.
+///
+/// The required inputs are selected first, and their value net of the fee for spending them and for lifting their
+/// unconfirmed ancestors is deducted from the target before the remaining inputs are selected, so uneconomical ones
+/// raise the target instead. They are never dropped, even when they worsen the waste, and when they cover
+/// the target on their own no other input is selected. The returned indices refer to `inputs`, in ascending order
+/// like those of every selection. The required inputs count towards `max_inputs`, returning `NoSolutionFound` if they
+/// exceed it.
+///
+/// # Panics
+///
+/// Panics if any of the `required_inputs` is out of bounds of `inputs`.
+pub fn select_coin_with_required<T: WeightedUtxo + Sync>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+    required_inputs: &[usize],
+) -> Result<SelectionOutput, SelectionError> {
+    options.validate()?;
+    validate_inputs(inputs)?;
+    validate_target(inputs, options)?;
+    let mut selected_inputs: Vec<usize> = required_inputs.to_vec();
+    selected_inputs.sort_unstable();
+    selected_inputs.dedup();
+    let required_value = selected_inputs
+        .iter()
+        .try_fold(0u64, |total, &i| total.checked_add(inputs[i].value()))
+        .ok_or(SelectionError::ArithmeticOverflow)?;
+    // Unlike the effective value, not floored at zero for the inputs worth less than their fees
+    let required_fees = selected_inputs
+        .iter()
+        .try_fold(0u64, |total, &i| {
+            total
+                .checked_add(calculate_fee(inputs[i].weight(), *options.target_feerate))?
+                .checked_add(calculate_bump_fee(&inputs[i], *options.target_feerate))
+        })
+        .ok_or(SelectionError::ArithmeticOverflow)?;
+    let required_count: usize = selected_inputs
+        .iter()
+        .map(|&i| inputs[i].input_count())
+        .sum();
+    if exceeds_max_inputs(options, required_count) {
+        return Err(SelectionError::NoSolutionFound);
+    }
+
+    let required_target = options
+        .target_value
+        .checked_add(options.min_change_value)
+        .and_then(|target| {
+            target.checked_add(
+                calculate_fee(options.base_weight, *options.target_feerate)
+                    .max(options.min_absolute_fee),
+            )
+        })
+        .and_then(|target| target.checked_add(required_fees))
+        .ok_or(SelectionError::ArithmeticOverflow)?;
+    if required_value < required_target {
+        // Selecting among the other inputs, for what is left of the target
+        let remaining_inputs: Vec<usize> = (0..inputs.len())
+            .filter(|i| selected_inputs.binary_search(i).is_err())
+            .collect();
+        let remaining_options = CoinSelectionOpt {
+            target_value: (options.target_value + required_fees).saturating_sub(required_value),
+            max_inputs: options
+                .max_inputs
+                .map(|max_inputs| max_inputs - required_count),
+            ..options.clone()
+        };
+        let selection = select_coin_among(inputs, &remaining_options, &remaining_inputs)?;
+        selected_inputs.extend(selection.selected_inputs);
+    }
+
+    let accumulated_value = selected_inputs
+        .iter()
+        .try_fold(0u64, |total, &i| total.checked_add(inputs[i].value()))
+        .ok_or(SelectionError::ArithmeticOverflow)?;
+    let accumulated_weight: u64 = selected_inputs.iter().map(|&i| inputs[i].weight()).sum();
+    let bump_fee: u64 = selected_inputs
+        .iter()
+        .map(|&i| calculate_bump_fee(&inputs[i], *options.target_feerate))
+        .sum();
+    let estimated_fees = calculate_selection_fee(options, accumulated_weight) + bump_fee;
+    let mut waste_breakdown = calculate_waste_detailed(
+        options,
+        accumulated_value,
+        accumulated_weight,
+        estimated_fees,
+    );
+    // The fee for the ancestors is paid now, on top of the fee for the inputs themselves
+    waste_breakdown.feerate_diff += bump_fee as i64;
+    selected_inputs.sort_unstable();
+    Ok(SelectionOutput {
+        selected_inputs,
+        waste: WasteMetric(waste_breakdown.total()),
+        waste_breakdown,
+        change_value: calculate_change(options, accumulated_value, estimated_fees),
+        estimated_fee: estimated_fees,
+        selected_value: accumulated_value,
+        selected_weight: accumulated_weight,
+    })
+}
+
+/// Performs coin selection via [`select_coin`], never spending the `excluded_inputs`.
+///
+/// Lets callers freeze inputs, such as immature coinbase outputs, without re-indexing `inputs`. The returned
+/// indices refer to `inputs`.
+pub fn select_coin_excluding<T: WeightedUtxo + Sync>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+    excluded_inputs: &HashSet<usize>,
+) -> Result<SelectionOutput, SelectionError> {
+    let candidates: Vec<usize> = (0..inputs.len())
+        .filter(|i| !excluded_inputs.contains(i))
+        .collect();
+    options.validate()?;
+    validate_inputs(&candidates)?;
+    select_coin_among(inputs, options, &candidates)
+}
+
+/// Performs coin selection via [`select_coin`], spending the unconfirmed inputs only when the confirmed ones can not
+/// cover the target.
+///
+/// An input is unconfirmed when its [`WeightedUtxo::block_height`] is `None`. The selection first runs over the
+/// confirmed inputs only, and is re-run over all of `inputs` if, and only if, it returns `InsufficientFunds`. The
+/// returned indices refer to `inputs`.
+pub fn select_coin_two_phase<T: WeightedUtxo + Sync>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+) -> Result<SelectionOutput, SelectionError> {
+    let unconfirmed_inputs: HashSet<usize> = (0..inputs.len())
+        .filter(|&i| inputs[i].block_height().is_none())
+        .collect();
+    match select_coin_excluding(inputs, options, &unconfirmed_inputs) {
+        // Excluding every unconfirmed input may leave none
+        Err(SelectionError::InsufficientFunds | SelectionError::EmptyInputs) => {
+            select_coin(inputs, options)
+        }
+        result => result,
+    }
+}
+
+/// The minimum feerate, in sats per weight unit, by which a replacement must pay for its own size under BIP-125.
+///
+/// Equals Bitcoin Core's default `-incrementalrelayfee` of 1 sat/vB.
+pub const INCREMENTAL_RELAY_FEERATE: f32 = 0.25;
+
+/// Performs coin selection via [`select_coin`] for the additional inputs of a replacement transaction.
+///
+/// The transaction being replaced weighs `original_weight` and pays `original_fee`. The selected inputs must
+/// raise the fee of the replacement, whose weight includes the added inputs, to `target_feerate`, and must also
+/// satisfy BIP-125 by paying at least [`INCREMENTAL_RELAY_FEERATE`] for the whole replacement on top of
+/// `original_fee`. The `base_weight` of the options is ignored, the original transaction already accounts for it.
+///
+/// The returned `estimated_fee` is the total fee of the replacement, including the `change_weight` of its change
+/// outputs, and `change_value` the value of the added inputs left over after paying it. The waste is that of the
+/// added inputs paying the difference to `original_fee`.
+pub fn select_coin_feebump<T: WeightedUtxo + Sync>(
+    inputs: &[T],
+    original_weight: u64,
+    original_fee: u64,
+    options: &CoinSelectionOpt,
+) -> Result<SelectionOutput, SelectionError> {
+    validate_feerate(*options.target_feerate)?;
+    // Every added input pays for its own weight, at no less than the incremental relay feerate
+    let target_feerate = options.target_feerate.max(INCREMENTAL_RELAY_FEERATE);
+    let shortfall = calculate_fee(original_weight, target_feerate)
+        .saturating_sub(original_fee)
+        .max(calculate_fee(original_weight, INCREMENTAL_RELAY_FEERATE));
+    let feebump_options = CoinSelectionOpt {
+        target_value: shortfall,
+        target_feerate: FeeRate::from_sat_per_wu(target_feerate),
+        base_weight: 0,
+        ..options.clone()
+    };
+    let mut selection = select_coin(inputs, &feebump_options)?;
+
+    let total_weight = original_weight + selection.selected_weight;
+    let bump_fee: u64 = selection
+        .selected_inputs
+        .iter()
+        .map(|&i| calculate_bump_fee(&inputs[i], target_feerate))
+        .sum();
+    let total_fee = calculate_fee(total_weight, *options.target_feerate)
+        .max(original_fee + calculate_fee(total_weight, INCREMENTAL_RELAY_FEERATE))
+        + bump_fee;
+    // The added inputs pay the difference to the original fee, their waste is measured against it alone
+    let added_fee = total_fee - original_fee;
+    if selection.selected_value < added_fee {
+        return Err(SelectionError::InsufficientFunds);
+    }
+    let waste_options = CoinSelectionOpt {
+        target_value: 0,
+        min_absolute_fee: 0,
+        ..feebump_options
+    };
+    selection.waste_breakdown = calculate_waste_detailed(
+        &waste_options,
+        selection.selected_value,
+        selection.selected_weight,
+        added_fee,
+    );
+    selection.waste = WasteMetric(selection.waste_breakdown.total());
+    // A change output adds its own weight to the replacement, which pays for it at the bumped feerate
+    selection.change_value = calculate_change(&waste_options, selection.selected_value, added_fee);
+    let change_outputs = match selection.change_value {
+        Some(_) if splits_change(&waste_options, selection.selected_value, added_fee) => 2,
+        Some(_) => 1,
+        None => 0,
+    };
+    selection.estimated_fee =
+        total_fee + change_outputs * calculate_fee(options.change_weight, target_feerate);
+    Ok(selection)
+}
+
+/// Performs coin selection via [`select_coin`], spending each of the `clusters` either whole or not at all.
+///
+/// Mirrors Bitcoin Core's `-avoidpartialspends`: when a caller builds one [`OutputGroup`] per UTXO, the UTXOs
+/// sharing an address can be listed as a cluster of indices into `inputs`, so that spending some of them never
+/// links the address while leaving the rest behind. Every cluster is merged into a single group before the
+/// selection, and the returned indices refer to `inputs`, in ascending order. Inputs outside of any cluster are
+/// selected on their own.
+///
+/// Returns `ArithmeticOverflow` if the values, weights or ancestors of a cluster do not sum within a `u64`.
+///
+/// # Panics
+///
+/// Panics if a cluster holds an index out of bounds of `inputs`, or if the clusters are not disjoint.
+pub fn select_coin_avoiding_partial_spends<T: WeightedUtxo + Sync>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+    clusters: &[Vec<usize>],
+) -> Result<SelectionOutput, SelectionError> {
+    let mut clustered = vec![false; inputs.len()];
+    let mut groups: Vec<&[usize]> = Vec::new();
+    for cluster in clusters.iter().filter(|cluster| !cluster.is_empty()) {
+        for &index in cluster {
+            assert!(!clustered[index], "input {} is in several clusters", index);
+            clustered[index] = true;
+        }
+        groups.push(cluster);
+    }
+    let unclustered: Vec<usize> = (0..inputs.len()).filter(|&i| !clustered[i]).collect();
+    groups.extend(unclustered.chunks(1));
+
+    let checked_sum = |group: &[usize], field: fn(&T) -> u64| {
+        group
+            .iter()
+            .try_fold(0u64, |total, &i| total.checked_add(field(&inputs[i])))
+    };
+    let merged_groups: Vec<OutputGroup> = groups
+        .iter()
+        .map(|group| {
+            Some(OutputGroup {
+                value: checked_sum(group, T::value)?,
+                weight: checked_sum(group, T::weight)?,
+                input_count: group.iter().map(|&i| inputs[i].input_count()).sum(),
+                creation_sequence: group
+                    .iter()
+                    .filter_map(|&i| inputs[i].creation_sequence())
+                    .min(),
+                ancestor_fee: checked_sum(group, T::ancestor_fee)?,
+                ancestor_weight: checked_sum(group, T::ancestor_weight)?,
+                block_height: group.iter().filter_map(|&i| inputs[i].block_height()).min(),
+            })
+        })
+        .collect::<Option<Vec<OutputGroup>>>()
+        .ok_or(SelectionError::ArithmeticOverflow)?;
+    let mut selection = select_coin(&merged_groups, options)?;
+    selection.selected_inputs = selection
+        .selected_inputs
+        .iter()
+        .flat_map(|&group| groups[group].iter().copied())
+        .collect();
+    selection.selected_inputs.sort_unstable();
+    Ok(selection)
+}
+
+/// Performs coin selection like [`select_coin`] among the `candidates` only, mapping the selected indices back to
+/// `inputs`.
+///
+/// The options and candidates are not checked, the callers do so. What is left of the target of
+/// [`select_coin_with_required`] may be zero, and the other inputs none.
+fn select_coin_among<T: WeightedUtxo + Sync>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+    candidates: &[usize],
+) -> Result<SelectionOutput, SelectionError> {
+    let candidate_groups: Vec<&T> = candidates.iter().map(|&i| &inputs[i]).collect();
+    let mut selection = best_selection(select_coin_all(&candidate_groups, options))?;
+    for index in selection.selected_inputs.iter_mut() {
+        *index = candidates[*index];
+    }
+    Ok(selection)
+}
+
+/// An input whose value is reduced by the fee lifting its unconfirmed ancestors to the target feerate.
+struct AncestorAdjusted<'a, T> {
+    input: &'a T,
+    index: usize,
+    bump_fee: u64,
+}
+
+impl<T: WeightedUtxo> WeightedUtxo for AncestorAdjusted<'_, T> {
+    fn value(&self) -> u64 {
+        self.input.value().saturating_sub(self.bump_fee)
+    }
+
+    fn weight(&self) -> u64 {
+        self.input.weight()
+    }
+
+    fn input_count(&self) -> usize {
+        self.input.input_count()
+    }
+
+    fn creation_sequence(&self) -> Option<u32> {
+        self.input.creation_sequence()
+    }
+
+    fn block_height(&self) -> Option<u32> {
+        self.input.block_height()
+    }
+}
+
+/// Wraps the economical inputs, see [`economical_inputs`], with their index in `inputs`.
+fn adjust_for_ancestors<'a, T: WeightedUtxo>(
+    inputs: &'a [T],
+    options: &CoinSelectionOpt,
+) -> Vec<AncestorAdjusted<'a, T>> {
+    economical_inputs(inputs, options)
+        .into_iter()
+        .map(|index| AncestorAdjusted {
+            input: &inputs[index],
+            index,
+            bump_fee: calculate_bump_fee(&inputs[index], *options.target_feerate),
+        })
+        .collect()
+}
+
+/// Restores the full value of the selected inputs, counting the fee paid for their ancestors in the estimated fee
+/// and in the waste, and maps the selected indices back to the original inputs.
+fn add_bump_fees<T>(
+    mut selection: SelectionOutput,
+    adjusted_inputs: &[AncestorAdjusted<T>],
+) -> SelectionOutput {
+    let bump_fee: u64 = selection
+        .selected_inputs
+        .iter()
+        .map(|&i| adjusted_inputs[i].bump_fee)
+        .sum();
+    selection.selected_value += bump_fee;
+    selection.estimated_fee += bump_fee;
+    selection.waste.0 += bump_fee as i64;
+    selection.waste_breakdown.feerate_diff += bump_fee as i64;
+    for index in selection.selected_inputs.iter_mut() {
+        *index = adjusted_inputs[*index].index;
+    }
+    selection
 }
 
 #[cfg(test)]
 mod test {
 
+    use super::{best_selection, run_algorithm, run_all_algorithms, CoinSelectionFn};
     use crate::{
-        selectcoin::select_coin,
-        types::{CoinSelectionOpt, ExcessStrategy, OutputGroup, SelectionError},
+        algorithms::{
+            annealing::select_coin_annealing,
+            bestfit::select_coin_bestfit,
+            bnb::{
+                select_coin_bnb, select_coin_bnb_random, select_coin_bnb_with_change,
+                select_coin_bnb_with_fallback,
+            },
+            coingrinder::select_coin_coingrinder,
+            consolidate::select_coin_consolidate,
+            dp::select_coin_dp,
+            exhaustive::select_coin_exhaustive,
+            fifo::select_coin_fifo,
+            fixedcount::select_coin_fixed_input_count,
+            greedywaste::select_coin_greedy_waste,
+            knapsack::{select_coin_knapsack, select_coin_knapsack_dp},
+            largestfirst::select_coin_largestfirst,
+            lowestlarger::{select_coin_lowestlarger, select_coin_lowestlarger_knapsack},
+            minchange::select_coin_min_change,
+            mininputs::select_coin_min_inputs,
+            mitm::select_coin_mitm,
+            randomimprove::select_coin_randomimprove,
+            smallestfirst::select_coin_smallestfirst,
+            srd::{select_coin_srd, select_coin_srd_weighted},
+            weighted_random::select_coin_weighted_random,
+        },
+        selectcoin::{
+            select_coin, select_coin_all, select_coin_all_with_rng,
+            select_coin_avoiding_partial_spends, select_coin_excluding, select_coin_feebump,
+            select_coin_sequential, select_coin_sequential_with_rng, select_coin_two_phase,
+            select_coin_with, select_coin_with_required, select_coin_with_rng,
+            INCREMENTAL_RELAY_FEERATE,
+        },
+        types::{
+            Algorithm, CoinSelectionOpt, ExcessStrategy, FeeRate, OutputGroup, SelectionError,
+            SelectionOutput, WasteBreakdown, WeightedUtxo,
+        },
+        utils::{
+            calculate_change, calculate_fee, calculate_selection_fee, calculate_waste,
+            calculate_waste_detailed, filter_spendable, splits_change, MAX_FEERATE,
+            MAX_STANDARD_TX_WEIGHT,
+        },
     };
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use std::collections::HashSet;
 
     fn setup_basic_output_groups() -> Vec<OutputGroup> {
         vec![
@@ -87,18 +675,27 @@ mod test {
                 weight: 100,
                 input_count: 1,
                 creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
             },
             OutputGroup {
                 value: 2000,
                 weight: 200,
                 input_count: 1,
                 creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
             },
             OutputGroup {
                 value: 3000,
                 weight: 300,
                 input_count: 1,
                 creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
             },
         ]
     }
@@ -106,8 +703,8 @@ mod test {
     fn setup_options(target_value: u64) -> CoinSelectionOpt {
         CoinSelectionOpt {
             target_value,
-            target_feerate: 0.4, // Simplified feerate
-            long_term_feerate: Some(0.4),
+            target_feerate: FeeRate::from_sat_per_wu(0.4), // Simplified feerate
+            long_term_feerate: Some(FeeRate::from_sat_per_wu(0.4)),
             min_absolute_fee: 0,
             base_weight: 10,
             change_weight: 50,
@@ -116,6 +713,13 @@ mod test {
             avg_output_weight: 10,
             min_change_value: 500,
             excess_strategy: ExcessStrategy::ToChange,
+            max_inputs: None,
+            bnb_tries: None,
+            include_uneconomical: false,
+            max_excess: None,
+            max_tx_weight: None,
+            knapsack_iterations: None,
+            knapsack_stagnation: None,
         }
     }
 
@@ -127,6 +731,7 @@ mod test {
         assert!(result.is_ok());
         let selection_output = result.unwrap();
         assert!(!selection_output.selected_inputs.is_empty());
+        assert!(selection_output.selected_value >= options.target_value);
     }
 
     #[test]
@@ -146,32 +751,44 @@ mod test {
                 weight: 50,
                 input_count: 1,
                 creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
             },
             OutputGroup {
                 value: 1500,
                 weight: 100,
                 input_count: 1,
                 creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
             },
             OutputGroup {
                 value: 2000,
                 weight: 200,
                 input_count: 1,
                 creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
             },
             OutputGroup {
                 value: 1000,
                 weight: 75,
                 input_count: 1,
                 creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
             },
         ];
 
         // Define the target selection options
         let options = CoinSelectionOpt {
             target_value: 1600, // Target value which lowest_larger can satisfy
-            target_feerate: 0.4,
-            long_term_feerate: Some(0.4),
+            target_feerate: FeeRate::from_sat_per_wu(0.4),
+            long_term_feerate: Some(FeeRate::from_sat_per_wu(0.4)),
             min_absolute_fee: 0,
             base_weight: 10,
             change_weight: 50,
@@ -180,6 +797,13 @@ mod test {
             avg_output_weight: 25,
             min_change_value: 500,
             excess_strategy: ExcessStrategy::ToChange,
+            max_inputs: None,
+            bnb_tries: None,
+            include_uneconomical: false,
+            max_excess: None,
+            max_tx_weight: None,
+            knapsack_iterations: None,
+            knapsack_stagnation: None,
         };
 
         // Call the select_coin function, which should internally use the lowest_larger algorithm
@@ -204,37 +828,52 @@ mod test {
                 weight: 1,
                 input_count: 1,
                 creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
             },
             OutputGroup {
                 value: 2500,
                 weight: 1,
                 input_count: 1,
                 creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
             },
             OutputGroup {
                 value: 3000,
                 weight: 1,
                 input_count: 1,
                 creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
             },
             OutputGroup {
                 value: 1000,
                 weight: 1,
                 input_count: 1,
                 creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
             },
             OutputGroup {
                 value: 500,
                 weight: 1,
                 input_count: 1,
                 creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
             },
         ];
 
         // Define the target selection options
         let options = CoinSelectionOpt {
             target_value: 4000, // Set a target that knapsack can match efficiently
-            target_feerate: 1.0,
+            target_feerate: FeeRate::from_sat_per_wu(1.0),
             min_absolute_fee: 0,
             base_weight: 1,
             change_weight: 1,
@@ -242,8 +881,15 @@ mod test {
             avg_input_weight: 1,
             avg_output_weight: 1,
             min_change_value: 500,
-            long_term_feerate: Some(0.5),
+            long_term_feerate: Some(FeeRate::from_sat_per_wu(0.5)),
             excess_strategy: ExcessStrategy::ToChange,
+            max_inputs: None,
+            bnb_tries: None,
+            include_uneconomical: false,
+            max_excess: None,
+            max_tx_weight: None,
+            knapsack_iterations: None,
+            knapsack_stagnation: None,
         };
 
         let selection_result = select_coin(&inputs, &options).unwrap();
@@ -271,35 +917,50 @@ mod test {
                 weight: 100,
                 input_count: 1,
                 creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
             },
             OutputGroup {
                 value: 250000,
                 weight: 100,
                 input_count: 1,
                 creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
             },
             OutputGroup {
                 value: 300000,
                 weight: 100,
                 input_count: 1,
                 creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
             },
             OutputGroup {
                 value: 100000,
                 weight: 100,
                 input_count: 1,
                 creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
             },
             OutputGroup {
                 value: 50000,
                 weight: 100,
                 input_count: 1,
                 creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
             },
         ];
         let opt = CoinSelectionOpt {
             target_value: 500000,
-            target_feerate: 1.0,
+            target_feerate: FeeRate::from_sat_per_wu(1.0),
             min_absolute_fee: 0,
             base_weight: 100,
             change_weight: 10,
@@ -307,8 +968,15 @@ mod test {
             avg_input_weight: 10,
             avg_output_weight: 10,
             min_change_value: 400,
-            long_term_feerate: Some(0.5),
+            long_term_feerate: Some(FeeRate::from_sat_per_wu(0.5)),
             excess_strategy: ExcessStrategy::ToChange,
+            max_inputs: None,
+            bnb_tries: None,
+            include_uneconomical: false,
+            max_excess: None,
+            max_tx_weight: None,
+            knapsack_iterations: None,
+            knapsack_stagnation: None,
         };
         let ans = select_coin(&inputs, &opt);
 
@@ -334,4 +1002,1261 @@ mod test {
             );
         }
     }
+
+    #[test]
+    fn test_select_coin_with_single_algorithm() {
+        let inputs = setup_basic_output_groups();
+        let options = setup_options(1500);
+        let result = select_coin_with(&inputs, &options, Algorithm::LowestLarger).unwrap();
+        let expected = select_coin_lowestlarger(&inputs, &options).unwrap();
+        assert_eq!(result.selected_inputs, expected.selected_inputs);
+
+        let options = setup_options(2500);
+        let result = select_coin_with(&inputs, &options, Algorithm::Bnb);
+        let expected = select_coin_bnb(&inputs, &options);
+        assert_eq!(result.is_ok(), expected.is_ok());
+
+        let options = setup_options(7000);
+        for algorithm in [Algorithm::Fifo, Algorithm::Srd, Algorithm::BestFit] {
+            let result = select_coin_with(&inputs, &options, algorithm);
+            assert!(matches!(result, Err(SelectionError::InsufficientFunds)));
+        }
+    }
+
+    #[test]
+    fn test_invalid_feerate_is_rejected() {
+        let inputs = setup_basic_output_groups();
+        let algorithms: Vec<CoinSelectionFn<OutputGroup>> = vec![
+            select_coin,
+            |inputs, options| select_coin_with(inputs, options, Algorithm::Fifo),
+            |inputs, options| select_coin_with_required(inputs, options, &[0]),
+            |inputs, options| select_coin_excluding(inputs, options, &HashSet::new()),
+            select_coin_two_phase,
+            |inputs, options| select_coin_feebump(inputs, 1000, 400, options),
+            |inputs, options| select_coin_avoiding_partial_spends(inputs, options, &[]),
+            select_coin_bnb,
+            select_coin_bnb_random,
+            select_coin_bnb_with_change,
+            select_coin_bnb_with_fallback,
+            select_coin_fifo,
+            select_coin_lowestlarger,
+            select_coin_lowestlarger_knapsack,
+            select_coin_srd,
+            select_coin_srd_weighted,
+            select_coin_knapsack,
+            select_coin_knapsack_dp,
+            select_coin_coingrinder,
+            select_coin_largestfirst,
+            select_coin_smallestfirst,
+            select_coin_randomimprove,
+            select_coin_bestfit,
+            select_coin_weighted_random,
+            select_coin_greedy_waste,
+            select_coin_annealing,
+            select_coin_consolidate,
+            select_coin_dp,
+            select_coin_exhaustive,
+            |inputs, options| select_coin_fixed_input_count(inputs, options, 2),
+            select_coin_min_change,
+            select_coin_min_inputs,
+            select_coin_mitm,
+        ];
+        for algorithm in algorithms {
+            for (target_feerate, expected) in [
+                (0.0, SelectionError::NonPositiveFeeRate),
+                (-1.0, SelectionError::NonPositiveFeeRate),
+                (f32::NAN, SelectionError::NonPositiveFeeRate),
+                (2000.0, SelectionError::AbnormallyHighFeeRate),
+            ] {
+                let options = CoinSelectionOpt {
+                    target_feerate: FeeRate::from_sat_per_wu(target_feerate),
+                    ..setup_options(1500)
+                };
+                assert_eq!(algorithm(&inputs, &options).unwrap_err(), expected);
+            }
+        }
+        // The highest accepted feerate still selects
+        let options = CoinSelectionOpt {
+            target_feerate: FeeRate::from_sat_per_wu(MAX_FEERATE),
+            ..setup_options(10)
+        };
+        let inputs = vec![OutputGroup {
+            value: 1_000_000,
+            weight: 100,
+            input_count: 1,
+            creation_sequence: None,
+            ancestor_fee: 0,
+            ancestor_weight: 0,
+            block_height: None,
+        }];
+        assert!(select_coin(&inputs, &options).is_ok());
+    }
+
+    #[test]
+    fn test_waste_drops_dust_change_to_fee() {
+        let options = setup_options(2000);
+        let fee = calculate_fee(300, *options.target_feerate);
+        // 100 sats left over are below the 500 sats minimum change, no change output is created
+        let accumulated_value = options.target_value + fee + 100;
+        assert_eq!(calculate_change(&options, accumulated_value, fee), None);
+        assert_eq!(calculate_waste(&options, accumulated_value, 300, fee), 100);
+
+        // Past the minimum change and the fee for the change output, the excess goes to a change output, which costs
+        // `change_cost`
+        let change_fee = calculate_fee(options.change_weight, *options.target_feerate);
+        let accumulated_value = options.target_value + fee + change_fee + 500;
+        assert_eq!(
+            calculate_change(&options, accumulated_value, fee),
+            Some(500)
+        );
+        assert_eq!(
+            calculate_waste(&options, accumulated_value, 300, fee),
+            options.change_cost as i64
+        );
+    }
+
+    #[test]
+    fn test_waste_breakdown_sums_to_waste() {
+        // Below the long term feerate, the dust change is dropped to the fee
+        let options = CoinSelectionOpt {
+            long_term_feerate: Some(FeeRate::from_sat_per_wu(0.6)),
+            ..setup_options(2000)
+        };
+        let fee = calculate_fee(300, *options.target_feerate);
+        let accumulated_value = options.target_value + fee + 100;
+        let breakdown = calculate_waste_detailed(&options, accumulated_value, 300, fee);
+        assert_eq!(
+            breakdown,
+            WasteBreakdown {
+                feerate_diff: -60,
+                change_cost: 0,
+                excess: 100,
+            }
+        );
+        assert_eq!(breakdown.total(), 40);
+        assert_eq!(
+            breakdown.total(),
+            calculate_waste(&options, accumulated_value, 300, fee)
+        );
+
+        // Past the minimum change and the fee for the change output, the excess goes to a change output
+        let change_fee = calculate_fee(options.change_weight, *options.target_feerate);
+        let accumulated_value = options.target_value + fee + change_fee + 500;
+        let breakdown = calculate_waste_detailed(&options, accumulated_value, 300, fee);
+        assert_eq!(breakdown.change_cost, options.change_cost);
+        assert_eq!(breakdown.excess, 0);
+        assert_eq!(
+            breakdown.total(),
+            calculate_waste(&options, accumulated_value, 300, fee)
+        );
+
+        // The breakdown of every selection adds up to its waste
+        let inputs = setup_basic_output_groups();
+        for excess_strategy in [
+            ExcessStrategy::ToChange,
+            ExcessStrategy::ToFee,
+            ExcessStrategy::Split,
+        ] {
+            let options = CoinSelectionOpt {
+                excess_strategy,
+                long_term_feerate: Some(FeeRate::from_sat_per_wu(0.2)),
+                ..setup_options(2500)
+            };
+            for (_, result) in select_coin_all(&inputs, &options) {
+                if let Ok(selection) = result {
+                    assert_eq!(selection.waste_breakdown.total(), selection.waste.0);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_split_excess_strategy() {
+        let options = CoinSelectionOpt {
+            excess_strategy: ExcessStrategy::Split,
+            ..setup_options(2000)
+        };
+        let fee = calculate_fee(300, *options.target_feerate);
+        let split_fee = calculate_fee(options.change_weight, *options.target_feerate);
+        // After paying for both outputs, 1001 sats are left, enough for two outputs of 500 sats
+        let accumulated_value = options.target_value + fee + 2 * split_fee + 1001;
+        assert!(splits_change(&options, accumulated_value, fee));
+        assert_eq!(
+            calculate_change(&options, accumulated_value, fee),
+            Some(1001)
+        );
+        assert_eq!(
+            calculate_waste(&options, accumulated_value, 300, fee),
+            2 * options.change_cost as i64
+        );
+
+        // One sat less falls back to a single change output
+        let accumulated_value = accumulated_value - 1;
+        assert!(!splits_change(&options, accumulated_value, fee));
+        assert_eq!(
+            calculate_change(&options, accumulated_value, fee),
+            Some(1000 + split_fee)
+        );
+        assert_eq!(
+            calculate_waste(&options, accumulated_value, 300, fee),
+            options.change_cost as i64
+        );
+
+        // The largest input leaves plenty of change to split
+        let inputs = setup_basic_output_groups();
+        let options = CoinSelectionOpt {
+            excess_strategy: ExcessStrategy::Split,
+            ..setup_options(1000)
+        };
+        let result = select_coin_with(&inputs, &options, Algorithm::LargestFirst).unwrap();
+        assert_eq!(result.selected_inputs, vec![2]);
+        assert_eq!(result.change_value, Some(3000 - 1000 - 125 - 2 * split_fee));
+        assert_eq!(result.waste.0, 2 * options.change_cost as i64);
+    }
+
+    #[test]
+    fn test_select_coin_ranks_bnb_by_real_fee() {
+        let inputs: Vec<OutputGroup> = [6000, 4000, 2500, 3000, 1200]
+            .iter()
+            .map(|&value| OutputGroup {
+                value,
+                weight: 100,
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            })
+            .collect();
+        let options = CoinSelectionOpt {
+            long_term_feerate: Some(FeeRate::from_sat_per_wu(0.2)),
+            excess_strategy: ExcessStrategy::ToFee,
+            ..setup_options(9911)
+        };
+        // BnB pays for the base weight and the weight of its inputs like every other algorithm
+        let bnb = select_coin_with(&inputs, &options, Algorithm::Bnb).unwrap();
+        let fee = calculate_fee(
+            options.base_weight + bnb.selected_weight,
+            *options.target_feerate,
+        );
+        assert_eq!(bnb.estimated_fee, fee);
+        assert_eq!(
+            bnb.waste.0,
+            calculate_waste(&options, bnb.selected_value, bnb.selected_weight, fee)
+        );
+
+        let best = select_coin(&inputs, &options).unwrap();
+        for algorithm in [
+            Algorithm::Bnb,
+            Algorithm::Fifo,
+            Algorithm::LowestLarger,
+            Algorithm::CoinGrinder,
+            Algorithm::LargestFirst,
+            Algorithm::SmallestFirst,
+            Algorithm::BestFit,
+            Algorithm::GreedyWaste,
+        ] {
+            if let Ok(result) = select_coin_with(&inputs, &options, algorithm) {
+                assert!(best.waste.0 <= result.waste.0, "{:?}", algorithm);
+            }
+        }
+    }
+
+    #[test]
+    fn test_filter_spendable() {
+        // At 0.5 sats/wu, spending 100 weight units costs 50 sats
+        let inputs: Vec<OutputGroup> = [2000, 50, 51, 10, 1000]
+            .iter()
+            .map(|&value| OutputGroup {
+                value,
+                weight: 100,
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            })
+            .collect();
+        let options = CoinSelectionOpt {
+            target_feerate: FeeRate::from_sat_per_wu(0.5),
+            ..setup_options(2200)
+        };
+        let spendable = filter_spendable(&inputs, &options);
+        // The input whose fee equals its value is dropped, the one worth a sat more is kept
+        let indices: Vec<usize> = spendable.iter().map(|(index, _)| *index).collect();
+        assert_eq!(indices, vec![0, 2, 4]);
+        for (index, input) in &spendable {
+            assert_eq!(input.value, inputs[*index].value);
+        }
+
+        // Indices of a selection over the survivors map back to the original inputs
+        let survivors: Vec<OutputGroup> =
+            spendable.iter().map(|(_, input)| input.clone()).collect();
+        let selection =
+            select_coin_with(&survivors, &setup_options(2200), Algorithm::LargestFirst).unwrap();
+        let mut selected: Vec<usize> = selection
+            .selected_inputs
+            .iter()
+            .map(|&i| spendable[i].0)
+            .collect();
+        selected.sort();
+        assert_eq!(selected, vec![0, 4]);
+
+        // Unconfirmed ancestors lacking fees lower the effective value too
+        let inputs = vec![OutputGroup {
+            value: 100,
+            weight: 100,
+            input_count: 1,
+            creation_sequence: None,
+            ancestor_fee: 0,
+            ancestor_weight: 100,
+            block_height: None,
+        }];
+        assert!(filter_spendable(&inputs, &options).is_empty());
+
+        // Every input is kept when the uneconomical ones are included
+        let options = CoinSelectionOpt {
+            include_uneconomical: true,
+            ..options
+        };
+        assert_eq!(filter_spendable(&inputs, &options).len(), 1);
+    }
+
+    #[test]
+    fn test_select_coin_skips_uneconomical_inputs() {
+        // Five inputs worth 30 sats, each costing 40 sats to spend
+        let mut inputs: Vec<OutputGroup> = (0..5)
+            .map(|_| OutputGroup {
+                value: 30,
+                weight: 100,
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            })
+            .collect();
+        inputs.push(OutputGroup {
+            value: 990,
+            weight: 100,
+            input_count: 1,
+            creation_sequence: None,
+            ancestor_fee: 0,
+            ancestor_weight: 0,
+            block_height: None,
+        });
+        // At face value the 1140 sats would cover 1000 sats and the 40 sats fee of the largest input
+        let result = select_coin(&inputs, &setup_options(500));
+        assert!(matches!(result, Err(SelectionError::InsufficientFunds)));
+
+        inputs.push(OutputGroup {
+            value: 2000,
+            weight: 100,
+            input_count: 1,
+            creation_sequence: None,
+            ancestor_fee: 0,
+            ancestor_weight: 0,
+            block_height: None,
+        });
+        let mut options = setup_options(1000);
+        for algorithm in [
+            Algorithm::Bnb,
+            Algorithm::Fifo,
+            Algorithm::LowestLarger,
+            Algorithm::Srd,
+            Algorithm::Knapsack,
+            Algorithm::CoinGrinder,
+            Algorithm::LargestFirst,
+            Algorithm::SmallestFirst,
+            Algorithm::RandomImprove,
+            Algorithm::BestFit,
+            Algorithm::WeightedRandom,
+            Algorithm::GreedyWaste,
+        ] {
+            if let Ok(result) = select_coin_with(&inputs, &options, algorithm) {
+                assert!(
+                    result.selected_inputs.iter().all(|&i| i >= 5),
+                    "{:?}",
+                    algorithm
+                );
+            }
+        }
+        let result = select_coin(&inputs, &options).unwrap();
+        assert!(result.selected_inputs.iter().all(|&i| i >= 5));
+
+        // Sweeping the dust on purpose
+        options.include_uneconomical = true;
+        let result = select_coin_with(&inputs, &options, Algorithm::Fifo).unwrap();
+        assert_eq!(result.selected_inputs, vec![0, 1, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn test_select_coin_with_rng_is_reproducible() {
+        let mut pool_rng = StdRng::seed_from_u64(1);
+        let inputs: Vec<OutputGroup> = (0..40)
+            .map(|_| OutputGroup {
+                value: pool_rng.gen_range(1000..50_000),
+                weight: pool_rng.gen_range(100..400),
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            })
+            .collect();
+        let options = setup_options(120_000);
+
+        for algorithm in Algorithm::ALL {
+            let first = run_algorithm(algorithm, &inputs, &options, &mut StdRng::seed_from_u64(7));
+            let second = run_algorithm(algorithm, &inputs, &options, &mut StdRng::seed_from_u64(7));
+            assert_eq!(
+                format!("{:?}", first),
+                format!("{:?}", second),
+                "{:?}",
+                algorithm
+            );
+        }
+
+        let first = select_coin_with_rng(&inputs, &options, &mut StdRng::seed_from_u64(7));
+        for _ in 0..5 {
+            let again = select_coin_with_rng(&inputs, &options, &mut StdRng::seed_from_u64(7));
+            assert_eq!(format!("{:?}", first), format!("{:?}", again));
+        }
+    }
+
+    #[test]
+    fn test_select_coin_all_ranked_by_waste() {
+        let inputs = setup_basic_output_groups();
+        let options = setup_options(2500);
+        let results = select_coin_all(&inputs, &options);
+
+        // One entry per algorithm
+        assert_eq!(results.len(), Algorithm::ALL.len());
+        for algorithm in Algorithm::ALL {
+            assert_eq!(
+                results
+                    .iter()
+                    .filter(|(other, _)| *other == algorithm)
+                    .count(),
+                1
+            );
+        }
+
+        // Successful selections by ascending waste, then the errors
+        let first_error = results
+            .iter()
+            .position(|(_, result)| result.is_err())
+            .unwrap_or(results.len());
+        assert!(first_error > 0);
+        assert!(results[first_error..]
+            .iter()
+            .all(|(_, result)| result.is_err()));
+        let wastes: Vec<i64> = results[..first_error]
+            .iter()
+            .map(|(_, result)| result.as_ref().unwrap().waste.0)
+            .collect();
+        assert!(wastes.windows(2).all(|pair| pair[0] <= pair[1]));
+
+        // select_coin picks the head of the ranking
+        let ranked = select_coin_all_with_rng(&inputs, &options, &mut StdRng::seed_from_u64(7));
+        let best = select_coin_with_rng(&inputs, &options, &mut StdRng::seed_from_u64(7));
+        assert_eq!(format!("{:?}", ranked[0].1), format!("{:?}", best));
+
+        // Every algorithm fails when the inputs can not cover the target
+        let results = select_coin_all(&inputs, &setup_options(7000));
+        assert_eq!(results.len(), Algorithm::ALL.len());
+        assert!(results.iter().all(|(_, result)| result.is_err()));
+    }
+
+    #[test]
+    fn test_select_coin_all_sorts_selected_inputs() {
+        // Listed from the largest, so that selecting in value or random order does not sort by chance
+        let inputs: Vec<OutputGroup> = (1..=12)
+            .rev()
+            .map(|i| OutputGroup {
+                value: i * 1000 + 37 * i * i,
+                weight: 100,
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            })
+            .collect();
+        let results = select_coin_all(&inputs, &setup_options(25_000));
+        let selections: Vec<&SelectionOutput> = results
+            .iter()
+            .filter_map(|(_, result)| result.as_ref().ok())
+            .collect();
+        assert!(selections
+            .iter()
+            .any(|selection| selection.selected_inputs.len() > 2));
+        for selection in selections {
+            assert!(selection
+                .selected_inputs
+                .windows(2)
+                .all(|pair| pair[0] < pair[1]));
+        }
+    }
+
+    #[test]
+    fn test_select_coin_all_estimate_the_same_fee() {
+        let inputs = setup_basic_output_groups();
+        let options = CoinSelectionOpt {
+            long_term_feerate: Some(FeeRate::from_sat_per_wu(0.2)),
+            ..setup_options(1500)
+        };
+        let mut succeeded = 0;
+        for (algorithm, result) in select_coin_all(&inputs, &options) {
+            if let Ok(selection) = result {
+                // Every algorithm pays for the base weight, so that their wastes compare
+                assert_eq!(
+                    selection.estimated_fee,
+                    calculate_selection_fee(&options, selection.selected_weight),
+                    "{:?}",
+                    algorithm
+                );
+                succeeded += 1;
+            }
+        }
+        assert!(succeeded > 10);
+    }
+
+    #[test]
+    fn test_select_coin_survives_panicking_algorithm() {
+        let inputs = setup_basic_output_groups();
+        let options = setup_options(2500);
+        let mut rng = StdRng::seed_from_u64(7);
+
+        // On a thread each or in turn, every algorithm but LargestFirst panics
+        for sequential in [false, true] {
+            let results = run_all_algorithms(
+                &inputs,
+                &options,
+                &mut rng,
+                sequential,
+                |algorithm, inputs, options, rng| {
+                    if algorithm != Algorithm::LargestFirst {
+                        panic!("fake {:?} failure", algorithm);
+                    }
+                    run_algorithm(algorithm, inputs, options, rng)
+                },
+            );
+            assert_eq!(results.len(), Algorithm::ALL.len());
+            assert_eq!(results[0].0, Algorithm::LargestFirst);
+            assert!(results[1..]
+                .iter()
+                .all(|(_, result)| matches!(result, Err(SelectionError::AlgorithmPanicked))));
+            let selection = best_selection(results).unwrap();
+            assert_eq!(
+                selection,
+                select_coin_largestfirst(&inputs, &options).unwrap()
+            );
+
+            let results =
+                run_all_algorithms(&inputs, &options, &mut rng, sequential, |_, _, _, _| {
+                    panic!("fake failure")
+                });
+            assert_eq!(
+                best_selection(results),
+                Err(SelectionError::AlgorithmPanicked)
+            );
+        }
+    }
+
+    #[test]
+    fn test_select_coin_sequential_matches_threaded() {
+        let mut pool_rng = StdRng::seed_from_u64(3);
+        let inputs: Vec<OutputGroup> = (0..40)
+            .map(|_| OutputGroup {
+                value: pool_rng.gen_range(1000..50_000),
+                weight: pool_rng.gen_range(100..400),
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            })
+            .collect();
+
+        for target in [5_000, 120_000, 400_000] {
+            let options = setup_options(target);
+            let sequential =
+                select_coin_sequential_with_rng(&inputs, &options, &mut StdRng::seed_from_u64(7));
+            let threaded = select_coin_with_rng(&inputs, &options, &mut StdRng::seed_from_u64(7));
+            assert_eq!(sequential, threaded, "{target}");
+        }
+        assert!(select_coin_sequential(&inputs, &setup_options(120_000)).is_ok());
+        assert!(matches!(
+            select_coin_sequential(&inputs, &setup_options(2_000_000)),
+            Err(SelectionError::InsufficientFunds)
+        ));
+    }
+
+    #[test]
+    fn test_selected_value_and_weight() {
+        let inputs = setup_basic_output_groups();
+        let options = setup_options(2500);
+        for algorithm in [
+            Algorithm::Bnb,
+            Algorithm::Fifo,
+            Algorithm::LowestLarger,
+            Algorithm::Srd,
+            Algorithm::Knapsack,
+            Algorithm::CoinGrinder,
+            Algorithm::LargestFirst,
+            Algorithm::SmallestFirst,
+            Algorithm::RandomImprove,
+            Algorithm::BestFit,
+            Algorithm::WeightedRandom,
+            Algorithm::GreedyWaste,
+        ] {
+            if let Ok(result) = select_coin_with(&inputs, &options, algorithm) {
+                let selected = result.selected_inputs.iter().map(|&i| &inputs[i]);
+                assert_eq!(
+                    result.selected_value,
+                    selected.clone().map(|input| input.value).sum::<u64>()
+                );
+                assert_eq!(
+                    result.selected_weight,
+                    selected.map(|input| input.weight).sum::<u64>()
+                );
+                assert!(result.selected_value >= options.target_value);
+            }
+        }
+    }
+
+    #[test]
+    fn test_select_coin_custom_input_type() {
+        struct WalletUtxo {
+            amount: u64,
+            satisfaction_weight: u64,
+        }
+
+        impl WeightedUtxo for WalletUtxo {
+            fn value(&self) -> u64 {
+                self.amount
+            }
+
+            fn weight(&self) -> u64 {
+                self.satisfaction_weight
+            }
+
+            fn input_count(&self) -> usize {
+                1
+            }
+
+            fn creation_sequence(&self) -> Option<u32> {
+                None
+            }
+        }
+
+        let inputs = setup_basic_output_groups();
+        let utxos: Vec<WalletUtxo> = inputs
+            .iter()
+            .map(|input| WalletUtxo {
+                amount: input.value,
+                satisfaction_weight: input.weight,
+            })
+            .collect();
+        let options = setup_options(1500);
+        let result = select_coin_with(&utxos, &options, Algorithm::LowestLarger).unwrap();
+        let expected = select_coin_with(&inputs, &options, Algorithm::LowestLarger).unwrap();
+        assert_eq!(result.selected_inputs, expected.selected_inputs);
+        assert!(select_coin(&utxos, &options).is_ok());
+    }
+
+    #[test]
+    fn test_select_coin_with_required_inputs() {
+        let mut inputs = setup_basic_output_groups();
+        inputs.push(OutputGroup {
+            value: 1_000_000,
+            weight: 100,
+            input_count: 1,
+            creation_sequence: None,
+            ancestor_fee: 0,
+            ancestor_weight: 0,
+            block_height: None,
+        });
+        // The required input is far larger than the target, it is spent anyway
+        let options = setup_options(1500);
+        let result = select_coin_with_required(&inputs, &options, &[3]).unwrap();
+        assert_eq!(result.selected_inputs, vec![3]);
+
+        // The required input covers part of the target, the others make up for the rest
+        let options = setup_options(3500);
+        let result = select_coin_with_required(&inputs[..3], &options, &[0]).unwrap();
+        assert_eq!(result.selected_inputs[0], 0);
+        assert!(result.selected_inputs.len() > 1);
+        assert!(result.selected_value >= options.target_value + options.min_change_value);
+
+        let options = setup_options(7000);
+        let result = select_coin_with_required(&inputs[..3], &options, &[0]);
+        assert!(matches!(result, Err(SelectionError::InsufficientFunds)));
+
+        // The required inputs count towards the maximum number of inputs
+        let options = CoinSelectionOpt {
+            max_inputs: Some(1),
+            ..setup_options(3500)
+        };
+        let result = select_coin_with_required(&inputs, &options, &[0, 3]);
+        assert!(matches!(result, Err(SelectionError::NoSolutionFound)));
+        let result = select_coin_with_required(&inputs[..3], &options, &[0]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_select_coin_with_required_pays_fees() {
+        let mut inputs = setup_basic_output_groups();
+        // Worth less than the fee for spending it
+        inputs.push(OutputGroup {
+            value: 100,
+            weight: 2000,
+            input_count: 1,
+            creation_sequence: None,
+            ancestor_fee: 0,
+            ancestor_weight: 0,
+            block_height: None,
+        });
+        let options = setup_options(4000);
+        let result = select_coin_with_required(&inputs, &options, &[3]).unwrap();
+        assert!(result.selected_inputs.contains(&3));
+        assert!(
+            result.selected_value
+                >= options.target_value + options.min_change_value + result.estimated_fee
+        );
+
+        // The required input covers the target and its own fee, not the minimum absolute fee
+        let options = CoinSelectionOpt {
+            min_absolute_fee: 1000,
+            ..setup_options(2200)
+        };
+        let result = select_coin_with_required(&inputs, &options, &[2]).unwrap();
+        assert!(result.selected_inputs.len() > 1);
+        assert!(
+            result.selected_value
+                >= options.target_value + result.estimated_fee.max(options.min_absolute_fee)
+        );
+    }
+
+    #[test]
+    fn test_select_coin_max_inputs() {
+        let inputs = setup_basic_output_groups();
+        // The two largest inputs cover the target
+        let options = CoinSelectionOpt {
+            max_inputs: Some(2),
+            ..setup_options(3500)
+        };
+        let result = select_coin(&inputs, &options).unwrap();
+        assert!(result.selected_inputs.len() <= 2);
+
+        let options = CoinSelectionOpt {
+            max_inputs: Some(1),
+            ..setup_options(3500)
+        };
+        assert!(select_coin(&inputs, &options).is_err());
+    }
+
+    #[test]
+    fn test_accumulative_fee_includes_base_and_change_weight() {
+        // 20 sat/vB, a P2WPKH recipient and change output and their 11 vB of transaction overhead
+        let options = CoinSelectionOpt {
+            target_feerate: FeeRate::from_sat_per_vb(20.0),
+            long_term_feerate: Some(FeeRate::from_sat_per_vb(20.0)),
+            base_weight: 4 * (11 + 31),
+            change_weight: 4 * 31,
+            change_cost: 500,
+            min_change_value: 1000,
+            ..setup_options(50_000)
+        };
+        let setup_input = |value| {
+            vec![OutputGroup {
+                value,
+                weight: 272,
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            }]
+        };
+        let algorithms: [(&str, CoinSelectionFn<OutputGroup>); 3] = [
+            ("fifo", select_coin_fifo),
+            ("srd", select_coin_srd),
+            ("lowestlarger", select_coin_lowestlarger),
+        ];
+        // The input pays for itself and the minimum change, but not for the 840 sats of base weight and the 620 sats
+        // of change output
+        let inputs = setup_input(50_000 + 1360 + 1000 + 800);
+        for (name, algorithm) in algorithms {
+            let result = algorithm(&inputs, &options);
+            assert!(
+                matches!(result, Err(SelectionError::InsufficientFunds)),
+                "{name}"
+            );
+        }
+
+        let inputs = setup_input(50_000 + 1360 + 840 + 620 + 1000);
+        for (name, algorithm) in algorithms {
+            let result = algorithm(&inputs, &options).unwrap();
+            assert_eq!(result.estimated_fee, 1360 + 840, "{name}");
+            // Once the change output is paid for, the minimum change is left
+            assert_eq!(result.change_value, Some(1000), "{name}");
+        }
+    }
+
+    #[test]
+    fn test_accumulated_value_overflow() {
+        // Neither input covers the target on its own, both together are worth more than `u64::MAX`
+        let inputs: Vec<OutputGroup> = [u64::MAX / 2 + 1000, u64::MAX / 2 + 2000]
+            .into_iter()
+            .map(|value| OutputGroup {
+                value,
+                weight: 100,
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            })
+            .collect();
+        let options = setup_options(u64::MAX / 2 + 5000);
+        let algorithms: Vec<(&str, CoinSelectionFn<OutputGroup>)> = vec![
+            ("fifo", select_coin_fifo),
+            ("largestfirst", select_coin_largestfirst),
+            ("srd", select_coin_srd),
+            ("lowestlarger", select_coin_lowestlarger),
+            ("knapsack", select_coin_knapsack),
+            ("dp", select_coin_dp),
+            ("knapsack_dp", select_coin_knapsack_dp),
+            ("coingrinder", select_coin_coingrinder),
+            ("bestfit", select_coin_bestfit),
+            ("greedy_waste", select_coin_greedy_waste),
+            ("min_change", select_coin_min_change),
+            ("min_inputs", select_coin_min_inputs),
+            ("exhaustive", select_coin_exhaustive),
+            ("mitm", select_coin_mitm),
+            ("randomimprove", select_coin_randomimprove),
+            ("annealing", select_coin_annealing),
+            ("weighted_random", select_coin_weighted_random),
+            ("fixed_input_count", |inputs, options| {
+                select_coin_fixed_input_count(inputs, options, 2)
+            }),
+            ("with_required", |inputs, options| {
+                select_coin_with_required(inputs, options, &[0, 1])
+            }),
+            ("avoiding_partial_spends", |inputs, options| {
+                select_coin_avoiding_partial_spends(inputs, options, &[vec![0, 1]])
+            }),
+        ];
+        for (name, algorithm) in algorithms {
+            assert_eq!(
+                algorithm(&inputs, &options),
+                Err(SelectionError::ArithmeticOverflow),
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_target_overflow() {
+        // The inputs sum within a `u64`, the target and the fee do not
+        let inputs: Vec<OutputGroup> = [1000, u64::MAX / 2]
+            .into_iter()
+            .map(|value| OutputGroup {
+                value,
+                weight: 100,
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            })
+            .collect();
+        let options = setup_options(u64::MAX - 10);
+        let algorithms: Vec<(&str, CoinSelectionFn<OutputGroup>)> = vec![
+            ("fifo", select_coin_fifo),
+            ("srd", select_coin_srd),
+            ("lowestlarger", select_coin_lowestlarger),
+            ("knapsack", select_coin_knapsack),
+            ("dp", select_coin_dp),
+            ("knapsack_dp", select_coin_knapsack_dp),
+            ("coingrinder", select_coin_coingrinder),
+            ("largestfirst", select_coin_largestfirst),
+            ("smallestfirst", select_coin_smallestfirst),
+            ("bestfit", select_coin_bestfit),
+            ("greedy_waste", select_coin_greedy_waste),
+            ("min_change", select_coin_min_change),
+            ("min_inputs", select_coin_min_inputs),
+            ("exhaustive", select_coin_exhaustive),
+            ("mitm", select_coin_mitm),
+            ("randomimprove", select_coin_randomimprove),
+            ("annealing", select_coin_annealing),
+            ("weighted_random", select_coin_weighted_random),
+            ("fixed_input_count", |inputs, options| {
+                select_coin_fixed_input_count(inputs, options, 2)
+            }),
+        ];
+        for (name, algorithm) in algorithms {
+            assert_eq!(
+                algorithm(&inputs, &options),
+                Err(SelectionError::ArithmeticOverflow),
+                "{name}"
+            );
+        }
+        assert_eq!(
+            select_coin(&inputs, &options),
+            Err(SelectionError::ArithmeticOverflow)
+        );
+    }
+
+    #[test]
+    fn test_select_coin_max_tx_weight() {
+        // A sweep of 5000 tiny P2WPKH inputs, the target needs more than 500 of them
+        let inputs: Vec<OutputGroup> = (0..5000)
+            .map(|_| OutputGroup {
+                value: 1000,
+                weight: 272,
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 0,
+                block_height: None,
+            })
+            .collect();
+        let options = CoinSelectionOpt {
+            max_tx_weight: Some(100_000),
+            ..setup_options(500_000)
+        };
+        for algorithm in Algorithm::ALL {
+            let result = select_coin_with(&inputs, &options, algorithm);
+            assert!(
+                matches!(
+                    result,
+                    Err(SelectionError::NoSolutionFound | SelectionError::MaxWeightExceeded)
+                ),
+                "{algorithm:?}"
+            );
+        }
+        let result = select_coin(&inputs, &options);
+        assert!(matches!(result, Err(SelectionError::NoSolutionFound)));
+
+        // The standard limit leaves room for the sweep
+        let options = CoinSelectionOpt {
+            max_tx_weight: Some(MAX_STANDARD_TX_WEIGHT),
+            ..setup_options(500_000)
+        };
+        let result = select_coin_with(&inputs, &options, Algorithm::LargestFirst).unwrap();
+        assert!(
+            options.base_weight + result.selected_weight + options.change_weight
+                <= MAX_STANDARD_TX_WEIGHT
+        );
+    }
+
+    #[test]
+    fn test_select_coin_excluding_inputs() {
+        let inputs = setup_basic_output_groups();
+        let options = setup_options(1500);
+        for excluded in [[0, 1], [1, 2], [0, 2]] {
+            let excluded_inputs: HashSet<usize> = excluded.into_iter().collect();
+            let result = select_coin_excluding(&inputs, &options, &excluded_inputs);
+            if let Ok(selection) = result {
+                assert!(selection
+                    .selected_inputs
+                    .iter()
+                    .all(|index| !excluded_inputs.contains(index)));
+            }
+        }
+
+        // Only the last input is left, and it covers the target
+        let excluded_inputs: HashSet<usize> = [0, 1].into_iter().collect();
+        let result = select_coin_excluding(&inputs, &options, &excluded_inputs).unwrap();
+        assert_eq!(result.selected_inputs, vec![2]);
+
+        // The target needs every input
+        let options = setup_options(4500);
+        let excluded_inputs: HashSet<usize> = [1].into_iter().collect();
+        let result = select_coin_excluding(&inputs, &options, &excluded_inputs);
+        assert!(matches!(result, Err(SelectionError::InsufficientFunds)));
+    }
+
+    #[test]
+    fn test_select_coin_avoiding_partial_spends() {
+        let mut inputs = setup_basic_output_groups();
+        inputs.push(OutputGroup {
+            value: 500,
+            weight: 100,
+            input_count: 1,
+            creation_sequence: None,
+            ancestor_fee: 0,
+            ancestor_weight: 0,
+            block_height: None,
+        });
+        // The last input alone would do, but it shares its address with the first one
+        let options = setup_options(2000);
+        let clusters = vec![vec![0, 3]];
+        let result = select_coin_avoiding_partial_spends(&inputs, &options, &clusters).unwrap();
+        let selected: HashSet<usize> = result.selected_inputs.iter().copied().collect();
+        assert_eq!(selected.contains(&0), selected.contains(&3));
+        assert_eq!(
+            result.selected_value,
+            result
+                .selected_inputs
+                .iter()
+                .map(|&i| inputs[i].value)
+                .sum::<u64>()
+        );
+
+        let mut rng = StdRng::seed_from_u64(3);
+        for _ in 0..20 {
+            let inputs: Vec<OutputGroup> = (0..12)
+                .map(|_| OutputGroup {
+                    value: rng.gen_range(500..5000),
+                    weight: rng.gen_range(50..300),
+                    input_count: 1,
+                    creation_sequence: None,
+                    ancestor_fee: 0,
+                    ancestor_weight: 0,
+                    block_height: None,
+                })
+                .collect();
+            let clusters = vec![vec![0, 5, 7], vec![2, 3], vec![11, 1]];
+            let options = setup_options(rng.gen_range(1000..20_000));
+            if let Ok(result) = select_coin_avoiding_partial_spends(&inputs, &options, &clusters) {
+                // The clusters are listed out of order, the indices are sorted back
+                assert!(result
+                    .selected_inputs
+                    .windows(2)
+                    .all(|pair| pair[0] < pair[1]));
+                let selected: HashSet<usize> = result.selected_inputs.iter().copied().collect();
+                for cluster in &clusters {
+                    let spent = cluster.iter().filter(|i| selected.contains(i)).count();
+                    assert!(spent == 0 || spent == cluster.len());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_select_coin_two_phase() {
+        // The last input has no block height, it is unconfirmed
+        let mut inputs = setup_basic_output_groups();
+        inputs[0].block_height = Some(800_000);
+        inputs[1].block_height = Some(800_001);
+
+        // The confirmed inputs suffice, the unconfirmed one is never spent
+        let options = setup_options(1500);
+        for _ in 0..10 {
+            let result = select_coin_two_phase(&inputs, &options).unwrap();
+            assert!(!result.selected_inputs.contains(&2));
+        }
+
+        // The confirmed inputs fall short, the unconfirmed one makes up for it
+        let options = setup_options(3000);
+        let result = select_coin_two_phase(&inputs, &options).unwrap();
+        assert!(result.selected_inputs.contains(&2));
+        assert!(result.selected_value >= options.target_value + options.min_change_value);
+
+        let options = setup_options(7000);
+        let result = select_coin_two_phase(&inputs, &options);
+        assert!(matches!(result, Err(SelectionError::InsufficientFunds)));
+
+        // Without any confirmed input, the unconfirmed ones are spent
+        let inputs = setup_basic_output_groups();
+        let options = setup_options(1500);
+        assert!(select_coin_two_phase(&inputs, &options).is_ok());
+    }
+
+    #[test]
+    fn test_select_coin_rejects_empty_inputs_and_zero_target() {
+        let inputs = setup_basic_output_groups();
+        let selections: Vec<CoinSelectionFn<OutputGroup>> = vec![
+            select_coin,
+            select_coin_sequential,
+            |inputs, options| select_coin_with(inputs, options, Algorithm::Fifo),
+            |inputs, options| select_coin_with_required(inputs, options, &[]),
+            |inputs, options| select_coin_excluding(inputs, options, &HashSet::new()),
+            |inputs, options| select_coin_avoiding_partial_spends(inputs, options, &[]),
+        ];
+        for selection in selections {
+            assert_eq!(
+                selection(&[], &setup_options(1500)).unwrap_err(),
+                SelectionError::EmptyInputs
+            );
+            assert_eq!(
+                selection(&inputs, &setup_options(0)).unwrap_err(),
+                SelectionError::InvalidOptions
+            );
+            assert!(selection(&inputs, &setup_options(1500)).is_ok());
+        }
+
+        // Excluding every input leaves none to select from
+        let excluded_inputs: HashSet<usize> = [0, 1, 2].into_iter().collect();
+        let result = select_coin_excluding(&inputs, &setup_options(1500), &excluded_inputs);
+        assert_eq!(result.unwrap_err(), SelectionError::EmptyInputs);
+    }
+
+    #[test]
+    fn test_select_coin_pays_for_ancestors() {
+        // The first input is unconfirmed, its parent is large and pays no fee at all
+        let mut inputs = setup_basic_output_groups();
+        inputs.splice(
+            0..0,
+            [OutputGroup {
+                value: 6000,
+                weight: 100,
+                input_count: 1,
+                creation_sequence: None,
+                ancestor_fee: 0,
+                ancestor_weight: 10_000,
+                block_height: None,
+            }],
+        );
+        let options = setup_options(2000);
+        let bump_fee = calculate_fee(10_000, *options.target_feerate);
+        for _ in 0..10 {
+            let result = select_coin(&inputs, &options).unwrap();
+            assert!(!result.selected_inputs.contains(&0));
+        }
+        // Ignoring its ancestors, the unconfirmed input is the largest one
+        let result = select_coin_with(&inputs, &options, Algorithm::LargestFirst).unwrap();
+        assert_eq!(result.selected_inputs, vec![3]);
+
+        // The confirmed inputs fall short, the unconfirmed one is spent and its parent paid for
+        let options = setup_options(7000);
+        let result = select_coin(&inputs, &options).unwrap();
+        assert!(result.selected_inputs.contains(&0));
+        assert!(result.estimated_fee > bump_fee);
+        assert!(
+            result.selected_value
+                >= options.target_value + options.min_change_value + result.estimated_fee
+        );
+    }
+
+    #[test]
+    fn test_sorted_algorithms_pay_for_ancestors() {
+        // Every input is unconfirmed, called directly the algorithms pay for the ancestors as the wrapper does
+        let inputs: Vec<OutputGroup> = setup_basic_output_groups()
+            .into_iter()
+            .map(|input| OutputGroup {
+                ancestor_fee: 100,
+                ancestor_weight: 1000,
+                ..input
+            })
+            .collect();
+        let options = setup_options(2000);
+        let algorithms: [(Algorithm, CoinSelectionFn<OutputGroup>); 3] = [
+            (Algorithm::Fifo, select_coin_fifo),
+            (Algorithm::LargestFirst, select_coin_largestfirst),
+            (Algorithm::SmallestFirst, select_coin_smallestfirst),
+        ];
+        for (algorithm, select) in algorithms {
+            let result = select(&inputs, &options).unwrap();
+            let bump_fee = result.selected_inputs.len() as u64
+                * (calculate_fee(1000, *options.target_feerate) - 100);
+            assert_eq!(
+                result.estimated_fee,
+                calculate_selection_fee(&options, result.selected_weight) + bump_fee
+            );
+            assert_eq!(result.waste_breakdown.feerate_diff, bump_fee as i64);
+            assert_eq!(
+                result,
+                select_coin_with(&inputs, &options, algorithm).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_select_coin_feebump() {
+        let inputs = setup_basic_output_groups();
+        let (original_weight, original_fee) = (1000, 400);
+        for (target_feerate, excess_strategy) in
+            [0.41, 0.5, 1.0, 2.0].into_iter().flat_map(|rate| {
+                [
+                    (rate, ExcessStrategy::ToChange),
+                    (rate, ExcessStrategy::ToFee),
+                ]
+            })
+        {
+            let options = CoinSelectionOpt {
+                target_feerate: FeeRate::from_sat_per_wu(target_feerate),
+                excess_strategy,
+                ..setup_options(0)
+            };
+            let result =
+                select_coin_feebump(&inputs, original_weight, original_fee, &options).unwrap();
+            let (change_weight, change_fee) = match result.change_value {
+                Some(_) => (
+                    options.change_weight,
+                    calculate_fee(options.change_weight, target_feerate),
+                ),
+                None => (0, 0),
+            };
+            let total_weight = original_weight + result.selected_weight + change_weight;
+            // The added inputs pay for themselves and the change, the replacement reaches the target feerate
+            assert!(result.estimated_fee as f32 / total_weight as f32 >= target_feerate);
+            assert!(
+                result.estimated_fee
+                    >= original_fee + calculate_fee(total_weight, INCREMENTAL_RELAY_FEERATE)
+            );
+            if matches!(options.excess_strategy, ExcessStrategy::ToChange) {
+                assert_eq!(
+                    result.selected_value,
+                    result.estimated_fee - original_fee + result.change_value.unwrap_or(0)
+                );
+            }
+            // The waste is that of the final fee, not of the selection made for the shortfall
+            let waste_options = CoinSelectionOpt {
+                target_value: 0,
+                min_absolute_fee: 0,
+                ..options.clone()
+            };
+            let waste_breakdown = calculate_waste_detailed(
+                &waste_options,
+                result.selected_value,
+                result.selected_weight,
+                result.estimated_fee - original_fee - change_fee,
+            );
+            assert_eq!(result.waste_breakdown, waste_breakdown);
+            assert_eq!(result.waste.0, waste_breakdown.total());
+        }
+
+        // Barely above the original feerate, the incremental relay fee sets the bump
+        let options = CoinSelectionOpt {
+            target_feerate: FeeRate::from_sat_per_wu(0.41),
+            ..setup_options(0)
+        };
+        let result = select_coin_feebump(&inputs, original_weight, original_fee, &options).unwrap();
+        let total_weight = original_weight + result.selected_weight;
+        assert!(result.change_value.is_some());
+        assert_eq!(
+            result.estimated_fee,
+            original_fee
+                + calculate_fee(total_weight, INCREMENTAL_RELAY_FEERATE)
+                + calculate_fee(options.change_weight, 0.41)
+        );
+
+        // A heavy change output is paid for too, the replacement does not fall below the target feerate
+        let options = CoinSelectionOpt {
+            target_feerate: FeeRate::from_sat_per_wu(1.0),
+            change_weight: 200,
+            min_change_value: 100,
+            ..setup_options(0)
+        };
+        let result = select_coin_feebump(&inputs, 1000, 500, &options).unwrap();
+        assert!(result.change_value.is_some());
+        let total_weight = 1000 + result.selected_weight + options.change_weight;
+        assert!(result.estimated_fee as f32 / total_weight as f32 >= 1.0);
+        assert_eq!(
+            result.selected_value,
+            result.estimated_fee - 500 + result.change_value.unwrap()
+        );
+
+        let options = CoinSelectionOpt {
+            target_feerate: FeeRate::from_sat_per_wu(10.0),
+            ..setup_options(0)
+        };
+        let result = select_coin_feebump(&inputs, original_weight, original_fee, &options);
+        assert!(matches!(result, Err(SelectionError::InsufficientFunds)));
+    }
 }
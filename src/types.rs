@@ -1,3 +1,6 @@
+use crate::utils::validate_feerate;
+use alloc::vec::Vec;
+use core::{cmp::Ordering, fmt, ops::Deref};
 /// Represents an input candidate for Coinselection, either as a single UTXO or a group of UTXOs.
 ///
 /// A [`OutputGroup`] can be a single UTXO or a group that should be spent together.
@@ -5,6 +8,7 @@
 /// In the UTXO model the output of a transaction is used as the input for the new transaction and hence the name [`OutputGroup`]
 /// The library user must craft this structure correctly, as incorrect representation can lead to incorrect selection results.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct OutputGroup {
     /// Total value of the UTXO(s) that this [`WeightedValue`] represents.
     pub value: u64,
@@ -20,21 +24,177 @@ pub struct OutputGroup {
     /// Set to `None` if FIFO selection is not required. Sequence numbers are arbitrary indices that denote the relative age of a UTXO group among a set of groups.
     /// To denote the oldest UTXO group, assign it a sequence number of `Some(0)`.
     pub creation_sequence: Option<u32>,
+    /// Total fee paid by the unconfirmed ancestors of the UTXO(s), set to `0` if they are confirmed.
+    pub ancestor_fee: u64,
+    /// Total weight of the unconfirmed ancestors of the UTXO(s), set to `0` if they are confirmed.
+    ///
+    /// Spending an unconfirmed UTXO makes the transaction pay for its ancestors too: the fee lifting them to the
+    /// target feerate, on top of the `ancestor_fee` they already pay, is deducted from the value of the group.
+    pub ancestor_weight: u64,
+    /// Height of the block confirming the UTXO(s), the oldest one for a group, or `None` if unconfirmed or unknown.
+    ///
+    /// Used by FIFO selection, for the groups without a `creation_sequence`, so that wallets can pass the heights
+    /// reported by `listunspent` rather than inventing sequence numbers. `select_coin_two_phase` spends the groups
+    /// without a height last.
+    pub block_height: Option<u32>,
+}
+
+/// An input candidate for selection, letting wallets pass their own UTXO types to the selection algorithms.
+///
+/// Implemented for [`OutputGroup`], see its fields for the meaning of each method. The indices returned in
+/// [`SelectionOutput::selected_inputs`] refer to positions in the slice of candidates.
+pub trait WeightedUtxo {
+    /// Total value of the UTXO(s).
+    fn value(&self) -> u64;
+    /// Total weight of including the UTXO(s) in the transaction.
+    fn weight(&self) -> u64;
+    /// The total number of inputs.
+    fn input_count(&self) -> usize;
+    /// The relative creation sequence, used only for FIFO selection.
+    fn creation_sequence(&self) -> Option<u32>;
+    /// Total fee paid by the unconfirmed ancestors, `0` unless overridden.
+    fn ancestor_fee(&self) -> u64 {
+        0
+    }
+    /// Total weight of the unconfirmed ancestors, `0` unless overridden.
+    fn ancestor_weight(&self) -> u64 {
+        0
+    }
+    /// The height of the confirming block, `None` if unconfirmed, used by FIFO and two phase selection. `None` unless
+    /// overridden.
+    fn block_height(&self) -> Option<u32> {
+        None
+    }
+}
+
+impl WeightedUtxo for OutputGroup {
+    fn value(&self) -> u64 {
+        self.value
+    }
+
+    fn weight(&self) -> u64 {
+        self.weight
+    }
+
+    fn input_count(&self) -> usize {
+        self.input_count
+    }
+
+    fn creation_sequence(&self) -> Option<u32> {
+        self.creation_sequence
+    }
+
+    fn ancestor_fee(&self) -> u64 {
+        self.ancestor_fee
+    }
+
+    fn ancestor_weight(&self) -> u64 {
+        self.ancestor_weight
+    }
+
+    fn block_height(&self) -> Option<u32> {
+        self.block_height
+    }
+}
+
+impl<T: WeightedUtxo> WeightedUtxo for &T {
+    fn value(&self) -> u64 {
+        (*self).value()
+    }
+
+    fn weight(&self) -> u64 {
+        (*self).weight()
+    }
+
+    fn input_count(&self) -> usize {
+        (*self).input_count()
+    }
+
+    fn creation_sequence(&self) -> Option<u32> {
+        (*self).creation_sequence()
+    }
+
+    fn ancestor_fee(&self) -> u64 {
+        (*self).ancestor_fee()
+    }
+
+    fn ancestor_weight(&self) -> u64 {
+        (*self).ancestor_weight()
+    }
+
+    fn block_height(&self) -> Option<u32> {
+        (*self).block_height()
+    }
+}
+
+/// A feerate in sats per weight unit, the unit every fee computation of the crate works in.
+///
+/// Wallets and fee estimators usually quote feerates in sats per vbyte, four times the rate per weight unit. Taking
+/// a `FeeRate` rather than a bare `f32` makes the unit explicit at the call site.
+///
+/// ```
+/// use rust_coinselect::types::FeeRate;
+///
+/// let feerate = FeeRate::from_sat_per_vb(10.0);
+/// assert_eq!(feerate.as_sat_per_wu(), 2.5);
+/// assert_eq!(*feerate, 2.5);
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, PartialOrd)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct FeeRate(f32);
+
+impl FeeRate {
+    /// Creates a feerate of `sat_per_wu` sats per weight unit.
+    pub const fn from_sat_per_wu(sat_per_wu: f32) -> FeeRate {
+        FeeRate(sat_per_wu)
+    }
+
+    /// Creates a feerate of `sat_per_vb` sats per vbyte, a vbyte being four weight units.
+    pub fn from_sat_per_vb(sat_per_vb: f32) -> FeeRate {
+        FeeRate(sat_per_vb / 4.0)
+    }
+
+    /// Returns the feerate in sats per weight unit.
+    pub const fn as_sat_per_wu(self) -> f32 {
+        self.0
+    }
+
+    /// Returns the feerate in sats per vbyte.
+    pub fn as_sat_per_vb(self) -> f32 {
+        self.0 * 4.0
+    }
+}
+
+/// Dereferences to the feerate in sats per weight unit.
+impl Deref for FeeRate {
+    type Target = f32;
+
+    fn deref(&self) -> &f32 {
+        &self.0
+    }
 }
 
 /// Options required to compute fees and waste metric.
+///
+/// Every selection API borrows the options. They are deliberately not `Copy`, so variants are derived explicitly
+/// with struct update syntax from a `clone()`.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CoinSelectionOpt {
     /// The value we need to select.
     pub target_value: u64,
 
-    /// The target feerate we should try and achieve in sats per weight unit.
-    pub target_feerate: f32,
+    /// The target feerate we should try and achieve.
+    pub target_feerate: FeeRate,
 
     /// The long term fee-rate is an estimate of the future transaction fee rate that a wallet might need to pay to spend its UTXOs.
     /// If the current fee rates are less than the long term fee rate, it is optimal to consolidate UTXOs to make the spend.
     /// It affects how the [`WasteMetric`] is computed.
-    pub long_term_feerate: Option<f32>,
+    pub long_term_feerate: Option<FeeRate>,
 
     /// Lowest possible transaction fee required to get a transaction included in a block
     pub min_absolute_fee: u64,
@@ -51,6 +211,7 @@ pub struct CoinSelectionOpt {
 
     /// Total cost associated with creating and later spending a change output in a transaction.
     /// This includes the transaction fees for both the current transaction (where the change is created) and the future transaction (where the change is spent)
+    /// It also bounds the excess a changeless Branch and Bound match may drop to fees.
     pub change_cost: u64,
 
     /// Estimate of average weight of an input.
@@ -64,14 +225,261 @@ pub struct CoinSelectionOpt {
 
     /// Strategy to use the excess value other than fee and target
     pub excess_strategy: ExcessStrategy,
+
+    /// The largest number of inputs a selection may spend, counting the `input_count` of every selected [`OutputGroup`].
+    ///
+    /// Useful to stay below the standardness weight limit or the signing limits of a hardware wallet. `None` for no limit.
+    pub max_inputs: Option<usize>,
+
+    /// The largest number of nodes the Branch and Bound search visits before giving up.
+    ///
+    /// Lower it for latency-sensitive callers on large pools, raise it to wait longer for a changeless match.
+    /// `None` for [`BNB_TOTAL_TRIES`](crate::algorithms::bnb::BNB_TOTAL_TRIES).
+    pub bnb_tries: Option<u32>,
+
+    /// Whether inputs costing more to spend at the `target_feerate` than they are worth may be selected.
+    ///
+    /// Such inputs are skipped by default, as they only add fees. Set it to sweep them on purpose, e.g. to consolidate
+    /// dust while fees are low.
+    pub include_uneconomical: bool,
+
+    /// The largest excess a changeless Branch and Bound match may drop to the fee, on top of the fee at the
+    /// `target_feerate`.
+    ///
+    /// Matches are otherwise accepted with up to `change_cost` of excess, which can be a sizeable overpayment when
+    /// the change is expensive. `None` to only bound the excess by `change_cost`.
+    pub max_excess: Option<u64>,
+
+    /// The largest weight of the transaction, in weight units, counting the `base_weight`, the selected inputs and
+    /// the `change_weight`.
+    ///
+    /// Selections going over it are rejected by every algorithm. Set it to Bitcoin Core's standardness limit of
+    /// [`MAX_STANDARD_TX_WEIGHT`](crate::utils::MAX_STANDARD_TX_WEIGHT) to keep sweeps of many small inputs
+    /// relayable. `None` for no limit.
+    pub max_tx_weight: Option<u64>,
+
+    /// The number of randomized passes the Knapsack algorithm makes to approximate the best subset.
+    ///
+    /// Lower it for latency-sensitive callers, raise it for a better approximation on pathological pools.
+    /// `None` for [`KNAPSACK_TOTAL_ITERATIONS`](crate::algorithms::knapsack::KNAPSACK_TOTAL_ITERATIONS).
+    pub knapsack_iterations: Option<u32>,
+
+    /// The number of consecutive randomized passes of the Knapsack algorithm without a better subset after which it
+    /// stops early.
+    ///
+    /// Stopping early draws fewer random numbers, so with the default a seeded RNG may select a different set than
+    /// making every pass would. Set it to `u32::MAX` to always make every pass of `knapsack_iterations`, which
+    /// reproduces those selections. `None` for
+    /// [`KNAPSACK_STAGNATION_ITERATIONS`](crate::algorithms::knapsack::KNAPSACK_STAGNATION_ITERATIONS).
+    pub knapsack_stagnation: Option<u32>,
+}
+
+impl CoinSelectionOpt {
+    /// Returns a [`CoinSelectionOptBuilder`] with default values.
+    pub fn builder() -> CoinSelectionOptBuilder {
+        CoinSelectionOptBuilder::default()
+    }
+
+    /// Checks the options before any selection, as [`select_coin`](crate::selectcoin::select_coin) does.
+    ///
+    /// Returns the error of [`validate_feerate`] if the `target_feerate` is invalid, and `InvalidOptions` if the
+    /// `target_value` is zero, as there is nothing to select for.
+    pub fn validate(&self) -> Result<(), SelectionError> {
+        validate_feerate(*self.target_feerate)?;
+        if self.target_value == 0 {
+            return Err(SelectionError::InvalidOptions);
+        }
+        Ok(())
+    }
+}
+
+/// Builder for [`CoinSelectionOpt`].
+///
+/// Every value defaults to zero, `long_term_feerate`, `max_inputs`, `bnb_tries`, `max_excess`, `max_tx_weight`,
+/// `knapsack_iterations` and `knapsack_stagnation` to `None`, `include_uneconomical` to `false` and
+/// `excess_strategy` to [`ExcessStrategy::ToChange`].
+/// The `target_feerate` has no sensible default and must be set to a positive value, or [`build`](Self::build) fails.
+#[derive(Debug, Clone)]
+pub struct CoinSelectionOptBuilder {
+    options: CoinSelectionOpt,
+}
+
+impl Default for CoinSelectionOptBuilder {
+    fn default() -> Self {
+        CoinSelectionOptBuilder {
+            options: CoinSelectionOpt {
+                target_value: 0,
+                target_feerate: FeeRate::default(),
+                long_term_feerate: None,
+                min_absolute_fee: 0,
+                base_weight: 0,
+                change_weight: 0,
+                change_cost: 0,
+                avg_input_weight: 0,
+                avg_output_weight: 0,
+                min_change_value: 0,
+                excess_strategy: ExcessStrategy::ToChange,
+                max_inputs: None,
+                bnb_tries: None,
+                include_uneconomical: false,
+                max_excess: None,
+                max_tx_weight: None,
+                knapsack_iterations: None,
+                knapsack_stagnation: None,
+            },
+        }
+    }
+}
+
+impl CoinSelectionOptBuilder {
+    pub fn with_target_value(mut self, target_value: u64) -> Self {
+        self.options.target_value = target_value;
+        self
+    }
+
+    pub fn with_target_feerate(mut self, target_feerate: FeeRate) -> Self {
+        self.options.target_feerate = target_feerate;
+        self
+    }
+
+    pub fn with_long_term_feerate(mut self, long_term_feerate: FeeRate) -> Self {
+        self.options.long_term_feerate = Some(long_term_feerate);
+        self
+    }
+
+    pub fn with_min_absolute_fee(mut self, min_absolute_fee: u64) -> Self {
+        self.options.min_absolute_fee = min_absolute_fee;
+        self
+    }
+
+    pub fn with_base_weight(mut self, base_weight: u64) -> Self {
+        self.options.base_weight = base_weight;
+        self
+    }
+
+    pub fn with_change_weight(mut self, change_weight: u64) -> Self {
+        self.options.change_weight = change_weight;
+        self
+    }
+
+    pub fn with_change_cost(mut self, change_cost: u64) -> Self {
+        self.options.change_cost = change_cost;
+        self
+    }
+
+    pub fn with_avg_input_weight(mut self, avg_input_weight: u64) -> Self {
+        self.options.avg_input_weight = avg_input_weight;
+        self
+    }
+
+    pub fn with_avg_output_weight(mut self, avg_output_weight: u64) -> Self {
+        self.options.avg_output_weight = avg_output_weight;
+        self
+    }
+
+    pub fn with_min_change_value(mut self, min_change_value: u64) -> Self {
+        self.options.min_change_value = min_change_value;
+        self
+    }
+
+    pub fn with_excess_strategy(mut self, excess_strategy: ExcessStrategy) -> Self {
+        self.options.excess_strategy = excess_strategy;
+        self
+    }
+
+    pub fn with_max_inputs(mut self, max_inputs: usize) -> Self {
+        self.options.max_inputs = Some(max_inputs);
+        self
+    }
+
+    pub fn with_bnb_tries(mut self, bnb_tries: u32) -> Self {
+        self.options.bnb_tries = Some(bnb_tries);
+        self
+    }
+
+    pub fn with_include_uneconomical(mut self, include_uneconomical: bool) -> Self {
+        self.options.include_uneconomical = include_uneconomical;
+        self
+    }
+
+    pub fn with_max_excess(mut self, max_excess: u64) -> Self {
+        self.options.max_excess = Some(max_excess);
+        self
+    }
+
+    pub fn with_max_tx_weight(mut self, max_tx_weight: u64) -> Self {
+        self.options.max_tx_weight = Some(max_tx_weight);
+        self
+    }
+
+    pub fn with_knapsack_iterations(mut self, knapsack_iterations: u32) -> Self {
+        self.options.knapsack_iterations = Some(knapsack_iterations);
+        self
+    }
+
+    pub fn with_knapsack_stagnation(mut self, knapsack_stagnation: u32) -> Self {
+        self.options.knapsack_stagnation = Some(knapsack_stagnation);
+        self
+    }
+
+    /// Returns the [`CoinSelectionOpt`], or the error of [`validate_feerate`] if the `target_feerate` is invalid.
+    pub fn build(self) -> Result<CoinSelectionOpt, SelectionError> {
+        validate_feerate(*self.options.target_feerate)?;
+        Ok(self.options)
+    }
 }
 
 /// Strategy to decide what to do with the excess amount.
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ExcessStrategy {
     ToFee,
     ToRecipient,
     ToChange,
+    /// Like [`ToChange`](Self::ToChange), dividing the change across two outputs when it is large enough, which
+    /// keeps the wallet supplied with several UTXOs and hides which output is the payment.
+    ///
+    /// The change is split when, after paying the fee for the `change_weight` of both outputs, it still
+    /// exceeds `2 * min_change_value`, so that both halves are worth at least `min_change_value`. The waste then
+    /// counts the `change_cost` of each output. Below that threshold a single change output is created, exactly as
+    /// with [`ToChange`](Self::ToChange). See [`splits_change`](crate::utils::splits_change).
+    Split,
+}
+
+/// Coin selection algorithms run by [`select_coin`](crate::selectcoin::select_coin), used to pick a single one with
+/// [`select_coin_with`](crate::selectcoin::select_coin_with).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    Bnb,
+    Fifo,
+    LowestLarger,
+    Srd,
+    Knapsack,
+    CoinGrinder,
+    LargestFirst,
+    SmallestFirst,
+    RandomImprove,
+    BestFit,
+    WeightedRandom,
+    GreedyWaste,
+}
+
+impl Algorithm {
+    /// Every algorithm, in the order [`select_coin`](crate::selectcoin::select_coin) runs them.
+    pub const ALL: [Algorithm; 12] = [
+        Algorithm::Bnb,
+        Algorithm::Fifo,
+        Algorithm::LowestLarger,
+        Algorithm::Srd,
+        Algorithm::Knapsack,
+        Algorithm::CoinGrinder,
+        Algorithm::LargestFirst,
+        Algorithm::SmallestFirst,
+        Algorithm::RandomImprove,
+        Algorithm::BestFit,
+        Algorithm::WeightedRandom,
+        Algorithm::GreedyWaste,
+    ];
 }
 
 /// Error Describing failure of a selection attempt, on any subset of inputs.
@@ -79,6 +487,19 @@ pub enum ExcessStrategy {
 pub enum SelectionError {
     InsufficientFunds,
     NoSolutionFound,
+    NonPositiveFeeRate,
+    AbnormallyHighFeeRate,
+    /// A sum of values, weights or fees over the inputs and options does not fit in a `u64`.
+    ArithmeticOverflow,
+    /// The inputs cover the target, but not without taking the transaction over `max_tx_weight`.
+    MaxWeightExceeded,
+    /// No inputs were given to select from.
+    EmptyInputs,
+    /// The options can not describe a selection, see [`CoinSelectionOpt::validate`].
+    InvalidOptions,
+    /// The algorithm panicked. [`select_coin`](crate::selectcoin::select_coin) only returns it when every algorithm
+    /// did, the result of the others is returned otherwise.
+    AlgorithmPanicked,
 }
 
 /// Measures the efficiency of input selection in satoshis, helping evaluate algorithms based on current and long-term fee rates
@@ -87,16 +508,98 @@ pub enum SelectionError {
 /// In high fee rate environments, selecting fewer inputs reduces transaction fees.
 /// In low fee rate environments, selecting more inputs reduces overall fees.
 /// It compares various selection algorithms to find the most optimized solution, represented by the lowest [WasteMetric] value.
-#[derive(Debug)]
-pub struct WasteMetric(pub u64);
+///
+/// The waste is negative when spending the inputs now, below the long term feerate, saves more than the selection
+/// otherwise wastes, which rewards consolidation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WasteMetric(pub i64);
+
+/// The components of the [`WasteMetric`] of a selection, which add up to it, see [`WasteBreakdown::total`].
+///
+/// At most one of `change_cost` and `excess` is non zero: the excess goes either to a change output or to the fee.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WasteBreakdown {
+    /// The weight of the selected inputs times the difference between the `target_feerate` and the
+    /// `long_term_feerate`, negative when spending the inputs now is cheaper than later. Zero without a
+    /// `long_term_feerate`.
+    pub feerate_diff: i64,
+    /// The cost of creating and later spending the change outputs, zero without change.
+    pub change_cost: u64,
+    /// The value selected beyond the target and the fee, dropped to the fee for lack of a change output.
+    pub excess: u64,
+}
+
+impl WasteBreakdown {
+    /// Returns the waste, the sum of the components.
+    pub fn total(&self) -> i64 {
+        self.feerate_diff + self.change_cost as i64 + self.excess as i64
+    }
+}
 
 /// The result of selection algorithm.
-#[derive(Debug)]
+///
+/// Selections are ordered by their [`WasteMetric`] first, so the best of several is their minimum. Selections of
+/// equal waste are ordered by their remaining fields, to stay consistent with equality.
+#[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SelectionOutput {
-    /// The selected input indices, refers to the indices of the inputs Slice Reference.
+    /// The selected input indices, refers to the indices of the inputs Slice Reference, in ascending order.
     pub selected_inputs: Vec<usize>,
     /// The waste amount, for the above inputs.
     pub waste: WasteMetric,
+    /// The components of the `waste`.
+    pub waste_breakdown: WasteBreakdown,
+    /// The value of the change output: the selected value minus the `target_value`, the estimated fee, raised to
+    /// `min_absolute_fee`, and the fee for the `change_weight` of the change output at `target_feerate`.
+    ///
+    /// `None` when no change output is created, either because the `excess_strategy` is neither
+    /// [`ExcessStrategy::ToChange`] nor [`ExcessStrategy::Split`], or because the remainder is below
+    /// `min_change_value`, in which case it is dropped to the fee. When the change is split, this is the total of
+    /// both change outputs, after the fee for each of them.
+    pub change_value: Option<u64>,
+    /// The fee assumed during selection, at `target_feerate` for the `base_weight` and the selected inputs, before
+    /// raising it to `min_absolute_fee`, see [`calculate_selection_fee`](crate::utils::calculate_selection_fee).
+    pub estimated_fee: u64,
+    /// The total value of the selected inputs.
+    pub selected_value: u64,
+    /// The total weight of the selected inputs.
+    pub selected_weight: u64,
+}
+
+impl Ord for SelectionOutput {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.waste
+            .cmp(&other.waste)
+            .then_with(|| self.selected_inputs.cmp(&other.selected_inputs))
+            .then_with(|| self.waste_breakdown.cmp(&other.waste_breakdown))
+            .then_with(|| self.change_value.cmp(&other.change_value))
+            .then_with(|| self.estimated_fee.cmp(&other.estimated_fee))
+            .then_with(|| self.selected_value.cmp(&other.selected_value))
+            .then_with(|| self.selected_weight.cmp(&other.selected_weight))
+    }
+}
+
+impl PartialOrd for SelectionOutput {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Formats the selection on a single line, e.g. `inputs [0, 2], waste 10, fee 250 sats, change 500 sats`.
+impl fmt::Display for SelectionOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "inputs {:?}, waste {}, fee {} sats, ",
+            self.selected_inputs, self.waste.0, self.estimated_fee
+        )?;
+        match self.change_value {
+            Some(change_value) => write!(f, "change {} sats", change_value),
+            None => write!(f, "no change"),
+        }
+    }
 }
 
 /// EffectiveValue type alias
@@ -104,3 +607,213 @@ pub type EffectiveValue = u64;
 
 /// Weight type alias
 pub type Weight = u64;
+
+#[cfg(test)]
+mod test {
+
+    use crate::{
+        types::{
+            CoinSelectionOpt, ExcessStrategy, FeeRate, SelectionError, SelectionOutput,
+            WasteBreakdown, WasteMetric,
+        },
+        utils::calculate_fee,
+    };
+
+    #[test]
+    fn test_feerate_units() {
+        // A vbyte is four weight units
+        assert_eq!(
+            FeeRate::from_sat_per_vb(1.0),
+            FeeRate::from_sat_per_wu(0.25)
+        );
+        assert_eq!(FeeRate::from_sat_per_vb(10.0).as_sat_per_wu(), 2.5);
+        assert_eq!(FeeRate::from_sat_per_vb(3.7).as_sat_per_vb(), 3.7);
+        assert_eq!(FeeRate::from_sat_per_wu(2.5).as_sat_per_vb(), 10.0);
+        assert_eq!(*FeeRate::from_sat_per_vb(2.0), 0.5);
+        assert!(FeeRate::from_sat_per_vb(1.0) < FeeRate::from_sat_per_wu(1.0));
+
+        // A 68 vbytes P2WPKH input at 10 sat/vB
+        assert_eq!(calculate_fee(272, *FeeRate::from_sat_per_vb(10.0)), 680);
+    }
+
+    #[test]
+    fn test_builder_defaults() {
+        let options = CoinSelectionOpt::builder()
+            .with_target_value(5000)
+            .with_target_feerate(FeeRate::from_sat_per_wu(1.5))
+            .with_change_cost(10)
+            .build()
+            .unwrap();
+        assert_eq!(options.target_value, 5000);
+        assert_eq!(options.target_feerate, FeeRate::from_sat_per_wu(1.5));
+        assert_eq!(options.long_term_feerate, None);
+        assert_eq!(options.min_absolute_fee, 0);
+        assert_eq!(options.change_cost, 10);
+        assert_eq!(options.excess_strategy, ExcessStrategy::ToChange);
+        assert_eq!(options.max_inputs, None);
+        assert_eq!(options.bnb_tries, None);
+        assert!(!options.include_uneconomical);
+        assert_eq!(options.max_excess, None);
+        assert_eq!(options.max_tx_weight, None);
+        assert_eq!(options.knapsack_iterations, None);
+        assert_eq!(options.knapsack_stagnation, None);
+
+        let options = CoinSelectionOpt::builder()
+            .with_target_feerate(FeeRate::from_sat_per_wu(1.5))
+            .with_long_term_feerate(FeeRate::from_sat_per_wu(0.5))
+            .with_excess_strategy(ExcessStrategy::ToFee)
+            .with_max_inputs(3)
+            .with_bnb_tries(1000)
+            .with_include_uneconomical(true)
+            .with_max_excess(1000)
+            .with_max_tx_weight(400_000)
+            .with_knapsack_iterations(100)
+            .with_knapsack_stagnation(10)
+            .build()
+            .unwrap();
+        assert_eq!(
+            options.long_term_feerate,
+            Some(FeeRate::from_sat_per_wu(0.5))
+        );
+        assert_eq!(options.max_inputs, Some(3));
+        assert_eq!(options.bnb_tries, Some(1000));
+        assert!(options.include_uneconomical);
+        assert_eq!(options.max_excess, Some(1000));
+        assert_eq!(options.max_tx_weight, Some(400_000));
+        assert_eq!(options.knapsack_iterations, Some(100));
+        assert_eq!(options.knapsack_stagnation, Some(10));
+        assert_eq!(options.excess_strategy, ExcessStrategy::ToFee);
+    }
+
+    #[test]
+    fn test_builder_rejects_non_positive_feerate() {
+        for target_feerate in [0.0, -1.0, f32::NAN] {
+            let result = CoinSelectionOpt::builder()
+                .with_target_value(5000)
+                .with_target_feerate(FeeRate::from_sat_per_wu(target_feerate))
+                .build();
+            assert!(matches!(result, Err(SelectionError::NonPositiveFeeRate)));
+        }
+        // The target feerate has no default
+        let result = CoinSelectionOpt::builder().with_target_value(5000).build();
+        assert!(matches!(result, Err(SelectionError::NonPositiveFeeRate)));
+
+        let result = CoinSelectionOpt::builder()
+            .with_target_feerate(FeeRate::from_sat_per_wu(2000.0))
+            .build();
+        assert!(matches!(result, Err(SelectionError::AbnormallyHighFeeRate)));
+    }
+
+    #[test]
+    fn test_validate_options() {
+        let options = CoinSelectionOpt::builder()
+            .with_target_value(5000)
+            .with_target_feerate(FeeRate::from_sat_per_wu(1.5))
+            .build()
+            .unwrap();
+        assert_eq!(options.validate(), Ok(()));
+
+        for (target_feerate, expected) in [
+            (0.0, SelectionError::NonPositiveFeeRate),
+            (f32::NAN, SelectionError::NonPositiveFeeRate),
+            (2000.0, SelectionError::AbnormallyHighFeeRate),
+        ] {
+            let options = CoinSelectionOpt {
+                target_feerate: FeeRate::from_sat_per_wu(target_feerate),
+                ..options.clone()
+            };
+            assert_eq!(options.validate(), Err(expected));
+        }
+
+        // The builder accepts a zero target, there is nothing to select for though
+        let options = CoinSelectionOpt::builder()
+            .with_target_feerate(FeeRate::from_sat_per_wu(1.5))
+            .build()
+            .unwrap();
+        assert_eq!(options.validate(), Err(SelectionError::InvalidOptions));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_options_serde_round_trip() {
+        let options = CoinSelectionOpt::builder()
+            .with_target_value(5000)
+            .with_target_feerate(FeeRate::from_sat_per_wu(1.5))
+            .with_long_term_feerate(FeeRate::from_sat_per_wu(0.5))
+            .with_excess_strategy(ExcessStrategy::ToRecipient)
+            .with_max_inputs(3)
+            .build()
+            .unwrap();
+        let json = serde_json::to_string(&options).unwrap();
+        // Feerates serialize as bare numbers in sats per weight unit
+        assert!(json.contains(r#""target_feerate":1.5"#));
+        let decoded: CoinSelectionOpt = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded.target_value, 5000);
+        assert_eq!(decoded.target_feerate, FeeRate::from_sat_per_wu(1.5));
+        assert_eq!(
+            decoded.long_term_feerate,
+            Some(FeeRate::from_sat_per_wu(0.5))
+        );
+        assert_eq!(decoded.excess_strategy, ExcessStrategy::ToRecipient);
+        assert_eq!(decoded.max_inputs, Some(3));
+        assert_eq!(decoded.bnb_tries, None);
+        assert_eq!(serde_json::to_string(&decoded).unwrap(), json);
+    }
+
+    #[test]
+    fn test_selection_output_display() {
+        let mut selection = SelectionOutput {
+            selected_inputs: vec![0, 2],
+            waste: WasteMetric(-10),
+            waste_breakdown: WasteBreakdown {
+                feerate_diff: -20,
+                change_cost: 10,
+                excess: 0,
+            },
+            change_value: Some(500),
+            estimated_fee: 250,
+            selected_value: 4000,
+            selected_weight: 500,
+        };
+        assert_eq!(
+            selection.to_string(),
+            "inputs [0, 2], waste -10, fee 250 sats, change 500 sats"
+        );
+        selection.change_value = None;
+        assert_eq!(
+            selection.to_string(),
+            "inputs [0, 2], waste -10, fee 250 sats, no change"
+        );
+    }
+
+    #[test]
+    fn test_selection_output_ordering() {
+        let selection = |selected_inputs: Vec<usize>, waste: i64| SelectionOutput {
+            selected_inputs,
+            waste: WasteMetric(waste),
+            waste_breakdown: WasteBreakdown {
+                feerate_diff: waste,
+                ..Default::default()
+            },
+            change_value: None,
+            estimated_fee: 100,
+            selected_value: 5000,
+            selected_weight: 200,
+        };
+        assert!(WasteMetric(-5) < WasteMetric(3));
+
+        let mut selections = [
+            selection(vec![2], 40),
+            selection(vec![1], -10),
+            selection(vec![0], 40),
+        ];
+        assert_eq!(selections.iter().min().unwrap().selected_inputs, vec![1]);
+        selections.sort();
+        let order: Vec<Vec<usize>> = selections
+            .iter()
+            .map(|selection| selection.selected_inputs.clone())
+            .collect();
+        assert_eq!(order, vec![vec![1], vec![0], vec![2]]);
+        assert_eq!(selection(vec![0], 40), selection(vec![0], 40));
+    }
+}
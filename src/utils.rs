@@ -1,4 +1,11 @@
-use crate::types::{CoinSelectionOpt, EffectiveValue, ExcessStrategy, OutputGroup, Weight};
+use crate::types::{
+    CoinSelectionOpt, ExcessStrategy, SelectionError, WasteBreakdown, WeightedUtxo,
+};
+#[cfg(feature = "std")]
+use crate::types::{EffectiveValue, Weight};
+use alloc::vec::Vec;
+use num_traits::Float;
+#[cfg(feature = "std")]
 use std::collections::HashSet;
 
 #[inline]
@@ -7,31 +14,152 @@ pub fn calculate_waste(
     accumulated_value: u64,
     accumulated_weight: u64,
     estimated_fee: u64,
-) -> u64 {
+) -> i64 {
+    calculate_waste_detailed(
+        options,
+        accumulated_value,
+        accumulated_weight,
+        estimated_fee,
+    )
+    .total()
+}
+
+/// Computes the waste of a selection split into its components, see [`WasteBreakdown`] and [`calculate_waste`].
+#[inline]
+pub fn calculate_waste_detailed(
+    options: &CoinSelectionOpt,
+    accumulated_value: u64,
+    accumulated_weight: u64,
+    estimated_fee: u64,
+) -> WasteBreakdown {
     // waste =  weight*(target feerate - long term fee rate) + cost of change + excess
     // weight - total weight of selected inputs
     // cost of change - includes the fees paid on this transaction's change output plus the fees that will need to be paid to spend it later. If there is no change output, the cost is 0.
     // excess - refers to the difference between the sum of selected inputs and the amount we need to pay (the sum of output values and fees). There shouldn’t be any excess if there is a change output.
 
-    let mut waste: u64 = 0;
+    // The fee difference is negative below the long term feerate, spending inputs now is cheaper than later.
+    let mut waste = WasteBreakdown::default();
     if let Some(long_term_feerate) = options.long_term_feerate {
-        waste = (accumulated_weight as f32 * (options.target_feerate - long_term_feerate)).ceil()
-            as u64;
+        waste.feerate_diff = Float::ceil(
+            accumulated_weight as f64
+                * (*options.target_feerate as f64 - *long_term_feerate as f64),
+        ) as i64;
     }
-    if options.excess_strategy != ExcessStrategy::ToChange {
-        // Change is not created if excess strategy is ToFee or ToRecipient. Hence cost of change is added
-        waste += accumulated_value - (options.target_value + estimated_fee);
+    if calculate_change(options, accumulated_value, estimated_fee).is_none() {
+        // Change is not created if excess strategy is ToFee or ToRecipient, or if the excess is too small for a
+        // change output and dropped to the fee. Hence the excess is added
+        waste.excess = accumulated_value.saturating_sub(options.target_value + estimated_fee);
+    } else if splits_change(options, accumulated_value, estimated_fee) {
+        // The change is divided across two outputs, each of which costs 'change_cost'
+        waste.change_cost = 2 * options.change_cost;
     } else {
         // Change is created if excess strategy is set to ToChange. Hence 'excess' should be set to 0
-        waste += options.change_cost;
+        waste.change_cost = options.change_cost;
     }
     waste
 }
 
+/// Returns the value of the change output of a selection worth `accumulated_value`, see
+/// [`SelectionOutput::change_value`](crate::types::SelectionOutput::change_value).
+///
+/// The fee for the `change_weight` of the change output, and of the second one when the change is split, is
+/// deducted, the `estimated_fee` only covering the `base_weight` and the inputs.
+#[inline]
+pub fn calculate_change(
+    options: &CoinSelectionOpt,
+    accumulated_value: u64,
+    estimated_fee: u64,
+) -> Option<u64> {
+    if !matches!(
+        options.excess_strategy,
+        ExcessStrategy::ToChange | ExcessStrategy::Split
+    ) {
+        return None;
+    }
+    let change_fee = calculate_fee(options.change_weight, *options.target_feerate);
+    let change_outputs = if splits_change(options, accumulated_value, estimated_fee) {
+        2
+    } else {
+        1
+    };
+    accumulated_value
+        .checked_sub(
+            options.target_value
+                + estimated_fee.max(options.min_absolute_fee)
+                + change_outputs * change_fee,
+        )
+        .filter(|&change| change >= options.min_change_value)
+}
+
+/// Returns whether a selection worth `accumulated_value` pays the target and the fee without a change output.
+///
+/// The accumulative algorithms otherwise keep selecting until they also cover `min_change_value`, rejecting a pool
+/// which pays the target exactly. A selection whose excess over the target and the fee, raised to `min_absolute_fee`,
+/// is at most `change_cost` is accepted as is, the excess going where the `excess_strategy` sends it.
+#[inline]
+pub fn within_changeless_window(
+    options: &CoinSelectionOpt,
+    accumulated_value: u64,
+    estimated_fee: u64,
+) -> bool {
+    accumulated_value
+        .checked_sub(options.target_value + estimated_fee.max(options.min_absolute_fee))
+        .is_some_and(|excess| excess <= options.change_cost)
+}
+
+/// Returns whether a selection worth `accumulated_value` pays the target, the fee and a change output of at least
+/// `min_change_value`.
+///
+/// The `estimated_fee` covers the `base_weight` and the selected inputs. When the `excess_strategy` creates a change
+/// output, the fee for its `change_weight` is required on top, otherwise the change would fall short of
+/// `min_change_value` once paid for.
+#[inline]
+pub fn funds_change(
+    options: &CoinSelectionOpt,
+    accumulated_value: u64,
+    estimated_fee: u64,
+) -> bool {
+    let change_fee = if matches!(
+        options.excess_strategy,
+        ExcessStrategy::ToChange | ExcessStrategy::Split
+    ) {
+        calculate_fee(options.change_weight, *options.target_feerate)
+    } else {
+        0
+    };
+    accumulated_value
+        >= options.target_value
+            + options.min_change_value
+            + estimated_fee.max(options.min_absolute_fee)
+            + change_fee
+}
+
+/// Returns whether the change of a selection worth `accumulated_value` is divided across two outputs, see
+/// [`ExcessStrategy::Split`].
+///
+/// Only ever `true` with the `Split` strategy, when the change left after paying for the `change_weight` of both
+/// outputs exceeds `2 * min_change_value`. Otherwise a single change output, if any, is created.
+#[inline]
+pub fn splits_change(
+    options: &CoinSelectionOpt,
+    accumulated_value: u64,
+    estimated_fee: u64,
+) -> bool {
+    options.excess_strategy == ExcessStrategy::Split
+        && accumulated_value
+            .checked_sub(
+                options.target_value
+                    + estimated_fee.max(options.min_absolute_fee)
+                    + 2 * calculate_fee(options.change_weight, *options.target_feerate),
+            )
+            .is_some_and(|change| change > options.min_change_value.saturating_mul(2))
+}
+
 /// `adjusted_target` is the target value plus the estimated fee.
 ///
 /// `smaller_coins` is a slice of pairs where the `usize` refers to the index of the `OutputGroup` in the provided inputs.
 /// This slice should be sorted in descending order by the value of each `OutputGroup`, with each value being less than `adjusted_target`.
+#[cfg(feature = "std")]
 pub fn calculate_accumulated_weight(
     smaller_coins: &[(usize, EffectiveValue, Weight)],
     selected_inputs: &HashSet<usize>,
@@ -47,15 +175,148 @@ pub fn calculate_accumulated_weight(
 
 #[inline]
 pub fn calculate_fee(weight: u64, rate: f32) -> u64 {
-    (weight as f32 * rate).ceil() as u64
+    // An f32 product keeps only 24 bits of mantissa, off by several sats for heavy transactions
+    Float::ceil(weight as f64 * rate as f64) as u64
 }
 
-/// Returns the effective value of the `OutputGroup`, which is the actual value minus the estimated fee.
+/// Returns the fee of a selection of `accumulated_weight` at the `target_feerate`, paying for the `base_weight` too.
+///
+/// Every algorithm reports this fee as its `estimated_fee` and derives the waste and the change from it, so that
+/// [`select_coin`](crate::selectcoin::select_coin) ranks their selections on the same footing.
 #[inline]
-pub fn effective_value(output: &OutputGroup, feerate: f32) -> u64 {
+pub fn calculate_selection_fee(options: &CoinSelectionOpt, accumulated_weight: u64) -> u64 {
+    calculate_fee(
+        options.base_weight + accumulated_weight,
+        *options.target_feerate,
+    )
+}
+
+/// Highest `target_feerate` accepted, in sats/wu.
+///
+/// Equals Bitcoin Core's `HIGH_TX_FEE_PER_KB` of 1000 sat/vB, past which a feerate is most likely a unit mistake.
+pub const MAX_FEERATE: f32 = 250.0;
+
+/// Returns `NonPositiveFeeRate` if `feerate` is not a positive number, and `AbnormallyHighFeeRate` if it is above
+/// [`MAX_FEERATE`].
+#[inline]
+pub fn validate_feerate(feerate: f32) -> Result<(), SelectionError> {
+    // Also rejects NaN, which is not greater than zero
+    if feerate.is_nan() || feerate <= 0.0 {
+        return Err(SelectionError::NonPositiveFeeRate);
+    }
+    if feerate > MAX_FEERATE {
+        return Err(SelectionError::AbnormallyHighFeeRate);
+    }
+    Ok(())
+}
+
+/// Returns `EmptyInputs` if there are no `inputs` to select from.
+#[inline]
+pub fn validate_inputs<T>(inputs: &[T]) -> Result<(), SelectionError> {
+    if inputs.is_empty() {
+        return Err(SelectionError::EmptyInputs);
+    }
+    Ok(())
+}
+
+/// Returns `ArithmeticOverflow` if the target of a selection over the `inputs` may not fit in a `u64`.
+///
+/// The algorithms sum the `target_value`, the `min_change_value`, the fee raised to `min_absolute_fee` and the fee
+/// for the change outputs. Those sums are largest when every one of the `inputs` is selected, with the fee for
+/// their ancestors, so once they fit for all of them, none overflows for a subset.
+pub fn validate_target<T: WeightedUtxo>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+) -> Result<(), SelectionError> {
+    let feerate = *options.target_feerate;
+    let total_weight = inputs
+        .iter()
+        .try_fold(options.base_weight, |total, input| {
+            total.checked_add(input.weight())
+        })
+        .ok_or(SelectionError::ArithmeticOverflow)?;
+    let bump_fee = inputs
+        .iter()
+        .try_fold(0u64, |total, input| {
+            total.checked_add(calculate_bump_fee(input, feerate))
+        })
+        .ok_or(SelectionError::ArithmeticOverflow)?;
+    // Each of the two outputs of a split change pays for its `change_weight`
+    let change_fee = calculate_fee(options.change_weight, feerate).checked_mul(2);
+    options
+        .target_value
+        .checked_add(options.min_change_value)
+        .and_then(|target| target.checked_add(calculate_fee(total_weight, feerate)))
+        .and_then(|target| target.checked_add(options.min_absolute_fee))
+        .and_then(|target| target.checked_add(bump_fee))
+        .and_then(|target| target.checked_add(change_fee?))
+        .map(|_| ())
+        .ok_or(SelectionError::ArithmeticOverflow)
+}
+
+/// Bitcoin Core's `MAX_STANDARD_TX_WEIGHT`, the largest weight of a transaction relayed by default.
+pub const MAX_STANDARD_TX_WEIGHT: u64 = 400_000;
+
+/// Returns whether selecting inputs of `accumulated_weight` takes the transaction over the `max_tx_weight` of the
+/// options, once the `base_weight` and the `change_weight` are added.
+#[inline]
+pub fn exceeds_max_tx_weight(options: &CoinSelectionOpt, accumulated_weight: u64) -> bool {
+    options.max_tx_weight.is_some_and(|max_tx_weight| {
+        accumulated_weight
+            .saturating_add(options.base_weight)
+            .saturating_add(options.change_weight)
+            > max_tx_weight
+    })
+}
+
+/// Returns whether spending `input_count` inputs goes over the `max_inputs` of the options.
+#[inline]
+pub fn exceeds_max_inputs(options: &CoinSelectionOpt, input_count: usize) -> bool {
+    options
+        .max_inputs
+        .is_some_and(|max_inputs| input_count > max_inputs)
+}
+
+/// Returns the effective value of the `OutputGroup`, which is the actual value minus the estimated fee, and minus the
+/// fee needed to lift its unconfirmed ancestors to `feerate`.
+#[inline]
+pub fn effective_value<T: WeightedUtxo>(output: &T, feerate: f32) -> u64 {
     output
-        .value
-        .saturating_sub(calculate_fee(output.weight, feerate))
+        .value()
+        .saturating_sub(calculate_fee(output.weight(), feerate))
+        .saturating_sub(calculate_bump_fee(output, feerate))
+}
+
+/// Returns the indices of the `inputs` worth spending at the `target_feerate`, those with a positive effective value.
+///
+/// Every index is returned when `include_uneconomical` is set.
+pub fn economical_inputs<T: WeightedUtxo>(inputs: &[T], options: &CoinSelectionOpt) -> Vec<usize> {
+    (0..inputs.len())
+        .filter(|&i| {
+            options.include_uneconomical || effective_value(&inputs[i], *options.target_feerate) > 0
+        })
+        .collect()
+}
+
+/// Returns the [`economical_inputs`] along with their index in `inputs`.
+///
+/// Inputs whose fee, including the bump fee of their ancestors, equals or exceeds their value can only lower the
+/// value of a selection. Dropping them up front spares the algorithms exploring them, the indices of a selection
+/// over the survivors map back to `inputs` through the returned ones.
+pub fn filter_spendable<T: WeightedUtxo + Clone>(
+    inputs: &[T],
+    options: &CoinSelectionOpt,
+) -> Vec<(usize, T)> {
+    economical_inputs(inputs, options)
+        .into_iter()
+        .map(|index| (index, inputs[index].clone()))
+        .collect()
+}
+
+/// Returns the fee the unconfirmed ancestors of the `OutputGroup` lack to reach `feerate`, which spending it pays.
+#[inline]
+pub fn calculate_bump_fee<T: WeightedUtxo>(output: &T, feerate: f32) -> u64 {
+    calculate_fee(output.ancestor_weight(), feerate).saturating_sub(output.ancestor_fee())
 }
 
 /// Returns the weights of data in transaction other than the list of inputs that would be selected.
@@ -72,3 +333,19 @@ pub fn calculate_base_weight_btc(output_weight: u64) -> u64 {
     // Source - https://docs.rs/bitcoin/latest/src/bitcoin/blockdata/transaction.rs.html#599-602
     output_weight + 43
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_calculate_fee_precision() {
+        // The exact product of the f32 rate 3.700000047683716 is ceiled, an f32 product is off by 4 sats here
+        assert_eq!(calculate_fee(10_000_000, 3.7), 37_000_001);
+        assert_eq!(calculate_fee(10_000_001, 3.7), 37_000_005);
+        assert_eq!(calculate_fee(12_345_679, 0.3), 3_703_704);
+        // Integral products are not rounded up
+        assert_eq!(calculate_fee(10_000_000, 0.25), 2_500_000);
+        assert_eq!(calculate_fee(4_000_000, MAX_FEERATE), 1_000_000_000);
+    }
+}